@@ -0,0 +1,274 @@
+use crate::bank::DecodeError;
+use crate::header::{
+    error::{HeaderError, HeaderErrorKind},
+    AudioFormat, Header, ParseOptions,
+};
+use crate::manifest::BankManifest;
+use crate::read::{AsyncReader, Reader};
+use crate::stream::Stream;
+use std::num::NonZeroU32;
+use tap::Pipe;
+use tokio::io::AsyncRead;
+
+// The sizes of the stream headers and name table are stored within the first 24 bytes of the file header.
+const SIZES_END: usize = 24;
+
+/// An FMOD sound bank read from an asynchronous I/O stream.
+///
+/// This works like [`Bank<R>`](crate::Bank), except that data is read from a [`tokio`] [`AsyncRead`] source.
+/// The file header is read into memory, then parsed in the same way as [`Bank::new`](crate::Bank::new),
+/// so both types accept (and reject) the same sound banks.
+///
+/// Streams are read into memory with [`AsyncBank::next_stream`]. Encoding a [`Stream`] doesn't require any more reads,
+/// so it can be done on a blocking task (e.g. with `tokio::task::spawn_blocking`).
+///
+/// # Examples
+///
+/// ```no_run
+/// use fsbex::AsyncBank;
+/// use std::error::Error;
+/// use tokio::io::AsyncRead;
+///
+/// async fn count_streams<R: AsyncRead + Unpin>(source: R) -> Result<(), Box<dyn Error>> {
+///     let mut bank = AsyncBank::new(source).await?;
+///
+///     while let Some(stream) = bank.next_stream().await {
+///         let stream = stream?;
+///         println!("stream has {} channels", stream.channels());
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct AsyncBank<R> {
+    header: Header,
+    read: AsyncReader<R>,
+    index: u32,
+}
+
+impl<R: AsyncRead + Unpin> AsyncBank<R> {
+    /// Creates a new [`AsyncBank<R>`] by parsing from an asynchronous I/O stream.
+    ///
+    /// See [`Bank::new`](crate::Bank::new) for more information.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if reading or parsing of the sound bank's file header failed.
+    /// See [`DecodeError`] for more information.
+    pub async fn new(source: R) -> Result<Self, DecodeError> {
+        let mut read = AsyncReader::new(source);
+        let data = read_header_data(&mut read).await?;
+
+        let header =
+            Header::parse_until_data(&mut Reader::new(data.as_slice()), &ParseOptions::default())?;
+
+        // skip padding before the first stream
+        read.advance_to(header.data_offset)
+            .await
+            .map_err(HeaderError::factory(HeaderErrorKind::StreamData { index: 0 }))?;
+
+        Ok(Self {
+            header,
+            read,
+            index: 0,
+        })
+    }
+
+    /// Returns the audio format of streams in the sound bank.
+    #[must_use]
+    pub fn format(&self) -> AudioFormat {
+        self.header.format
+    }
+
+    /// Returns the minor version of the FSB5 file format used by the sound bank (currently 0 or 1).
+    #[must_use]
+    pub fn fsb_version(&self) -> u32 {
+        self.header.version
+    }
+
+    /// Returns the raw encoding flags from the file header.
+    /// See [`Bank::flags`](crate::Bank::flags) for more information.
+    #[must_use]
+    pub fn flags(&self) -> u32 {
+        self.header.flags
+    }
+
+    /// Returns the number of streams in the sound bank.
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn num_streams(&self) -> NonZeroU32 {
+        self.header
+            .stream_info
+            .len()
+            .pipe(u32::try_from)
+            .expect("stream count was already validated to be NonZeroU32")
+            .try_into()
+            .expect("stream count was already validated to be NonZeroU32")
+    }
+
+    /// Returns a description of the sound bank and all of its streams.
+    /// See [`BankManifest`] for more information.
+    #[must_use]
+    pub fn manifest(&self) -> BankManifest {
+        BankManifest::new(&self.header)
+    }
+
+    /// Reads the next stream into memory, or returns `None` if every stream has been read.
+    ///
+    /// Streams are read in order. After an error is returned, no more streams are read.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the stream data could not be read (e.g. because the sound bank was truncated).
+    /// See [`DecodeError`] for more information.
+    pub async fn next_stream(&mut self) -> Option<Result<Stream, DecodeError>> {
+        let index = self.index;
+        let info = self.header.stream_info.get(index as usize)?;
+        let offset = self.header.stream_offsets[index as usize];
+
+        let data = match self.read.advance_to(offset).await {
+            Ok(()) => self.read.take(info.size.get() as usize).await,
+            Err(e) => Err(e),
+        };
+
+        match data {
+            Ok(data) => {
+                self.index += 1;
+                Some(Ok(Stream::new(
+                    index,
+                    self.header.format,
+                    self.header.flags,
+                    info.clone(),
+                    data.into_boxed_slice(),
+                )))
+            }
+            Err(e) => {
+                // reading stops after the first error
+                self.index = u32::MAX;
+                Some(Err(HeaderError::new_with_source(
+                    HeaderErrorKind::StreamData { index },
+                    e,
+                )
+                .into()))
+            }
+        }
+    }
+}
+
+// Reads the bytes of the file header (stream headers and name table included), without any padding after it.
+// If the data ends early, the returned bytes are incomplete, so parsing them reports where the header was truncated.
+async fn read_header_data<R: AsyncRead + Unpin>(
+    read: &mut AsyncReader<R>,
+) -> Result<Vec<u8>, HeaderError> {
+    let to_error = |e| HeaderError::new_with_source(HeaderErrorKind::FileHeader, e);
+
+    let mut data = read.take_up_to(SIZES_END).await.map_err(to_error)?;
+
+    if data.len() < SIZES_END {
+        return Ok(data);
+    }
+
+    let le_u32 = |offset: usize| {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]) as usize
+    };
+
+    // version 0 has a larger base header than later versions
+    let base_header_size: usize = if le_u32(0x04) == 0 { 64 } else { 60 };
+    let header_size = base_header_size
+        .saturating_add(le_u32(0x0C))
+        .saturating_add(le_u32(0x10));
+
+    let rest = read
+        .take_up_to(header_size - read.position())
+        .await
+        .map_err(to_error)?;
+    data.extend_from_slice(&rest);
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::AsyncBank;
+    use crate::AudioFormat;
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    // `Waker::noop` requires a newer Rust version than the minimum supported one
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    // Byte slices are always ready to be read, so futures that only read from them complete on the first poll.
+    fn poll_ready<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        match pin!(future).poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future was not ready"),
+        }
+    }
+
+    // 2 named streams of 32 bytes each
+    fn bank_data() -> Vec<u8> {
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x02\x00\x00\x00\x10\x00\x00\x00\x10\x00\x00\x00\x40\x00\x00\x00\x02\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34);
+
+        let mut buf = Vec::from(HEADER_BASE);
+        buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+        // data offset 32
+        buf.extend_from_slice(&(STREAM_HEADER | (1 << 7)).to_le_bytes());
+        buf.extend_from_slice(b"\x08\x00\x00\x00\x0c\x00\x00\x00ab\x00\x00cd\x00\x00");
+        buf.extend_from_slice(&[1; 32]);
+        buf.extend_from_slice(&[2; 32]);
+        buf
+    }
+
+    #[test]
+    fn read_streams() {
+        let data = bank_data();
+        let mut bank = poll_ready(AsyncBank::new(data.as_slice())).unwrap();
+
+        assert_eq!(bank.format(), AudioFormat::Pcm16);
+        assert_eq!(bank.num_streams().get(), 2);
+
+        let stream = poll_ready(bank.next_stream()).unwrap().unwrap();
+        assert_eq!(stream.name(), Some("ab"));
+        assert_eq!(stream.raw_data(), [1; 32]);
+
+        let stream = poll_ready(bank.next_stream()).unwrap().unwrap();
+        assert_eq!(stream.name(), Some("cd"));
+        assert_eq!(stream.raw_data(), [2; 32]);
+
+        assert!(poll_ready(bank.next_stream()).is_none());
+    }
+
+    #[test]
+    fn report_truncated_data() {
+        let data = bank_data();
+
+        // the file header ends early
+        let result = poll_ready(AsyncBank::new(&data[..40]));
+        assert!(result.is_err_and(|e| e.is_eof()));
+
+        // the second stream ends early
+        let mut bank = poll_ready(AsyncBank::new(&data[..data.len() - 1])).unwrap();
+        assert!(poll_ready(bank.next_stream()).is_some_and(|stream| stream.is_ok()));
+        assert!(
+            poll_ready(bank.next_stream()).is_some_and(|stream| stream.is_err_and(|e| e.is_eof()))
+        );
+        assert!(poll_ready(bank.next_stream()).is_none());
+    }
+}
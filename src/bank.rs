@@ -1,11 +1,34 @@
-use crate::header::{error::HeaderError, AudioFormat, Header};
+use crate::encode::{
+    can_init_vorbis_headers, encode_combined, EncodeError, EncodeOptions, SetupHeaderCache,
+};
+use crate::header::{
+    borrow_stream_names,
+    error::{HeaderError, HeaderErrorKind},
+    AudioFormat, ChunkParseMode, Header, ParseOptions, StreamInfo,
+};
+use crate::manifest::BankManifest;
+#[cfg(feature = "metrics")]
+use crate::metrics::StreamMetric;
+use crate::name::unnamed_file_stem;
+use crate::probe::ProbeResult;
 use crate::read::{ReadError, Reader};
-use crate::stream::{LazyStream, Stream, StreamIntoIter};
+use crate::stream::{LazyStream, Stream, StreamIntoIter, StreamIter};
+#[cfg(feature = "rayon")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+#[cfg(feature = "rayon")]
+use std::io::Error as IoError;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 use std::{
+    collections::{BTreeMap, HashSet},
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
-    io::Read,
+    fs::File,
+    io::{Chain, Cursor, Read, Result as IoResult, Seek, SeekFrom, Write},
+    iter::zip,
     num::NonZeroU32,
+    path::Path,
+    time::Duration,
 };
 use tap::Pipe;
 
@@ -72,6 +95,12 @@ impl<R: Read> Bank<R> {
         Ok(Self { header, read })
     }
 
+    /// Creates a [`BankBuilder`] for configuring how a sound bank is parsed.
+    #[must_use]
+    pub fn builder() -> BankBuilder {
+        BankBuilder::new()
+    }
+
     /// Returns the audio format of streams in the sound bank.
     ///
     /// See [`AudioFormat`] for the list of known formats.
@@ -80,6 +109,38 @@ impl<R: Read> Bank<R> {
         self.header.format
     }
 
+    /// Returns the minor version of the FSB5 file format used by the sound bank (currently 0 or 1).
+    #[must_use]
+    pub fn fsb_version(&self) -> u32 {
+        self.header.version
+    }
+
+    /// Returns the raw encoding flags from the file header.
+    ///
+    /// These flags carry codec hints, such as whether PCM samples are stored in big-endian order (`0x01`).
+    /// The flags are only stored by version 1 of the file format; for version 0 sound banks, 0 is returned.
+    #[must_use]
+    pub fn flags(&self) -> u32 {
+        self.header.flags
+    }
+
+    /// Returns the offset (in bytes) from the start of the sound bank to where stream data begins.
+    ///
+    /// This accounts for any padding after the name table or before the first stream's data.
+    #[must_use]
+    pub fn data_section_start(&self) -> usize {
+        self.header.data_offset
+    }
+
+    /// Returns the raw bytes of the file header, from the start of the sound bank to the start of the stream data section.
+    ///
+    /// This covers the base header, the stream headers, and the name table.
+    /// The bytes are only kept when parsing with [`BankBuilder::capture_raw_header`]; otherwise, `None` is returned.
+    #[must_use]
+    pub fn raw_header(&self) -> Option<&[u8]> {
+        self.header.raw.as_deref()
+    }
+
     /// Returns the number of streams in the sound bank.
     #[allow(clippy::missing_panics_doc)]
     #[must_use]
@@ -93,6 +154,63 @@ impl<R: Read> Bank<R> {
             .expect("stream count was already validated to be NonZeroU32")
     }
 
+    /// Returns the number of streams with each channel count, keyed by channel count.
+    ///
+    /// All streams in a sound bank have the same audio format, but they can have different channel counts.
+    #[must_use]
+    pub fn channel_histogram(&self) -> BTreeMap<u8, usize> {
+        let mut histogram = BTreeMap::new();
+
+        for info in &*self.header.stream_info {
+            *histogram.entry(info.channels.get()).or_insert(0) += 1;
+        }
+
+        histogram
+    }
+
+    /// Returns the number of times each type of stream header chunk appears in the sound bank, keyed by chunk type flag.
+    ///
+    /// Every chunk is counted, including chunks whose contents are ignored by this library (e.g. XMA seek tables)
+    /// and chunks that were skipped with [`BankBuilder::parse_chunks`] or [`BankBuilder::skip_invalid_chunks`].
+    /// This is useful for finding out which FMOD features a sound bank uses.
+    #[must_use]
+    pub fn chunk_usage(&self) -> BTreeMap<u8, usize> {
+        let mut usage = BTreeMap::new();
+
+        for flag in self.header.stream_info.iter().flat_map(|info| &*info.chunk_flags) {
+            *usage.entry(*flag).or_insert(0) += 1;
+        }
+
+        usage
+    }
+
+    /// Returns the size (in bytes) of the data of the stream at `index`, or `None` if there is no such stream.
+    ///
+    /// This only reads metadata from the file header, so it can be used before streams are read
+    /// (e.g. to estimate progress or pre-allocate buffers).
+    #[must_use]
+    pub fn stream_size(&self, index: u32) -> Option<NonZeroU32> {
+        self.header.stream_info.get(index as usize).map(|info| info.size)
+    }
+
+    /// Returns the total duration of all streams in the sound bank, computed from their sample counts without decoding.
+    ///
+    /// See [`LazyStream::duration`] for more information.
+    #[must_use]
+    pub fn total_duration(&self) -> Duration {
+        self.header.stream_info.iter().map(StreamInfo::duration).sum()
+    }
+
+    /// Returns the number of streams in the sound bank that have loop information.
+    #[must_use]
+    pub fn looping_stream_count(&self) -> usize {
+        self.header
+            .stream_info
+            .iter()
+            .filter(|info| info.stream_loop.is_some())
+            .count()
+    }
+
     /// Sequentially reads streams from the sound bank, consuming this [`Bank<R>`].
     /// Streams can be accessed within the function `f` as they are read.
     /// See [`LazyStream`] for more information.
@@ -104,10 +222,38 @@ impl<R: Read> Bank<R> {
     /// - the underlying reader failed to advance to the next stream
     ///
     /// See [`LazyStreamError`] for more information.
-    pub fn read_streams<F, E>(mut self, f: F) -> Result<(), LazyStreamError<E>>
+    pub fn read_streams<F, E>(self, f: F) -> Result<(), LazyStreamError<E>>
     where
-        F: Fn(LazyStream<'_, R>) -> Result<(), E>,
+        F: FnMut(LazyStream<'_, R>) -> Result<(), E>,
     {
+        self.read_streams_with_progress(f, |_, _| {})
+    }
+
+    /// Sequentially reads streams from the sound bank, consuming this [`Bank<R>`].
+    ///
+    /// Streams are accessed within the function `f` in the same way as [`Bank::read_streams`].
+    /// After each stream is finished (including any of its data that `f` didn't read),
+    /// `progress` is called with the number of streams read so far and the total number of streams,
+    /// so the last call reports that every stream was read.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if:
+    /// - an error was returned from `f`
+    /// - the underlying reader failed to advance to the next stream
+    ///
+    /// See [`LazyStreamError`] for more information.
+    pub fn read_streams_with_progress<F, P, E>(
+        mut self,
+        mut f: F,
+        mut progress: P,
+    ) -> Result<(), LazyStreamError<E>>
+    where
+        F: FnMut(LazyStream<'_, R>) -> Result<(), E>,
+        P: FnMut(u32, NonZeroU32),
+    {
+        let total = self.num_streams();
+
         for (info, index) in self.header.stream_info.iter().zip(0..) {
             let size = info.size.get() as usize;
             let start_pos = self.read.position();
@@ -122,11 +268,794 @@ impl<R: Read> Bank<R> {
             .map_err(LazyStreamError::from_other(index))?;
 
             self.read
-                .advance_to(start_pos + size)
+                .advance_to_offset(start_pos, size)
                 .map_err(LazyStreamError::from_read(index))?;
+
+            progress(index + 1, total);
         }
         Ok(())
     }
+
+    /// Returns the indexes of streams whose header chunks could not be parsed.
+    ///
+    /// Invalid chunks are only skipped when parsing with [`BankBuilder::skip_invalid_chunks`];
+    /// otherwise, this is always empty. Metadata of these streams may be incomplete.
+    #[must_use]
+    pub fn streams_with_chunk_errors(&self) -> Vec<u32> {
+        self.header
+            .stream_info
+            .iter()
+            .zip(0..)
+            .filter_map(|(info, index)| info.chunk_error.then_some(index))
+            .collect()
+    }
+
+    /// Returns a description of the sound bank and all of its streams, without reading any stream data.
+    ///
+    /// See [`BankManifest`] for more information.
+    #[must_use]
+    pub fn manifest(&self) -> BankManifest {
+        BankManifest::new(&self.header)
+    }
+
+    /// Returns whether this sound bank and another sound bank have the same structure.
+    ///
+    /// Sound banks are compared by their audio format, encoding flags, and the information of every stream
+    /// (sample rate, number of channels, number of samples, loop information, name, and size).
+    /// Stream data and the underlying readers are not compared.
+    /// This is useful for checking that a sound bank survives a round trip through another tool.
+    #[must_use]
+    pub fn structurally_eq<S: Read>(&self, other: &Bank<S>) -> bool {
+        self.header.format == other.header.format
+            && self.header.flags == other.header.flags
+            && self.header.stream_info.len() == other.header.stream_info.len()
+            && zip(self.header.stream_info.iter(), other.header.stream_info.iter()).all(|(a, b)| {
+                a.sample_rate == b.sample_rate
+                    && a.channels == b.channels
+                    && a.num_samples == b.num_samples
+                    && a.stream_loop == b.stream_loop
+                    && a.name == b.name
+                    && a.size == b.size
+            })
+    }
+
+    /// Reads the data of all streams into memory, consuming this [`Bank<R>`].
+    ///
+    /// Unlike iterating over the sound bank with [`Bank::into_iter`],
+    /// all streams are returned at once, so they can be accessed in any order (e.g. in reverse).
+    /// This is useful for non-seekable sources like pipes, at the cost of holding every stream in memory.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the data of any stream could not be read.
+    pub fn into_buffered_streams(mut self) -> Result<Vec<Stream>, DecodeError> {
+        let mut streams = Vec::with_capacity(self.header.stream_info.len());
+
+        for (info, index) in self.header.stream_info.iter().zip(0..) {
+            let size = info.size.get() as usize;
+            let start_pos = self.read.position();
+
+            let data = self
+                .read
+                .take_boxed(size)
+                .map_err(HeaderError::factory(HeaderErrorKind::StreamData { index }))?;
+
+            self.read
+                .advance_to_offset(start_pos, size)
+                .map_err(HeaderError::factory(HeaderErrorKind::StreamData { index }))?;
+
+            streams.push(Stream::new(
+                index,
+                self.header.format,
+                self.header.flags,
+                info.clone(),
+                data,
+            ));
+        }
+
+        Ok(streams)
+    }
+
+    /// Writes all streams in the sound bank into a single WAVE file, consuming this [`Bank<R>`].
+    ///
+    /// Streams are written one after another.
+    /// A cue point is placed at the start of every stream, and cue points of named streams are labeled with the stream name.
+    /// Only PCM formats are supported, and all streams must have the same number of channels and sample rate.
+    ///
+    /// Seeking is needed to update sizes in the file header after all streams are written.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the streams could not be combined or successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn write_combined_wav<W: Write + Seek>(mut self, sink: W) -> Result<W, EncodeError> {
+        encode_combined(
+            self.header.format,
+            self.header.flags,
+            &self.header.stream_info,
+            &mut self.read,
+            sink,
+        )
+    }
+
+    /// Encodes every stream in the sound bank while recording how long each stream took to encode,
+    /// consuming this [`Bank<R>`].
+    ///
+    /// For every stream, `f` is called to create the writer that the stream is encoded to.
+    /// The returned [`StreamMetric`]s are in stream order.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if:
+    /// - an error was returned from `f`
+    /// - a stream could not be encoded
+    /// - the underlying reader failed to advance to the next stream
+    ///
+    /// See [`LazyStreamError`] for more information.
+    #[cfg(feature = "metrics")]
+    pub fn extract_with_metrics<F, W, E>(
+        self,
+        mut f: F,
+    ) -> Result<Vec<StreamMetric>, LazyStreamError<E>>
+    where
+        F: FnMut(&LazyStream<'_, R>) -> Result<W, E>,
+        W: Write,
+        E: From<EncodeError>,
+    {
+        let mut metrics = Vec::with_capacity(self.header.stream_info.len());
+
+        self.read_streams(|stream| {
+            let index = stream.index();
+            let sink = CountingWriter::new(f(&stream)?);
+
+            let start = Instant::now();
+            let sink = stream.write(sink)?;
+            metrics.push(StreamMetric::new(index, start.elapsed(), sink.count()));
+
+            Ok(())
+        })?;
+
+        Ok(metrics)
+    }
+
+    /// Encodes every stream in the sound bank, consuming this [`Bank<R>`].
+    ///
+    /// For every stream, `f` is called with the stream to create the writer that the stream is encoded to.
+    /// Stream data is encoded directly from the underlying reader,
+    /// so unlike iterating over the sound bank with [`Bank::into_iter`], streams are never fully read into memory.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if:
+    /// - an error was returned from `f` (see [`EncodeError::CreateWriter`])
+    /// - a stream could not be encoded
+    /// - the underlying reader failed to advance to the next stream
+    ///
+    /// See [`LazyStreamError`] for more information.
+    pub fn extract_streaming<F, W>(self, mut f: F) -> Result<(), LazyStreamError<EncodeError>>
+    where
+        F: FnMut(&LazyStream<'_, R>) -> IoResult<W>,
+        W: Write,
+    {
+        self.read_streams(|stream| {
+            let sink = f(&stream).map_err(EncodeError::CreateWriter)?;
+            stream.write(sink).map(drop)
+        })
+    }
+
+    /// Encodes every stream in the sound bank into files in a directory, consuming this [`Bank<R>`].
+    ///
+    /// Each stream is written to a file named after the stream, which is converted into a file name by `name_sanitizer`,
+    /// or `stream_{index}` if the stream has no name.
+    /// [`sanitize_name`] can be passed as `name_sanitizer` to replace characters that can't be used in file names;
+    /// a custom function must also return names that are safe to use, since they are joined to `dir` as-is.
+    /// If a file name was already used by an earlier stream (ignoring case), a numeric suffix is added (e.g. `name_1`).
+    /// PCM and ADPCM (GC, IMA, and FADPCM) streams are written as `.wav` files, Vorbis streams are written as `.ogg` files,
+    /// ATRAC9 streams are written as `.at9` files, MPEG streams are written as `.mp3` files,
+    /// xWMA streams are written as `.xwma` files, and Opus streams are written as `.opus` files.
+    /// Existing files are overwritten, and the directory must already exist.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if:
+    /// - a stream is in a format that can't be encoded
+    /// - a file could not be created
+    /// - a stream could not be encoded
+    /// - the underlying reader failed to advance to the next stream
+    ///
+    /// See [`LazyStreamError`] for more information.
+    ///
+    /// [`sanitize_name`]: crate::sanitize_name
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fsbex::{sanitize_name, Bank};
+    /// use std::{error::Error, fs::File, io::BufReader};
+    ///
+    /// fn extract(path: &str) -> Result<(), Box<dyn Error>> {
+    ///     let bank = Bank::new(BufReader::new(File::open(path)?))?;
+    ///     bank.extract_to_dir("out", sanitize_name)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn extract_to_dir<P, S>(
+        self,
+        dir: P,
+        name_sanitizer: S,
+    ) -> Result<(), LazyStreamError<EncodeError>>
+    where
+        P: AsRef<Path>,
+        S: Fn(&str) -> String,
+    {
+        self.extract_to_dir_with_progress(dir, name_sanitizer, |_, _, _| {})
+    }
+
+    /// Encodes every stream in the sound bank into files in a directory, consuming this [`Bank<R>`].
+    ///
+    /// Files are named in the same way as [`Bank::extract_to_dir`].
+    ///
+    /// After each stream is written, `progress` is called with the stream index, the file name (without extension),
+    /// and the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if:
+    /// - a stream is in a format that can't be encoded
+    /// - a file could not be created
+    /// - a stream could not be encoded
+    /// - the underlying reader failed to advance to the next stream
+    ///
+    /// See [`LazyStreamError`] for more information.
+    pub fn extract_to_dir_with_progress<P, S, F>(
+        self,
+        dir: P,
+        name_sanitizer: S,
+        mut progress: F,
+    ) -> Result<(), LazyStreamError<EncodeError>>
+    where
+        P: AsRef<Path>,
+        S: Fn(&str) -> String,
+        F: FnMut(u32, &str, u64),
+    {
+        let dir = dir.as_ref();
+        let mut used_names = HashSet::new();
+
+        self.read_streams(|stream| {
+            let index = stream.index();
+            let format = stream.format();
+            let extension =
+                file_extension(format).ok_or(EncodeError::UnsupportedFormat { format })?;
+
+            let name = stream
+                .name()
+                .map_or_else(|| unnamed_file_stem(index), &name_sanitizer);
+            let name = unique_name(&name, &mut used_names);
+            // names can contain dots, so the extension is appended rather than set with `Path::with_extension`
+            let path = dir.join(format!("{name}.{extension}"));
+
+            let file = File::create(path).map_err(EncodeError::CreateFile)?;
+            let sink = stream.write_buffered(CountingWriter::new(file))?;
+            progress(index, &name, sink.count());
+
+            Ok(())
+        })
+    }
+}
+
+// Adds a numeric suffix to the name until it differs from every name in `used_names`, then records it.
+// Names are compared without case, since file systems on Windows and macOS are case-insensitive by default.
+fn unique_name(name: &str, used_names: &mut HashSet<String>) -> String {
+    let mut unique = name.to_owned();
+    let mut suffix = 1;
+
+    while !used_names.insert(unique.to_lowercase()) {
+        unique = format!("{name}_{suffix}");
+        suffix += 1;
+    }
+
+    unique
+}
+
+// Returns the extension of files that streams in a format are encoded to, or `None` if the format can't be encoded.
+fn file_extension(format: AudioFormat) -> Option<&'static str> {
+    match format {
+        AudioFormat::Pcm8
+        | AudioFormat::Pcm16
+        | AudioFormat::Pcm24
+        | AudioFormat::Pcm32
+        | AudioFormat::PcmFloat
+        | AudioFormat::GcAdpcm
+        | AudioFormat::ImaAdpcm
+        | AudioFormat::FAdpcm
+        | AudioFormat::Vorbis
+        | AudioFormat::Atrac9
+        | AudioFormat::Mpeg
+        | AudioFormat::Xwma
+        | AudioFormat::Opus => Some(format.recommended_extension()),
+        _ => None,
+    }
+}
+
+// counts the number of bytes successfully written to the inner writer
+pub(crate) struct CountingWriter<W: Write> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read + Seek> Bank<R> {
+    /// Parses the next sound bank in the I/O stream, consuming this [`Bank<R>`].
+    ///
+    /// Some files contain multiple sound banks stored back-to-back.
+    /// This seeks past the end of the current sound bank (its file header and stream data, rounded up to 32 bytes)
+    /// and parses another sound bank from that position.
+    /// The next sound bank is parsed with default options, like [`Bank::new`].
+    ///
+    /// `None` is returned if the end of the I/O stream was reached.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fsbex::Bank;
+    /// use std::{error::Error, io::Cursor};
+    ///
+    /// fn count_banks(bytes: &[u8]) -> Result<usize, Box<dyn Error>> {
+    ///     let mut bank = Bank::new(Cursor::new(bytes))?;
+    ///     let mut count = 1;
+    ///
+    ///     while let Some(next) = bank.next_bank() {
+    ///         bank = next?;
+    ///         count += 1;
+    ///     }
+    ///
+    ///     Ok(count)
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if:
+    /// - the underlying reader is already past the end of this sound bank
+    /// - seeking failed
+    /// - parsing of the next sound bank's file header failed
+    ///
+    /// See [`DecodeError`] for more information.
+    pub fn next_bank(self) -> Option<Result<Self, DecodeError>> {
+        let position = self.read.position();
+        let mut source = self.read.into_inner();
+
+        // The reader can only be past the end of the sound bank if the file header was inconsistent,
+        // which is reported instead of being mistaken for the end of the I/O stream.
+        let Some(remaining) = self
+            .header
+            .bank_size
+            .checked_next_multiple_of(32)
+            .and_then(|bank_end| bank_end.checked_sub(position))
+            .and_then(|n| i64::try_from(n).ok())
+        else {
+            return Some(Err(HeaderError::new(HeaderErrorKind::NextBank).into()));
+        };
+
+        let next_position = match source.seek(SeekFrom::Current(remaining)) {
+            Ok(n) => n,
+            Err(e) => {
+                return Some(Err(HeaderError::new_with_io(HeaderErrorKind::NextBank, e).into()))
+            }
+        };
+
+        match source.seek(SeekFrom::End(0)) {
+            Ok(end) if end <= next_position => return None,
+            Ok(_) => {}
+            Err(e) => {
+                return Some(Err(HeaderError::new_with_io(HeaderErrorKind::NextBank, e).into()))
+            }
+        }
+
+        if let Err(e) = source.seek(SeekFrom::Start(next_position)) {
+            return Some(Err(HeaderError::new_with_io(HeaderErrorKind::NextBank, e).into()));
+        }
+
+        Some(Self::new(source))
+    }
+
+    /// Returns the stream at `index` without reading the streams before it,
+    /// or `None` if there is no such stream or seeking to the stream failed.
+    ///
+    /// The offset of every stream is calculated when parsing the file header,
+    /// so the underlying reader seeks directly to the start of the stream.
+    /// See [`LazyStream`] for more information.
+    ///
+    /// Sequentially reading streams afterwards (e.g. with [`Bank::read_streams`]) starts from wherever the reader was left,
+    /// so it should not be mixed with this method.
+    pub fn stream_at(&mut self, index: u32) -> Option<LazyStream<'_, R>> {
+        let info = self.header.stream_info.get(index as usize)?;
+        let offset = self.header.stream_offsets[index as usize];
+
+        self.read.seek_to(offset).ok()?;
+
+        Some(LazyStream::new(
+            index,
+            self.header.format,
+            self.header.flags,
+            info,
+            &mut self.read,
+        ))
+    }
+
+    /// Returns an iterator over the streams in the sound bank without consuming this [`Bank<R>`].
+    ///
+    /// Unlike [`Bank::into_iter`], the sound bank can still be used after iteration (e.g. to iterate again).
+    /// Streams are read into memory as they are returned, and the underlying reader seeks to the start of every stream,
+    /// so iteration is not affected by streams that were previously read.
+    /// See [`StreamIter`] for more information.
+    pub fn iter(&mut self) -> StreamIter<'_, R> {
+        StreamIter::new(
+            self.header.format,
+            self.header.flags,
+            &self.header.stream_info,
+            &self.header.stream_offsets,
+            &mut self.read,
+        )
+    }
+
+    /// Reads the start of the first stream to check whether the sound bank is likely to be extracted successfully.
+    ///
+    /// Up to 4 KiB of stream data is read from the start of the first stream, wherever the underlying reader is,
+    /// then the underlying reader seeks back to its previous position, so streams can still be read afterwards.
+    /// For Vorbis streams, the headers needed for decoding are also reconstructed.
+    /// This is a cheap check to run before extracting an entire sound bank.
+    /// See [`ProbeResult`] for more information.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the stream data could not be read or if seeking back to the previous position failed.
+    /// See [`DecodeError`] for more information.
+    pub fn probe_first_stream(&mut self) -> Result<ProbeResult, DecodeError> {
+        self.probe_first_stream_with(&EncodeOptions::default())
+    }
+
+    /// Reads the start of the first stream like [`Bank::probe_first_stream`],
+    /// but reconstructs Vorbis headers with the setup header CRC32 override and setup headers in `options`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the stream data could not be read or if seeking back to the previous position failed.
+    /// See [`DecodeError`] for more information.
+    pub fn probe_first_stream_with(
+        &mut self,
+        options: &EncodeOptions,
+    ) -> Result<ProbeResult, DecodeError> {
+        self.probe_cached(options, &mut SetupHeaderCache::default())
+    }
+
+    // Probes the first stream like `probe_first_stream_with`, taking Vorbis setup headers from `cache`.
+    pub(crate) fn probe_cached(
+        &mut self,
+        options: &EncodeOptions,
+        cache: &mut SetupHeaderCache,
+    ) -> Result<ProbeResult, DecodeError> {
+        const PROBE_SIZE: usize = 4096;
+
+        let format = self.header.format;
+        let info = &self.header.stream_info[0];
+        let start_pos = self.read.position();
+
+        self.read
+            .seek_to(self.header.stream_offsets[0])
+            .map_err(|e| HeaderError::new_with_io(HeaderErrorKind::StreamData { index: 0 }, e))?;
+        let prefix = self.read.take((info.size.get() as usize).min(PROBE_SIZE));
+        self.read
+            .seek_to(start_pos)
+            .map_err(|e| HeaderError::new_with_io(HeaderErrorKind::Rewind, e))?;
+        let prefix =
+            prefix.map_err(HeaderError::factory(HeaderErrorKind::StreamData { index: 0 }))?;
+
+        let setup_header_found =
+            (format == AudioFormat::Vorbis).then(|| can_init_vorbis_headers(info, options, cache));
+
+        Ok(ProbeResult::new(
+            format,
+            file_extension(format).is_some(),
+            prefix.len(),
+            setup_header_found,
+        ))
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<R: Read> Bank<R> {
+    /// Reads streams from the sound bank in parallel, using the global [`rayon`] thread pool.
+    /// Streams can be accessed within the function `f` as they are read; [`LazyStream::index`] identifies each stream.
+    ///
+    /// The offset of every stream is calculated when parsing the file header,
+    /// so each thread calls `open` once to create its own reader, then seeks directly to the start of every stream it reads.
+    /// Readers returned from `open` must start at the beginning of the sound bank (e.g. by opening the file again),
+    /// and must not share a cursor with each other: readers such as clones of `&File` move each other's position,
+    /// so threads would read the wrong data.
+    /// Streams are not read in any particular order. This is useful for extracting large sound banks,
+    /// where encoding streams one at a time (e.g. with [`Bank::read_streams`]) leaves most of the CPU idle.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if:
+    /// - an error was returned from `f`
+    /// - an error was returned from `open`
+    /// - a reader failed to seek to a stream or to read the rest of a stream
+    ///
+    /// If multiple streams fail, the error of any one of them is returned, and streams that haven't started are skipped.
+    /// See [`LazyStreamError`] for more information.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fsbex::Bank;
+    /// use std::{error::Error, fs::File, io::BufReader};
+    ///
+    /// fn extract_in_parallel(path: &str) -> Result<(), Box<dyn Error>> {
+    ///     let bank = Bank::new(BufReader::new(File::open(path)?))?;
+    ///
+    ///     bank.par_read_streams(
+    ///         || File::open(path).map(BufReader::new),
+    ///         |stream| {
+    ///             let path = format!("{}.wav", stream.index());
+    ///             stream.write_to_path(path)
+    ///         },
+    ///     )?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn par_read_streams<O, S, F, E>(&self, open: O, f: F) -> Result<(), LazyStreamError<E>>
+    where
+        O: Fn() -> IoResult<S> + Send + Sync,
+        S: Read + Seek,
+        F: Fn(LazyStream<'_, S>) -> Result<(), E> + Send + Sync,
+        E: Send,
+    {
+        let header = &self.header;
+
+        (0..self.num_streams().get()).into_par_iter().try_for_each_init(
+            || None,
+            |read: &mut Option<Reader<S>>, index| {
+                let info = &header.stream_info[index as usize];
+                let offset = header.stream_offsets[index as usize];
+
+                let read = match read {
+                    Some(read) => read,
+                    None => {
+                        read.insert(Reader::new(open().map_err(LazyStreamError::from_open(index))?))
+                    }
+                };
+                read.seek_to(offset).map_err(LazyStreamError::from_seek(index))?;
+
+                f(LazyStream::new(index, header.format, header.flags, info, read))
+                    .map_err(LazyStreamError::from_other(index))?;
+
+                // like `read_streams`, a stream that ends early is reported even if `f` didn't read all of it
+                read.advance_to_offset(offset, info.size.get() as usize)
+                    .map_err(LazyStreamError::from_read(index))
+            },
+        )
+    }
+}
+
+impl<R: Read> Bank<Chain<Cursor<Vec<u8>>, R>> {
+    /// Creates a new [`Bank`] from bytes that were already read from an I/O stream, followed by the rest of the stream.
+    ///
+    /// This is useful when the start of a non-seekable source was already read (e.g. to detect the file type),
+    /// since the bytes in `prefix` are not read again from `rest`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fsbex::Bank;
+    /// use std::{
+    ///     error::Error,
+    ///     io::{Chain, Cursor, Read},
+    /// };
+    ///
+    /// fn read_after_sniffing<R: Read>(
+    ///     mut source: R,
+    /// ) -> Result<Bank<Chain<Cursor<Vec<u8>>, R>>, Box<dyn Error>> {
+    ///     let mut magic = vec![0; 4];
+    ///     source.read_exact(&mut magic)?;
+    ///     if magic != b"FSB5" {
+    ///         return Err("not an FSB5 file".into());
+    ///     }
+    ///
+    ///     let bank = Bank::new_with_prefix(magic, source)?;
+    ///     Ok(bank)
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if parsing of the sound bank's file header failed.
+    /// See [`DecodeError`] for more information.
+    pub fn new_with_prefix(prefix: Vec<u8>, rest: R) -> Result<Self, DecodeError> {
+        Self::new(Cursor::new(prefix).chain(rest))
+    }
+}
+
+impl<'data> Bank<&'data [u8]> {
+    /// Parses the file header of a sound bank stored in memory, returning the names of all streams in order.
+    ///
+    /// Unlike [`Bank::new`], names are not copied: they borrow from `data`, and are validated once while parsing.
+    /// This is useful for tools that only need stream names from many sound banks (e.g. memory-mapped files),
+    /// since no allocations are made per name. Streams without names are returned as `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fsbex::Bank;
+    /// use std::error::Error;
+    ///
+    /// fn list_names(bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    ///     for name in Bank::borrowed_stream_names(bytes)?.iter().flatten() {
+    ///         println!("{name}");
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if parsing of the sound bank's file header failed.
+    /// See [`DecodeError`] for more information.
+    pub fn borrowed_stream_names(
+        data: &'data [u8],
+    ) -> Result<Box<[Option<&'data str>]>, DecodeError> {
+        Ok(borrow_stream_names(data)?)
+    }
+}
+
+/// A builder for parsing a [`Bank`] with non-default options.
+///
+/// All options default to the same behavior as [`Bank::new`].
+///
+/// # Examples
+///
+/// ```
+/// use fsbex::{Bank, BankBuilder};
+/// use std::error::Error;
+///
+/// fn read_newer_bank(bytes: &[u8]) -> Result<Bank<&[u8]>, Box<dyn Error>> {
+///     let bank = BankBuilder::new()
+///         .treat_unknown_format_as_raw(true)
+///         .build(bytes)?;
+///     Ok(bank)
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BankBuilder {
+    options: ParseOptions,
+}
+
+impl BankBuilder {
+    /// Creates a new [`BankBuilder`] with default options.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether an unrecognized audio format flag is accepted instead of failing to parse.
+    ///
+    /// When enabled, the format is reported as [`AudioFormat::Unknown`].
+    /// Streams can then be extracted as raw bytes, but encoding them returns an error.
+    /// This is disabled by default.
+    pub fn treat_unknown_format_as_raw(&mut self, value: bool) -> &mut Self {
+        self.options.unknown_format_as_raw = value;
+        self
+    }
+
+    /// Sets whether stream data must be 32-byte aligned.
+    ///
+    /// Stream data offsets are stored as multiples of 32, so every stream in a well-formed sound bank is 32-byte aligned.
+    /// When enabled, parsing fails if the stream data section doesn't start right after the file header at an aligned offset,
+    /// or if the first stream doesn't start at the beginning of the stream data section.
+    /// This is disabled by default.
+    pub fn check_alignment(&mut self, value: bool) -> &mut Self {
+        self.options.strict_alignment = value;
+        self
+    }
+
+    /// Sets which metadata is parsed from stream header chunks.
+    /// See [`ChunkParseMode`] for more information.
+    pub fn parse_chunks(&mut self, mode: ChunkParseMode) -> &mut Self {
+        self.options.parse_chunks = mode;
+        self
+    }
+
+    /// Sets whether invalid stream header chunks are skipped instead of failing to parse.
+    ///
+    /// By default, a single invalid chunk causes parsing of the entire sound bank to fail.
+    /// When enabled, invalid chunks are skipped using the chunk size, and parsing continues with the next chunk.
+    /// Metadata of affected streams may be incomplete; see [`Bank::streams_with_chunk_errors`].
+    /// This is useful for salvaging streams from damaged sound banks.
+    pub fn skip_invalid_chunks(&mut self, value: bool) -> &mut Self {
+        self.options.skip_invalid_chunks = value;
+        self
+    }
+
+    /// Sets whether stream names with invalid UTF-8 are accepted instead of failing to parse.
+    ///
+    /// By default, a single stream name that isn't valid UTF-8 causes parsing of the entire sound bank to fail.
+    /// Some sound banks contain names in other encodings (e.g. Shift-JIS);
+    /// when enabled, invalid sequences in names are replaced with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn lossy_names(&mut self, value: bool) -> &mut Self {
+        self.options.lossy_names = value;
+        self
+    }
+
+    /// Sets whether streams without samples are allowed.
+    ///
+    /// By default, a stream with a sample count of 0 causes parsing of the entire sound bank to fail.
+    /// Some sound banks contain such streams as event markers or placeholders, and these banks need this option to be parsed at all.
+    /// When enabled, zero-sample streams are parsed like other streams, and their sample count is reported as `None`
+    /// (e.g. by [`LazyStream::sample_count`]). Their data can still be read,
+    /// so callers that want to skip them can check the sample count.
+    ///
+    /// A stream whose data is 0 bytes long is still rejected, even when this option is enabled.
+    pub fn allow_zero_samples(&mut self, value: bool) -> &mut Self {
+        self.options.allow_zero_samples = value;
+        self
+    }
+
+    /// Sets whether the raw bytes of the file header are kept after parsing.
+    ///
+    /// When enabled, the bytes can be retrieved with [`Bank::raw_header`].
+    /// This is useful for reproducing or comparing file headers without reading the sound bank again.
+    /// This is disabled by default.
+    pub fn capture_raw_header(&mut self, value: bool) -> &mut Self {
+        self.options.capture_raw_header = value;
+        self
+    }
+
+    /// Sets DSP coefficients to use for GC ADPCM streams that don't contain their own.
+    ///
+    /// GC ADPCM streams are decoded with a set of 16 coefficients per channel, which are usually stored in the stream header.
+    /// Some sound banks omit them, relying on a coefficient set known ahead of time.
+    /// When set, the given coefficients are used for every channel of such streams.
+    /// Streams that contain their own coefficients are not affected.
+    pub fn fallback_dsp_coefficients(&mut self, coeffs: [i16; 16]) -> &mut Self {
+        self.options.fallback_dsp_coeffs = Some(coeffs);
+        self
+    }
+
+    /// Creates a new [`Bank<R>`] by parsing from an I/O stream with the configured options.
+    ///
+    /// See [`Bank::new`] for more information.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if parsing of the sound bank's file header failed.
+    /// See [`DecodeError`] for more information.
+    pub fn build<R: Read>(&self, source: R) -> Result<Bank<R>, DecodeError> {
+        let mut read = Reader::new(source);
+        let header = Header::parse_with(&mut read, &self.options)?;
+        Ok(Bank { header, read })
+    }
 }
 
 impl<R: Read> From<Bank<R>> for StreamIntoIter<R> {
@@ -142,7 +1071,7 @@ impl<R: Read> From<Bank<R>> for StreamIntoIter<R> {
 
 impl<R: Read> IntoIterator for Bank<R> {
     type IntoIter = StreamIntoIter<R>;
-    type Item = Stream;
+    type Item = Result<Stream, DecodeError>;
 
     fn into_iter(self) -> Self::IntoIter {
         Self::IntoIter::from(self)
@@ -151,34 +1080,71 @@ impl<R: Read> IntoIterator for Bank<R> {
 
 /// Represents an error that can occur when parsing a sound bank.
 ///
-/// This type is returned from [`Bank::new`] when file header parsing fails.
+/// This type is returned from [`Bank::new`] when file header parsing fails,
+/// and from [`Bank::into_buffered_streams`] and [`StreamIntoIter`] when stream data could not be read.
+/// It is also returned from methods that decode stream samples (e.g. [`Stream::measure_peaks`]) when decoding fails.
 /// This can be caused by invalid data or the underlying reader encountering an I/O error.
 #[derive(Debug)]
 pub struct DecodeError {
-    inner: Box<HeaderError>,
+    inner: Box<DecodeErrorSource>,
+}
+
+#[derive(Debug)]
+enum DecodeErrorSource {
+    Header(HeaderError),
+    Samples(EncodeError),
 }
 
 impl From<HeaderError> for DecodeError {
     fn from(value: HeaderError) -> Self {
         Self {
-            inner: Box::new(value),
+            inner: Box::new(DecodeErrorSource::Header(value)),
+        }
+    }
+}
+
+impl From<EncodeError> for DecodeError {
+    fn from(value: EncodeError) -> Self {
+        Self {
+            inner: Box::new(DecodeErrorSource::Samples(value)),
+        }
+    }
+}
+
+impl DecodeError {
+    /// Returns whether the error was caused by the data ending early (e.g. a truncated file).
+    ///
+    /// If this returns `false`, the error was caused by invalid data or an I/O error reported by the reader.
+    #[must_use]
+    pub fn is_eof(&self) -> bool {
+        match &*self.inner {
+            DecodeErrorSource::Header(e) => e.is_eof(),
+            // decoded streams are already in memory, so their data can't end early
+            DecodeErrorSource::Samples(_) => false,
         }
     }
 }
 
 impl Display for DecodeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        self.inner.fmt(f)
+        match &*self.inner {
+            DecodeErrorSource::Header(e) => e.fmt(f),
+            DecodeErrorSource::Samples(e) => e.fmt(f),
+        }
     }
 }
 
 impl Error for DecodeError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        self.inner.source()
+        match &*self.inner {
+            DecodeErrorSource::Header(e) => e.source(),
+            DecodeErrorSource::Samples(e) => e.source(),
+        }
     }
 }
 
-/// Represents an error that can occur when reading sound bank streams with [`Bank::read_streams`].
+/// Represents an error that can occur when reading sound bank streams with [`Bank::read_streams`]
+/// or `Bank::par_read_streams`.
 #[derive(Debug)]
 pub struct LazyStreamError<E> {
     index: u32,
@@ -188,6 +1154,10 @@ pub struct LazyStreamError<E> {
 #[derive(Debug)]
 enum LazyStreamErrorSource<E> {
     Read(ReadError),
+    #[cfg(feature = "rayon")]
+    Open(IoError),
+    #[cfg(feature = "rayon")]
+    Seek(IoError),
     Other(E),
 }
 
@@ -199,6 +1169,22 @@ impl<E> LazyStreamError<E> {
         }
     }
 
+    #[cfg(feature = "rayon")]
+    fn from_open(index: u32) -> impl FnOnce(IoError) -> Self {
+        move |source| Self {
+            index,
+            source: LazyStreamErrorSource::Open(source),
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    fn from_seek(index: u32) -> impl FnOnce(IoError) -> Self {
+        move |source| Self {
+            index,
+            source: LazyStreamErrorSource::Seek(source),
+        }
+    }
+
     fn from_other(index: u32) -> impl FnOnce(E) -> Self {
         move |source| Self {
             index,
@@ -210,6 +1196,14 @@ impl<E> LazyStreamError<E> {
     pub fn index(&self) -> u32 {
         self.index
     }
+
+    /// Returns whether the error was caused by the sound bank ending before the stream data did (e.g. a truncated file).
+    ///
+    /// Readers that return fewer bytes than requested are read from again, so this is only `true` once the reader reaches EOF.
+    /// If this returns `false`, the error was caused by an I/O error reported by the reader or by the callback.
+    pub fn is_eof(&self) -> bool {
+        matches!(&self.source, LazyStreamErrorSource::Read(e) if e.is_eof())
+    }
 }
 
 impl<E> Display for LazyStreamError<E> {
@@ -222,7 +1216,630 @@ impl<E: Error + 'static> Error for LazyStreamError<E> {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match &self.source {
             LazyStreamErrorSource::Read(e) => Some(e),
+            #[cfg(feature = "rayon")]
+            LazyStreamErrorSource::Open(e) | LazyStreamErrorSource::Seek(e) => Some(e),
             LazyStreamErrorSource::Other(e) => Some(e),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Bank, BankBuilder};
+    use crate::header::{AudioFormat, StreamInfo};
+    use crate::sanitize_name;
+    use crate::stream::Stream;
+    use std::{
+        cell::{Cell, RefCell},
+        io::{Cursor, Error as IoError, ErrorKind, Read, Result as IoResult, Write},
+        iter::zip,
+        time::Duration,
+    };
+
+    // 2 streams of 32 bytes each, followed by `data`
+    fn bank_data(data: &[u8]) -> Vec<u8> {
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x02\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x00\x40\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34);
+
+        let mut buf = Vec::from(HEADER_BASE);
+        buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+        buf.extend_from_slice(&(STREAM_HEADER | (1 << 7)).to_le_bytes());
+        buf.extend_from_slice(data);
+        buf
+    }
+
+    #[test]
+    fn report_truncated_streams() {
+        let data = bank_data(&[1; 48]);
+
+        let mut streams = Bank::new(data.as_slice()).unwrap().into_iter();
+        assert_eq!(streams.size_hint(), (1, Some(2)));
+        assert!(streams.next().is_some_and(|stream| stream.is_ok()));
+        assert!(streams.next().is_some_and(|stream| stream.is_err_and(|e| e.is_eof())));
+        assert!(streams.next().is_none());
+        assert_eq!(streams.size_hint(), (0, Some(0)));
+
+        let mut streams = Bank::new(data.as_slice()).unwrap().into_iter().allow_truncated(true);
+        assert!(streams.next().is_some_and(|stream| stream.is_ok()));
+        let stream = streams.next().unwrap().unwrap();
+        assert!(stream.is_truncated());
+        assert_eq!(stream.size().get(), 16);
+        assert_eq!(stream.raw_data(), [1; 16]);
+        assert!(streams.next().is_none());
+
+        // iteration ends after a truncated stream, instead of reporting an error for the streams after it
+        let data = bank_data(&[1; 16]);
+        let mut streams = Bank::new(data.as_slice()).unwrap().into_iter().allow_truncated(true);
+        assert!(streams
+            .next()
+            .is_some_and(|stream| stream.is_ok_and(|s| s.is_truncated())));
+        assert!(streams.next().is_none());
+        assert_eq!(streams.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn distinguish_eof_from_io_errors() {
+        struct FailingReader;
+
+        impl Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> IoResult<usize> {
+                Err(IoError::from(ErrorKind::Unsupported))
+            }
+        }
+
+        // the reader fails partway through the second stream instead of reaching EOF
+        let data = bank_data(&[1; 48]);
+        let mut streams = Bank::new(data.as_slice().chain(FailingReader)).unwrap().into_iter();
+        assert!(streams.next().is_some_and(|stream| stream.is_ok()));
+        assert!(streams
+            .next()
+            .is_some_and(|stream| stream.is_err_and(|e| !e.is_eof())));
+
+        // I/O errors are reported instead of being treated as truncation
+        let mut streams = Bank::new(data.as_slice().chain(FailingReader))
+            .unwrap()
+            .into_iter()
+            .allow_truncated(true);
+        assert!(streams.next().is_some_and(|stream| stream.is_ok()));
+        assert!(streams
+            .next()
+            .is_some_and(|stream| stream.is_err_and(|e| !e.is_eof())));
+    }
+
+    #[test]
+    fn expose_header_info() {
+        let data = bank_data(&[1; 64]);
+        let bank = Bank::new(data.as_slice()).unwrap();
+
+        assert_eq!(bank.fsb_version(), 1);
+        assert_eq!(bank.flags(), u32::from_le_bytes(*b"0000"));
+    }
+
+    #[test]
+    fn buffer_all_streams() {
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let streams = Bank::new(data.as_slice()).unwrap().into_buffered_streams().unwrap();
+
+        // streams can be accessed in any order
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[1].raw_data(), [2; 32]);
+        assert_eq!(streams[0].raw_data(), [1; 32]);
+
+        let data = bank_data(&[1; 48]);
+        let result = Bank::new(data.as_slice()).unwrap().into_buffered_streams();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn read_concatenated_banks() {
+        // each sound bank is 140 bytes long, and the next one starts at the following multiple of 32 bytes
+        let mut data = bank_data(&[1; 64]);
+        data.resize(160, 0);
+        data.extend_from_slice(&bank_data(&[2; 64]));
+
+        let bank = Bank::new(Cursor::new(data.as_slice())).unwrap();
+        let next = bank.next_bank().unwrap().unwrap();
+        assert_eq!(next.num_streams().get(), 2);
+        assert!(next.next_bank().is_none());
+
+        // a reader past the end of the sound bank means the file header was inconsistent
+        let mut bank = Bank::new(Cursor::new(data.as_slice())).unwrap();
+        bank.header.bank_size = 0;
+        assert!(bank.next_bank().is_some_and(|next| next.is_err()));
+    }
+
+    #[test]
+    fn read_encoded_streams() {
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let stream = Bank::new(data.as_slice())
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let mut encoded = Vec::new();
+        let _ = stream.clone().into_pcm_reader().read_to_end(&mut encoded).unwrap();
+        assert_eq!(encoded, stream.write(Vec::new()).unwrap());
+
+        // CELT streams can't be encoded, so reading fails
+        let mut data = bank_data(&[[1; 32], [2; 32]].concat());
+        data[24] = 12;
+        let stream = Bank::new(data.as_slice())
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+        let mut reader = stream.into_pcm_reader();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        assert!(reader.read(&mut [0; 4]).is_err());
+    }
+
+    #[test]
+    fn write_buffered_streams() {
+        // counts the write calls made to the underlying writer
+        struct CountingWriter {
+            data: Vec<u8>,
+            writes: usize,
+        }
+
+        impl Write for CountingWriter {
+            fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+                self.writes += 1;
+                self.data.write(buf)
+            }
+
+            fn flush(&mut self) -> IoResult<()> {
+                Ok(())
+            }
+        }
+
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let stream = Bank::new(data.as_slice())
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let new_sink = || CountingWriter {
+            data: Vec::new(),
+            writes: 0,
+        };
+        let unbuffered = stream.clone().write(new_sink()).unwrap();
+        let buffered = stream.write_buffered(new_sink()).unwrap();
+        assert_eq!(buffered.data, unbuffered.data);
+        assert!(buffered.writes < unbuffered.writes);
+    }
+
+    #[test]
+    fn compute_normalization_gain() {
+        // 8-bit samples with a peak amplitude of 0.5
+        let info = StreamInfo::new_test(44100, 1, 4, 4);
+        let stream =
+            Stream::new(0, AudioFormat::Pcm8, 0, info.clone(), Box::new([128, 192, 96, 128]));
+        assert_eq!(stream.normalization_gain(0.0), Some(2.0));
+        assert!(stream
+            .normalization_gain(-6.0)
+            .is_some_and(|gain| (gain - 1.0).abs() < 0.01));
+
+        // the stored peak volume is used instead of the stream data
+        let info = StreamInfo {
+            peak_volume: Some(0.25f32.to_bits()),
+            ..info
+        };
+        let stream =
+            Stream::new(0, AudioFormat::Pcm8, 0, info.clone(), Box::new([128, 192, 96, 128]));
+        assert_eq!(stream.normalization_gain(0.0), Some(4.0));
+
+        // silent streams can't be normalized
+        let info = StreamInfo {
+            peak_volume: None,
+            ..info
+        };
+        let stream = Stream::new(0, AudioFormat::Pcm8, 0, info, Box::new([128; 4]));
+        assert!(stream.normalization_gain(0.0).is_none());
+    }
+
+    #[test]
+    fn write_streams_to_path() {
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let stream = Bank::new(data.as_slice())
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("fsbex-write-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stream.wav");
+
+        // existing files are truncated
+        std::fs::write(&path, [0; 256]).unwrap();
+        stream.clone().write_to_path(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), stream.write(Vec::new()).unwrap());
+
+        // the file can't be created in a directory that doesn't exist
+        assert!(Bank::new(data.as_slice())
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap()
+            .unwrap()
+            .write_to_path(dir.join("missing").join("stream.wav"))
+            .is_err_and(|e| matches!(e, crate::encode::EncodeError::CreateFile(_))));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn extract_streams_to_writers() {
+        // appends written data to a shared buffer
+        struct SharedWriter<'a>(&'a RefCell<Vec<u8>>);
+
+        impl Write for SharedWriter<'_> {
+            fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> IoResult<()> {
+                Ok(())
+            }
+        }
+
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let outputs = [RefCell::new(Vec::new()), RefCell::new(Vec::new())];
+        Bank::new(data.as_slice())
+            .unwrap()
+            .extract_streaming(|stream| Ok(SharedWriter(&outputs[stream.index() as usize])))
+            .unwrap();
+
+        let streams = Bank::new(data.as_slice()).unwrap().into_buffered_streams().unwrap();
+        for (stream, output) in zip(streams, outputs) {
+            assert_eq!(output.into_inner(), stream.write(Vec::new()).unwrap());
+        }
+
+        // errors from creating a writer are reported with the index of the stream
+        let result = Bank::new(data.as_slice()).unwrap().extract_streaming(|stream| {
+            if stream.index() == 1 {
+                Err(IoError::from(ErrorKind::PermissionDenied))
+            } else {
+                Ok(Vec::new())
+            }
+        });
+        assert!(result.is_err_and(|e| e.index() == 1 && !e.is_eof()));
+    }
+
+    #[test]
+    fn read_stream_at_index() {
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let mut bank = Bank::new(Cursor::new(data)).unwrap();
+
+        // 2 streams of 1 sample at 44100 Hz
+        let sample_duration = Duration::from_secs_f64(1.0 / 44100.0);
+        assert_eq!(bank.total_duration(), sample_duration * 2);
+
+        let stream = bank.stream_at(1).unwrap();
+        assert_eq!(stream.index(), 1);
+        assert_eq!(stream.duration(), sample_duration);
+        assert_eq!(stream.write_raw(Vec::new()).unwrap(), [2; 32]);
+
+        // earlier streams can be read after later streams
+        let stream = bank.stream_at(0).unwrap();
+        assert_eq!(stream.write_raw(Vec::new()).unwrap(), [1; 32]);
+
+        assert!(bank.stream_at(2).is_none());
+    }
+
+    #[test]
+    fn probe_first_stream_after_later_stream() {
+        // only 16 bytes of the second stream are present
+        let data = bank_data(&[[1; 32], [2; 32]].concat()[..48]);
+        let mut bank = Bank::new(Cursor::new(data)).unwrap();
+
+        assert!(bank.stream_at(1).is_some());
+        let stream_pos = bank.read.position();
+
+        // the first stream is probed, even though the reader is at the start of the second stream
+        let probe = bank.probe_first_stream().unwrap();
+        assert_eq!(probe.bytes_read(), 32);
+        assert_eq!(bank.read.position(), stream_pos);
+
+        let stream = bank.stream_at(0).unwrap();
+        assert_eq!(stream.write_raw(Vec::new()).unwrap(), [1; 32]);
+    }
+
+    #[test]
+    fn allow_streams_without_samples() {
+        // the second stream has 0 samples
+        let mut data = bank_data(&[[1; 32], [2; 32]].concat());
+        data[72..76].fill(0);
+
+        assert!(Bank::new(data.as_slice()).is_err());
+
+        let bank = BankBuilder::new()
+            .allow_zero_samples(true)
+            .build(data.as_slice())
+            .unwrap();
+        let streams = bank.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(streams[0].sample_count().is_some());
+        assert!(streams[1].sample_count().is_none());
+        assert_eq!(streams[1].raw_data(), [2; 32]);
+    }
+
+    #[test]
+    fn report_read_progress() {
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let mut updates = Vec::new();
+
+        Bank::new(data.as_slice())
+            .unwrap()
+            .read_streams_with_progress(
+                // streams whose data isn't read are still counted
+                |_| Ok::<_, ()>(()),
+                |read, total| updates.push((read, total.get())),
+            )
+            .unwrap();
+
+        assert_eq!(updates, [(1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn iterate_without_consuming() {
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let mut bank = Bank::new(Cursor::new(data)).unwrap();
+
+        // reading a stream out of order doesn't affect iteration
+        assert_eq!(bank.stream_at(1).unwrap().write_raw(Vec::new()).unwrap(), [2; 32]);
+
+        for _ in 0..2 {
+            let streams = bank.iter().collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(streams.len(), 2);
+            assert_eq!(streams[0].raw_data(), [1; 32]);
+            assert_eq!(streams[1].raw_data(), [2; 32]);
+        }
+
+        assert_eq!(bank.num_streams().get(), 2);
+    }
+
+    #[test]
+    fn encode_lazy_vorbis_streams_incrementally() {
+        // records the size of the largest read from the underlying reader
+        struct TrackReads<'a, R> {
+            inner: R,
+            largest_read: &'a Cell<usize>,
+        }
+
+        impl<R: Read> Read for TrackReads<'_, R> {
+            fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+                self.largest_read.set(self.largest_read.get().max(buf.len()));
+                self.inner.read(buf)
+            }
+        }
+
+        // 100 Vorbis audio packets of zeros, which decode to silence
+        let packets = [[8, 0, 0, 0, 0, 0, 0, 0, 0, 0]; 100].concat();
+        let packets_size = u32::try_from(packets.len()).unwrap();
+
+        // 1 stream of 2 channels at 44100 Hz, with a chunk holding the CRC32 of its setup header
+        let mut data =
+            b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        data.extend_from_slice(&packets_size.to_le_bytes());
+        data.extend_from_slice(&15u32.to_le_bytes());
+        data.extend_from_slice(&[b'0'; 32]);
+        data.extend_from_slice(&(1u64 | (8 << 1) | (1 << 5) | (1000 << 34)).to_le_bytes());
+        data.extend_from_slice(&((4u32 << 1) | (11 << 25)).to_le_bytes());
+        data.extend_from_slice(&0xA722_97FFu32.to_le_bytes());
+        data.extend_from_slice(&packets);
+
+        let largest_read = Cell::new(0);
+        let bank = Bank::new(TrackReads {
+            inner: data.as_slice(),
+            largest_read: &largest_read,
+        })
+        .unwrap();
+        largest_read.set(0);
+
+        bank.read_streams(|stream| {
+            let output = stream.write(Vec::new()).unwrap();
+            assert_eq!(&output[..4], b"OggS");
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+
+        // the stream data is read one packet at a time instead of all at once
+        assert!(largest_read.get() <= 8);
+    }
+
+    #[test]
+    fn extract_streams_to_dir() {
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x03\x00\x00\x00\x18\x00\x00\x00\x18\x00\x00\x00\xc0\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34);
+
+        // 3 streams of 64 bytes each;
+        // the first 2 names are the same after sanitization, and the last name contains a dot
+        let mut data = Vec::from(HEADER_BASE);
+        for offset in [0, 2, 4] {
+            data.extend_from_slice(&(STREAM_HEADER | (offset << 7)).to_le_bytes());
+        }
+        data.extend_from_slice(
+            b"\x0c\x00\x00\x00\x10\x00\x00\x00\x14\x00\x00\x00A/b\x00a:b\x00a.v\x00",
+        );
+        data.extend_from_slice(&[0; 0xC0]);
+
+        let dir = std::env::temp_dir().join(format!("fsbex-extract-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut names = Vec::new();
+        Bank::new(data.as_slice())
+            .unwrap()
+            .extract_to_dir_with_progress(&dir, sanitize_name, |_, name, _| {
+                names.push(name.to_owned());
+            })
+            .unwrap();
+
+        assert_eq!(names, ["A_b", "a_b_1", "a.v"]);
+        assert!(names.iter().all(|name| dir.join(format!("{name}.wav")).is_file()));
+
+        // names can be converted with a custom function
+        let mut names = Vec::new();
+        Bank::new(data.as_slice())
+            .unwrap()
+            .extract_to_dir_with_progress(
+                &dir,
+                |name| format!("sfx_{}", sanitize_name(name)),
+                |_, name, _| names.push(name.to_owned()),
+            )
+            .unwrap();
+
+        assert_eq!(names, ["sfx_A_b", "sfx_a_b_1", "sfx_a.v"]);
+        assert!(names.iter().all(|name| dir.join(format!("{name}.wav")).is_file()));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn name_unnamed_streams_after_index() {
+        let data = bank_data(&[0; 64]);
+
+        let dir = std::env::temp_dir().join(format!("fsbex-unnamed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut names = Vec::new();
+        Bank::new(data.as_slice())
+            .unwrap()
+            .extract_to_dir_with_progress(&dir, sanitize_name, |_, name, _| {
+                names.push(name.to_owned());
+            })
+            .unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+
+        // file stems of unnamed streams match the names used when extracting them
+        let stems: Vec<_> = Bank::new(data.as_slice())
+            .unwrap()
+            .into_iter()
+            .map(|stream| stream.unwrap().safe_file_stem().into_owned())
+            .collect();
+        assert_eq!(names, ["stream_0", "stream_1"]);
+        assert_eq!(stems, names);
+
+        let mut bank = Bank::new(Cursor::new(data.as_slice())).unwrap();
+        let stream = bank.stream_at(1).unwrap();
+        assert_eq!(stream.safe_file_stem(), "stream_1");
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn read_streams_in_parallel() {
+        use std::sync::Mutex;
+
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let bank = Bank::new(data.as_slice()).unwrap();
+
+        let streams = Mutex::new(Vec::new());
+        bank.par_read_streams(
+            || Ok(Cursor::new(data.as_slice())),
+            |stream| {
+                let index = stream.index();
+                let data = stream.write_raw(Vec::new())?;
+                streams.lock().unwrap().push((index, data));
+                Ok::<_, crate::encode::EncodeError>(())
+            },
+        )
+        .unwrap();
+
+        let mut streams = streams.into_inner().unwrap();
+        streams.sort();
+        assert_eq!(streams, [(0, vec![1; 32]), (1, vec![2; 32])]);
+
+        // a truncated bank fails to read the second stream
+        let data = bank_data(&[1; 48]);
+        let bank = Bank::new(data.as_slice()).unwrap();
+        let result = bank.par_read_streams(
+            || Ok(Cursor::new(data.as_slice())),
+            |stream| stream.write_raw(Vec::new()).map(drop),
+        );
+        assert!(result.is_err_and(|e| e.index() == 1));
+
+        // errors from opening a reader are reported
+        let result = bank.par_read_streams(
+            || Err::<Cursor<&[u8]>, _>(IoError::from(ErrorKind::NotFound)),
+            |stream| stream.write_raw(Vec::new()).map(drop),
+        );
+        assert!(result.is_err_and(|e| !e.is_eof()));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn read_streams_in_parallel_from_shared_source() {
+        use std::{
+            io::{Seek, SeekFrom},
+            sync::{Arc, Mutex},
+        };
+
+        // like `&File`, clones of this reader share a single cursor
+        #[derive(Clone)]
+        struct SharedCursor(Arc<Mutex<Cursor<Vec<u8>>>>);
+
+        impl Read for SharedCursor {
+            fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+                // small reads give other threads a chance to move the cursor in between
+                let len = buf.len().min(4);
+                self.0.lock().unwrap().read(&mut buf[..len])
+            }
+        }
+
+        impl Seek for SharedCursor {
+            fn seek(&mut self, pos: SeekFrom) -> IoResult<u64> {
+                self.0.lock().unwrap().seek(pos)
+            }
+        }
+
+        // 2 streams, repeated so that threads read streams at the same time
+        let data = bank_data(&(0..64).collect::<Vec<u8>>());
+        let shared = SharedCursor(Arc::new(Mutex::new(Cursor::new(data.clone()))));
+        let bank = Bank::new(shared.clone()).unwrap();
+        let position = shared.0.lock().unwrap().position();
+
+        // the reader of the sound bank is never used, so its shared cursor isn't moved;
+        // every reader from `open` has its own cursor, so each stream gets its own bytes
+        for _ in 0..64 {
+            bank.par_read_streams(
+                || Ok(SharedCursor(Arc::new(Mutex::new(Cursor::new(data.clone()))))),
+                |stream| {
+                    let start = u8::try_from(stream.index()).unwrap() * 32;
+                    let raw = stream.write_raw(Vec::new())?;
+                    assert_eq!(raw, (start..start + 32).collect::<Vec<_>>());
+                    Ok::<_, crate::encode::EncodeError>(())
+                },
+            )
+            .unwrap();
+        }
+
+        assert_eq!(shared.0.lock().unwrap().position(), position);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn record_stream_metrics() {
+        use crate::{encode::EncodeError, metrics::StreamMetric};
+
+        let data = bank_data(&[[1; 32], [2; 32]].concat());
+        let expected = Bank::new(data.as_slice())
+            .unwrap()
+            .into_iter()
+            .map(|stream| stream.unwrap().write(Vec::new()).unwrap().len() as u64)
+            .collect::<Vec<_>>();
+
+        let metrics = Bank::new(data.as_slice())
+            .unwrap()
+            .extract_with_metrics(|_| Ok::<_, EncodeError>(Vec::new()))
+            .unwrap();
+
+        assert_eq!(metrics.iter().map(StreamMetric::index).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(metrics.iter().map(StreamMetric::bytes).collect::<Vec<_>>(), expected);
+    }
+}
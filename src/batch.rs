@@ -0,0 +1,119 @@
+use crate::bank::{Bank, DecodeError, LazyStreamError};
+use crate::encode::{EncodeError, EncodeOptions, SetupHeaderCache};
+use crate::probe::ProbeResult;
+use crate::stream::LazyStream;
+use std::io::{Read, Result as IoResult, Seek, Write};
+
+/// Extracts streams from multiple sound banks, sharing parsed Vorbis setup headers between them.
+///
+/// Every Vorbis stream is decoded with a setup header, which is reconstructed from a table of known setup headers
+/// (see [`vorbis_setup_header`]). Sound banks from the same game usually share a handful of setup headers,
+/// so [`BatchExtractor`] keeps every setup header it reconstructs and reuses it for later streams with the same CRC32,
+/// even across sound banks. This saves time when extracting a large number of sound banks.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fsbex::{Bank, BatchExtractor};
+/// use std::{error::Error, fs::File, io::BufReader, path::Path};
+///
+/// fn extract_banks(paths: &[&Path]) -> Result<(), Box<dyn Error>> {
+///     let mut extractor = BatchExtractor::new();
+///
+///     for path in paths {
+///         let bank = Bank::new(BufReader::new(File::open(path)?))?;
+///         extractor.extract(bank, |stream| {
+///             File::create(path.with_extension(format!("{}.ogg", stream.index())))
+///         })?;
+///     }
+///
+///     Ok(())
+/// }
+/// ```
+///
+/// [`vorbis_setup_header`]: crate::encode::vorbis_setup_header
+#[derive(Debug, Default)]
+pub struct BatchExtractor {
+    options: EncodeOptions,
+    cache: SetupHeaderCache,
+}
+
+impl BatchExtractor {
+    /// Creates a new [`BatchExtractor`] with default encoding options and no registered setup headers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the options that every stream is encoded with.
+    /// See [`EncodeOptions`] for more information.
+    pub fn options(&mut self, options: EncodeOptions) -> &mut Self {
+        self.options = options;
+        self
+    }
+
+    /// Registers a Vorbis setup header to use for streams with the given setup header CRC32 checksum.
+    ///
+    /// `data` must be a complete setup header packet, like the ones returned by [`vorbis_setup_header`].
+    /// Registered setup headers take precedence over the built-in table,
+    /// so this can be used for games with setup headers that aren't in the table.
+    /// Invalid setup headers are only detected when a stream using them is encoded.
+    ///
+    /// [`vorbis_setup_header`]: crate::encode::vorbis_setup_header
+    pub fn register_setup_header(&mut self, crc32: u32, data: &[u8]) -> &mut Self {
+        self.cache.register(crc32, data);
+        self
+    }
+
+    /// Returns the number of Vorbis setup headers that have been reconstructed so far.
+    ///
+    /// Setup headers are parsed separately for each channel count, so a single CRC32 can account for multiple setup headers.
+    #[must_use]
+    pub fn cached_setup_headers(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Reads the start of the first stream in a sound bank like [`Bank::probe_first_stream`],
+    /// but reconstructs Vorbis headers with the configured options and registered setup headers.
+    /// Setup headers from the built-in table are added to the shared cache.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the stream data could not be read or if seeking back to the previous position failed.
+    /// See [`DecodeError`] for more information.
+    pub fn probe_first_stream<R: Read + Seek>(
+        &mut self,
+        bank: &mut Bank<R>,
+    ) -> Result<ProbeResult, DecodeError> {
+        bank.probe_cached(&self.options, &mut self.cache)
+    }
+
+    /// Encodes every stream in a sound bank, consuming the [`Bank<R>`].
+    ///
+    /// This works like [`Bank::extract_streaming`], except that streams are encoded with the configured options,
+    /// and Vorbis setup headers are taken from (and added to) the shared cache.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if:
+    /// - an error was returned from `f` (see [`EncodeError::CreateWriter`])
+    /// - a stream could not be encoded
+    /// - the underlying reader failed to advance to the next stream
+    ///
+    /// See [`LazyStreamError`] for more information.
+    pub fn extract<R, F, W>(
+        &mut self,
+        bank: Bank<R>,
+        mut f: F,
+    ) -> Result<(), LazyStreamError<EncodeError>>
+    where
+        R: Read,
+        F: FnMut(&LazyStream<'_, R>) -> IoResult<W>,
+        W: Write,
+    {
+        bank.read_streams(|stream| {
+            let sink = f(&stream).map_err(EncodeError::CreateWriter)?;
+            stream.write_cached(sink, &self.options, &mut self.cache).map(drop)
+        })
+    }
+}
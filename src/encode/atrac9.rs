@@ -0,0 +1,287 @@
+use crate::header::StreamInfo;
+use crate::read::Reader;
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{copy, Error as IoError, Read, Write},
+    num::NonZeroU32,
+};
+
+// ATRAC9 files are WAVE files with a WAVEFORMATEXTENSIBLE format, identified by this subformat GUID:
+// {47E142D2-36BA-4D8D-88FC-61654F8C836C}
+const SUBFORMAT_GUID: [u8; 16] = [
+    0xD2, 0x42, 0xE1, 0x47, 0xBA, 0x36, 0x8D, 0x4D, 0x88, 0xFC, 0x61, 0x65, 0x4F, 0x8C, 0x83, 0x6C,
+];
+
+// every ATRAC9 configuration starts with this byte
+const CONFIG_SYNC: u8 = 0xFE;
+
+// size of the `fmt ` chunk contents, including the WAVEFORMATEXTENSIBLE and ATRAC9-specific fields
+const FMT_SIZE: u32 = 52;
+// size of the `fact` chunk contents
+const FACT_SIZE: u32 = 12;
+
+// Wraps the stream data in an ATRAC9 file (a WAVE file that can be decoded by tools like at9tool and vgmstream).
+// The stream data is copied as-is, so no decoding takes place.
+pub(super) fn encode<R: Read, W: Write>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    mut sink: W,
+) -> Result<W, Atrac9Error> {
+    let config = info
+        .atrac9_config
+        .as_deref()
+        .ok_or_else(|| Atrac9Error::new(Atrac9ErrorKind::MissingConfig))
+        .and_then(Config::parse)?;
+
+    // Streams with multiple layers store one configuration per layer, which can't be represented in a single file.
+    if config.channels() != info.channels.get() {
+        return Err(Atrac9Error::new(Atrac9ErrorKind::MismatchedChannels));
+    }
+
+    write_header(info, &config, &mut sink)
+        .map_err(Atrac9Error::from_io(Atrac9ErrorKind::CreateHeader))?;
+
+    // There could be more data after the stream, so a limit is placed on the number of bytes read.
+    let _ = copy(&mut source.limit(info.size.get() as usize), &mut sink)
+        .map_err(Atrac9Error::from_io(Atrac9ErrorKind::EncodeStream))?;
+
+    sink.flush()
+        .map(|()| sink)
+        .map_err(Atrac9Error::from_io(Atrac9ErrorKind::FinishStream))
+}
+
+// The 4-byte ATRAC9 configuration, which describes how the stream data is divided into frames.
+// FMOD stores it after a 4-byte field in the ATRAC9 configuration chunk.
+struct Config([u8; 4]);
+
+impl Config {
+    fn parse(chunk: &[u8]) -> Result<Self, Atrac9Error> {
+        match chunk.get(4..8) {
+            Some(&[sync, b1, b2, b3]) if sync == CONFIG_SYNC => Ok(Self([sync, b1, b2, b3])),
+            _ => Err(Atrac9Error::new(Atrac9ErrorKind::InvalidConfig)),
+        }
+    }
+
+    fn sample_rate_index(&self) -> u8 {
+        self.0[1] >> 4
+    }
+
+    fn channels(&self) -> u8 {
+        match (self.0[1] >> 1) & 0b111 {
+            0 => 1,
+            1 | 2 => 2,
+            3 => 6,
+            4 => 8,
+            5 => 4,
+            _ => 0,
+        }
+    }
+
+    // the size of each frame, in bytes
+    fn frame_size(&self) -> u32 {
+        ((u32::from(self.0[2]) << 3) | u32::from(self.0[3] >> 5)) + 1
+    }
+
+    // the number of frames in each superframe, as a power of 2
+    fn superframe_index(&self) -> u8 {
+        (self.0[3] >> 3) & 0b11
+    }
+
+    fn superframe_size(&self) -> u32 {
+        self.frame_size() << self.superframe_index()
+    }
+
+    fn superframe_samples(&self) -> u32 {
+        // the number of samples per frame, as a power of 2, depends on the sample rate
+        const FRAME_SAMPLES_POWER: [u8; 16] = [6, 6, 7, 7, 7, 8, 8, 8, 6, 6, 7, 7, 7, 8, 8, 8];
+        1 << (FRAME_SAMPLES_POWER[self.sample_rate_index() as usize] + self.superframe_index())
+    }
+}
+
+fn write_header<W: Write>(info: &StreamInfo, config: &Config, sink: &mut W) -> Result<(), IoError> {
+    // ATRAC9 file header information taken from:
+    // [1]: https://learn.microsoft.com/en-us/windows/win32/api/mmreg/ns-mmreg-waveformatextensible
+    // [2]: https://github.com/vgmstream/vgmstream/blob/master/src/meta/riff.c
+
+    let channels = info.channels.get();
+    let sample_rate = info.sample_rate.get();
+    let data_size = info.size.get();
+
+    let bytes_per_second = u64::from(config.superframe_size()) * u64::from(sample_rate)
+        / u64::from(config.superframe_samples());
+    let channel_mask: u32 = match channels {
+        1 => 0x4,
+        2 => 0x3,
+        4 => 0x33,
+        6 => 0x3F,
+        8 => 0x63F,
+        _ => 0,
+    };
+
+    let file_size = (4 + 8 + FMT_SIZE + 8 + FACT_SIZE + 8)
+        .checked_add(data_size)
+        .ok_or_else(|| IoError::other("ATRAC9 stream is too large to be written"))?;
+
+    sink.write_all(b"RIFF")?;
+    sink.write_all(&file_size.to_le_bytes())?;
+    sink.write_all(b"WAVE")?;
+
+    sink.write_all(b"fmt ")?;
+    sink.write_all(&FMT_SIZE.to_le_bytes())?;
+    sink.write_all(&0xFFFEu16.to_le_bytes())?;
+    sink.write_all(&u16::from(channels).to_le_bytes())?;
+    sink.write_all(&sample_rate.to_le_bytes())?;
+    sink.write_all(&u32::try_from(bytes_per_second).unwrap_or(u32::MAX).to_le_bytes())?;
+    sink.write_all(
+        &u16::try_from(config.superframe_size())
+            .unwrap_or(u16::MAX)
+            .to_le_bytes(),
+    )?;
+    // bits per sample are not meaningful for ATRAC9
+    sink.write_all(&0u16.to_le_bytes())?;
+    // size of the extension fields that follow
+    sink.write_all(&34u16.to_le_bytes())?;
+    sink.write_all(
+        &u16::try_from(config.superframe_samples())
+            .unwrap_or(u16::MAX)
+            .to_le_bytes(),
+    )?;
+    sink.write_all(&channel_mask.to_le_bytes())?;
+    sink.write_all(&SUBFORMAT_GUID)?;
+    // format version
+    sink.write_all(&1u32.to_le_bytes())?;
+    sink.write_all(&config.0)?;
+    sink.write_all(&0u32.to_le_bytes())?;
+
+    // FMOD sample counts already account for the encoder delay, so no delay is signaled
+    sink.write_all(b"fact")?;
+    sink.write_all(&FACT_SIZE.to_le_bytes())?;
+    sink.write_all(&info.num_samples.map_or(0, NonZeroU32::get).to_le_bytes())?;
+    sink.write_all(&0u32.to_le_bytes())?;
+    sink.write_all(&0u32.to_le_bytes())?;
+
+    sink.write_all(b"data")?;
+    sink.write_all(&data_size.to_le_bytes())?;
+
+    Ok(())
+}
+
+/// Represents an error that can occur when encoding an ATRAC9 stream.
+///
+/// See [`Atrac9ErrorKind`] for the different kinds of errors that can occur.
+#[derive(Debug)]
+pub struct Atrac9Error {
+    kind: Atrac9ErrorKind,
+    source: Option<IoError>,
+}
+
+/// A variant of an [`Atrac9Error`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Atrac9ErrorKind {
+    /// The stream header did not contain an ATRAC9 configuration.
+    MissingConfig,
+    /// The ATRAC9 configuration of the stream was invalid.
+    InvalidConfig,
+    /// The number of channels in the ATRAC9 configuration did not match the number of channels in the stream.
+    /// This happens for streams with multiple layers, which can't be written as a single file.
+    MismatchedChannels,
+    /// Failed to write the file header due to an underlying I/O error.
+    CreateHeader,
+    /// Failed to copy the stream data from reader to writer.
+    EncodeStream,
+    /// Failed to flush the writer after encoding the entire stream.
+    FinishStream,
+}
+
+impl Atrac9Error {
+    fn new(kind: Atrac9ErrorKind) -> Self {
+        Self { kind, source: None }
+    }
+
+    fn from_io(kind: Atrac9ErrorKind) -> impl FnOnce(IoError) -> Self {
+        move |source| Self {
+            kind,
+            source: Some(source),
+        }
+    }
+
+    /// Returns the [`Atrac9ErrorKind`] associated with this error.
+    #[must_use]
+    pub fn kind(&self) -> Atrac9ErrorKind {
+        self.kind
+    }
+}
+
+impl Display for Atrac9Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.kind.fmt(f)
+    }
+}
+
+impl Error for Atrac9Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.source {
+            Some(e) => Some(e),
+            None => None,
+        }
+    }
+}
+
+impl Display for Atrac9ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(match self {
+            Self::MissingConfig => "ATRAC9 configuration was not found in stream header",
+            Self::InvalidConfig => "ATRAC9 configuration was invalid",
+            Self::MismatchedChannels => {
+                "number of channels in ATRAC9 configuration did not match stream"
+            }
+            Self::CreateHeader => "failed to encode file header",
+            Self::EncodeStream => "failed to copy ATRAC9 stream data",
+            Self::FinishStream => "failed to finalize writing ATRAC9 stream data",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode, Atrac9ErrorKind};
+    use crate::header::StreamInfo;
+    use crate::read::Reader;
+
+    #[test]
+    fn wrap_stream_data() {
+        let mut info = StreamInfo::new_test(48000, 2, 1024, 8);
+        // 48 kHz stereo, 4 frames of 256 bytes per superframe
+        info.atrac9_config = Some(Box::new([0, 0, 0, 0, 0xFE, 0x74, 0x1F, 0xF0, 0, 0, 0, 0]));
+        let data = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+        let file = encode(&info, &mut Reader::new(data.as_slice()), Vec::new()).unwrap();
+
+        assert_eq!(file.len(), 108);
+        assert_eq!(&file[..4], b"RIFF");
+        assert_eq!(&file[4..8], 100u32.to_le_bytes());
+        // block alignment is the size of a superframe
+        assert_eq!(&file[32..34], 1024u16.to_le_bytes());
+        // samples per superframe
+        assert_eq!(&file[38..40], 1024u16.to_le_bytes());
+        assert_eq!(&file[64..68], [0xFE, 0x74, 0x1F, 0xF0]);
+        assert_eq!(&file[72..76], b"fact");
+        assert_eq!(&file[80..84], 1024u32.to_le_bytes());
+        assert_eq!(&file[100..], &data[..8]);
+    }
+
+    #[test]
+    fn reject_invalid_config() {
+        let mut info = StreamInfo::new_test(48000, 2, 1024, 8);
+        let source = [0; 8];
+
+        let result = encode(&info, &mut Reader::new(source.as_slice()), Vec::new());
+        assert!(result.is_err_and(|e| e.kind() == Atrac9ErrorKind::MissingConfig));
+
+        info.atrac9_config = Some(Box::new([0, 0, 0, 0, 0xFE, 0x70, 0x1F, 0xF0]));
+        let result = encode(&info, &mut Reader::new(source.as_slice()), Vec::new());
+        assert!(result.is_err_and(|e| e.kind() == Atrac9ErrorKind::MismatchedChannels));
+    }
+}
@@ -0,0 +1,119 @@
+use std::io::{Result as IoResult, Write};
+
+// total size of the `bext` chunk contents (version 1, without coding history)
+const BEXT_SIZE: u32 = 602;
+
+/// Broadcast Wave Format (BWF) metadata that is written to WAVE files in a `bext` chunk.
+///
+/// See [`EncodeOptions::broadcast_metadata`] for more information.
+/// Text fields are truncated to the lengths allowed by the BWF specification
+/// (32 bytes for the originator and 256 bytes for the description).
+///
+/// # Examples
+///
+/// ```
+/// use fsbex::encode::{BroadcastMetadata, EncodeOptions};
+///
+/// let mut metadata = BroadcastMetadata::new("fsbex");
+/// metadata.origination_date(2024, 1, 31).origination_time(12, 30, 0);
+///
+/// let mut options = EncodeOptions::new();
+/// options.broadcast_metadata(Some(metadata));
+/// ```
+///
+/// [`EncodeOptions::broadcast_metadata`]: super::EncodeOptions::broadcast_metadata
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BroadcastMetadata {
+    originator: String,
+    date: Option<(u16, u8, u8)>,
+    time: Option<(u8, u8, u8)>,
+}
+
+impl BroadcastMetadata {
+    /// Creates a new [`BroadcastMetadata`] with the name of the organization or tool that created the audio.
+    ///
+    /// The origination date and time are left empty by default.
+    #[must_use]
+    pub fn new(originator: &str) -> Self {
+        Self {
+            originator: originator.to_owned(),
+            date: None,
+            time: None,
+        }
+    }
+
+    /// Sets the date that the audio was created, written in the form `yyyy-mm-dd`.
+    pub fn origination_date(&mut self, year: u16, month: u8, day: u8) -> &mut Self {
+        self.date = Some((year, month, day));
+        self
+    }
+
+    /// Sets the time that the audio was created, written in the form `hh:mm:ss`.
+    pub fn origination_time(&mut self, hour: u8, minute: u8, second: u8) -> &mut Self {
+        self.time = Some((hour, minute, second));
+        self
+    }
+}
+
+// Writes a `bext` chunk (including the chunk ID and size) with the stream name as the description.
+pub(super) fn write_chunk<W: Write>(
+    metadata: &BroadcastMetadata,
+    description: Option<&str>,
+    sink: &mut W,
+) -> IoResult<()> {
+    // `bext` chunk information taken from:
+    // [1]: https://tech.ebu.ch/docs/tech/tech3285.pdf
+
+    let date = metadata
+        .date
+        .map(|(year, month, day)| format!("{year:04}-{month:02}-{day:02}"));
+    let time = metadata
+        .time
+        .map(|(hour, minute, second)| format!("{hour:02}:{minute:02}:{second:02}"));
+
+    sink.write_all(b"bext")?;
+    sink.write_all(&BEXT_SIZE.to_le_bytes())?;
+    write_fixed::<_, 256>(description.unwrap_or_default(), sink)?;
+    write_fixed::<_, 32>(&metadata.originator, sink)?;
+    // originator reference
+    write_fixed::<_, 32>("", sink)?;
+    write_fixed::<_, 10>(date.as_deref().unwrap_or_default(), sink)?;
+    write_fixed::<_, 8>(time.as_deref().unwrap_or_default(), sink)?;
+    // time reference (the position of the first sample since midnight)
+    sink.write_all(&0u64.to_le_bytes())?;
+    // version
+    sink.write_all(&1u16.to_le_bytes())?;
+    // SMPTE UMID and reserved bytes
+    sink.write_all(&[0; 64 + 190])?;
+
+    Ok(())
+}
+
+// Writes text in a fixed-size field, truncating it or padding it with null bytes as needed.
+fn write_fixed<W: Write, const LEN: usize>(text: &str, sink: &mut W) -> IoResult<()> {
+    let mut field = [0; LEN];
+    let len = text.len().min(LEN);
+    field[..len].copy_from_slice(&text.as_bytes()[..len]);
+    sink.write_all(&field)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{write_chunk, BroadcastMetadata, BEXT_SIZE};
+
+    #[test]
+    fn write_bext_chunk() {
+        let mut metadata = BroadcastMetadata::new("fsbex");
+        let _ = metadata.origination_date(2024, 1, 31).origination_time(9, 5, 0);
+
+        let mut chunk = Vec::new();
+        write_chunk(&metadata, Some("explosion"), &mut chunk).unwrap();
+
+        assert_eq!(chunk.len(), 8 + BEXT_SIZE as usize);
+        assert_eq!(&chunk[..4], b"bext");
+        assert_eq!(&chunk[8..18], b"explosion\0");
+        assert_eq!(&chunk[264..270], b"fsbex\0");
+        assert_eq!(&chunk[328..346], b"2024-01-3109:05:00");
+        assert_eq!(&chunk[354..356], 1u16.to_le_bytes());
+    }
+}
@@ -0,0 +1,71 @@
+// Converts 32-bit float samples to 16-bit integer samples, appending them to `out`.
+// Samples are clamped to the range [-1.0, 1.0], scaled by 32767, and rounded to the nearest integer.
+// If `dither` is given, triangular (TPDF) dither of up to 1 LSB is added before rounding,
+// which trades a small amount of noise for the removal of quantization distortion in quiet passages.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn f32_to_i16(samples: &[f32], out: &mut Vec<i16>, mut dither: Option<&mut Dither>) {
+    out.reserve(samples.len());
+
+    for &sample in samples {
+        // NaN is treated as silence
+        let sample = if sample.is_nan() {
+            0.0
+        } else {
+            sample.clamp(-1.0, 1.0)
+        };
+        let noise = dither.as_mut().map_or(0.0, |dither| dither.next_triangular());
+
+        // the value is clamped to the range of i16, so truncation is harmless
+        out.push((sample * 32767.0 + noise).round().clamp(-32768.0, 32767.0) as i16);
+    }
+}
+
+// A source of dither noise, using a xorshift generator so that output is deterministic for a given seed.
+pub(crate) struct Dither {
+    state: u32,
+}
+
+impl Dither {
+    pub(crate) fn new(seed: u32) -> Self {
+        // xorshift generators get stuck at 0
+        Self { state: seed.max(1) }
+    }
+
+    fn next_uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        // the top 24 bits fit exactly in the mantissa of an f32
+        #[allow(clippy::cast_precision_loss)]
+        let value = (self.state >> 8) as f32;
+        value / 16_777_216.0
+    }
+
+    // The difference of two uniform values has a triangular distribution in the range (-1.0, 1.0).
+    fn next_triangular(&mut self) -> f32 {
+        self.next_uniform() - self.next_uniform()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{f32_to_i16, Dither};
+
+    #[test]
+    fn clamp_and_round_samples() {
+        let mut out = Vec::new();
+        f32_to_i16(&[0.0, 1.0, -1.0, 2.0, -2.0, 0.5, f32::NAN], &mut out, None);
+        assert_eq!(out, [0, 32767, -32767, 32767, -32767, 16384, 0]);
+    }
+
+    #[test]
+    fn dither_by_at_most_one_step() {
+        let samples = [0.25; 256];
+        let mut out = Vec::new();
+        f32_to_i16(&samples, &mut out, Some(&mut Dither::new(1)));
+
+        // 0.25 * 32767 = 8191.75, and dither noise is less than 1.0 in either direction
+        assert!(out.iter().all(|sample| (8191..=8193).contains(sample)));
+        assert!(out.iter().any(|&sample| sample != out[0]));
+    }
+}
@@ -1,9 +1,17 @@
+use super::atrac9::Atrac9Error;
+use super::fadpcm::FadpcmError;
+use super::gcadpcm::GcAdpcmError;
+use super::ima::ImaAdpcmError;
+use super::mpeg::MpegError;
+use super::opus::OpusError;
 use super::pcm::PcmError;
 use super::vorbis::VorbisError;
+use super::xwma::XwmaError;
 use crate::header::AudioFormat;
 use std::{
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
+    io::Error as IoError,
 };
 
 /// Represents an error that can occur when encoding a sound bank stream.
@@ -21,6 +29,37 @@ pub enum EncodeError {
     /// Failed to encode a Vorbis stream.
     /// See [`VorbisError`] for more information.
     Vorbis(VorbisError),
+    /// Failed to wrap an ATRAC9 stream in an ATRAC9 file.
+    /// See [`Atrac9Error`] for more information.
+    Atrac9(Atrac9Error),
+    /// Failed to decode a GC ADPCM stream.
+    /// See [`GcAdpcmError`] for more information.
+    GcAdpcm(GcAdpcmError),
+    /// Failed to decode an IMA ADPCM stream.
+    /// See [`ImaAdpcmError`] for more information.
+    ImaAdpcm(ImaAdpcmError),
+    /// Failed to copy an MPEG stream.
+    /// See [`MpegError`] for more information.
+    Mpeg(MpegError),
+    /// Failed to decode a FADPCM stream.
+    /// See [`FadpcmError`] for more information.
+    Fadpcm(FadpcmError),
+    /// Failed to wrap an xWMA stream in an xWMA file.
+    /// See [`XwmaError`] for more information.
+    Xwma(XwmaError),
+    /// Failed to wrap an Opus stream in an Ogg Opus file.
+    /// See [`OpusError`] for more information.
+    Opus(OpusError),
+    /// Failed to copy raw stream data to the writer due to an underlying I/O error.
+    Raw(IoError),
+    /// The requested range of the stream contained no samples.
+    EmptyRange,
+    /// Failed to flush buffered output to the writer due to an underlying I/O error.
+    Flush(IoError),
+    /// Failed to create the output file due to an underlying I/O error.
+    CreateFile(IoError),
+    /// Failed to create the writer that a stream is encoded to due to an underlying I/O error.
+    CreateWriter(IoError),
 }
 
 impl From<PcmError> for EncodeError {
@@ -35,6 +74,48 @@ impl From<VorbisError> for EncodeError {
     }
 }
 
+impl From<Atrac9Error> for EncodeError {
+    fn from(value: Atrac9Error) -> Self {
+        Self::Atrac9(value)
+    }
+}
+
+impl From<GcAdpcmError> for EncodeError {
+    fn from(value: GcAdpcmError) -> Self {
+        Self::GcAdpcm(value)
+    }
+}
+
+impl From<ImaAdpcmError> for EncodeError {
+    fn from(value: ImaAdpcmError) -> Self {
+        Self::ImaAdpcm(value)
+    }
+}
+
+impl From<FadpcmError> for EncodeError {
+    fn from(value: FadpcmError) -> Self {
+        Self::Fadpcm(value)
+    }
+}
+
+impl From<MpegError> for EncodeError {
+    fn from(value: MpegError) -> Self {
+        Self::Mpeg(value)
+    }
+}
+
+impl From<XwmaError> for EncodeError {
+    fn from(value: XwmaError) -> Self {
+        Self::Xwma(value)
+    }
+}
+
+impl From<OpusError> for EncodeError {
+    fn from(value: OpusError) -> Self {
+        Self::Opus(value)
+    }
+}
+
 impl Display for EncodeError {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         match self {
@@ -43,6 +124,18 @@ impl Display for EncodeError {
             }
             Self::Pcm(_) => f.write_str("failed to encode PCM stream"),
             Self::Vorbis(_) => f.write_str("failed to encode Vorbis stream"),
+            Self::Atrac9(_) => f.write_str("failed to encode ATRAC9 stream"),
+            Self::GcAdpcm(_) => f.write_str("failed to decode GC ADPCM stream"),
+            Self::ImaAdpcm(_) => f.write_str("failed to decode IMA ADPCM stream"),
+            Self::Mpeg(_) => f.write_str("failed to encode MPEG stream"),
+            Self::Fadpcm(_) => f.write_str("failed to decode FADPCM stream"),
+            Self::Xwma(_) => f.write_str("failed to encode xWMA stream"),
+            Self::Opus(_) => f.write_str("failed to encode Opus stream"),
+            Self::Raw(_) => f.write_str("failed to copy raw stream data"),
+            Self::EmptyRange => f.write_str("requested range of stream contained no samples"),
+            Self::Flush(_) => f.write_str("failed to flush buffered output"),
+            Self::CreateFile(_) => f.write_str("failed to create output file"),
+            Self::CreateWriter(_) => f.write_str("failed to create output writer"),
         }
     }
 }
@@ -50,9 +143,17 @@ impl Display for EncodeError {
 impl Error for EncodeError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
-            Self::UnsupportedFormat { format: _ } => None,
+            Self::UnsupportedFormat { format: _ } | Self::EmptyRange => None,
             Self::Pcm(e) => Some(e),
             Self::Vorbis(e) => Some(e),
+            Self::Atrac9(e) => Some(e),
+            Self::GcAdpcm(e) => Some(e),
+            Self::ImaAdpcm(e) => Some(e),
+            Self::Mpeg(e) => Some(e),
+            Self::Fadpcm(e) => Some(e),
+            Self::Xwma(e) => Some(e),
+            Self::Opus(e) => Some(e),
+            Self::Raw(e) | Self::Flush(e) | Self::CreateFile(e) | Self::CreateWriter(e) => Some(e),
         }
     }
 }
@@ -0,0 +1,232 @@
+use crate::header::StreamInfo;
+use crate::read::{ReadError, Reader};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::Read,
+};
+
+// FADPCM information taken from:
+// [1]: https://github.com/vgmstream/vgmstream/blob/master/src/coding/fadpcm_decoder.c
+// [2]: https://github.com/vgmstream/vgmstream/blob/master/src/meta/fsb5.c
+
+// Each frame has a 12-byte header followed by 8 groups of 32 samples stored as 4-bit nibbles.
+const FRAME_SIZE: usize = 0x8C;
+const FRAME_HEADER_SIZE: usize = 0x0C;
+const GROUP_SIZE: usize = 0x10;
+const SAMPLES_PER_FRAME: usize = (FRAME_SIZE - FRAME_HEADER_SIZE) * 2;
+
+// indices past the end of the table wrap around (e.g. 9 selects the same coefficients as 2)
+const COEFFS: [(i32, i32); 7] = [
+    (0, 0),
+    (60, 0),
+    (122, 60),
+    (115, 52),
+    (98, 55),
+    (0, 0),
+    (0, 0),
+];
+
+// Decodes the entire stream into interleaved 16-bit samples.
+// Channels are interleaved frame by frame, so every channel's first frame comes before every channel's second frame.
+pub(super) fn decode<R: Read>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+) -> Result<Vec<i16>, FadpcmError> {
+    let channels = usize::from(info.channels.get());
+
+    // Frames are padded to a whole number of samples, so trailing samples past the sample count are discarded.
+    let frame_samples = info.size.get() as usize / (FRAME_SIZE * channels) * SAMPLES_PER_FRAME;
+    let num_samples = info
+        .num_samples
+        .map_or(frame_samples, |samples| samples.get() as usize);
+
+    if num_samples > frame_samples {
+        return Err(FadpcmError::new(FadpcmErrorKind::Truncated));
+    }
+
+    let mut samples = vec![0; num_samples * channels];
+
+    for frame_start in (0..num_samples).step_by(SAMPLES_PER_FRAME) {
+        let len = SAMPLES_PER_FRAME.min(num_samples - frame_start);
+
+        for channel in 0..channels {
+            let frame = source
+                .take_const::<FRAME_SIZE>()
+                .map_err(FadpcmError::from_read(FadpcmErrorKind::ReadFrame))?;
+
+            for (index, sample) in decode_frame(&frame).into_iter().take(len).enumerate() {
+                samples[(frame_start + index) * channels + channel] = sample;
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+// Decodes the samples of a single frame.
+// Unlike other ADPCM formats, the sample history is stored in every frame header rather than carried across frames.
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+fn decode_frame(frame: &[u8; FRAME_SIZE]) -> [i16; SAMPLES_PER_FRAME] {
+    let le_u32 = |offset: usize| {
+        u32::from_le_bytes([
+            frame[offset],
+            frame[offset + 1],
+            frame[offset + 2],
+            frame[offset + 3],
+        ])
+    };
+
+    // every group of samples has its own coefficients and scale, packed as nibbles
+    let coeff_indices = le_u32(0x00);
+    let shifts = le_u32(0x04);
+    let mut history = [
+        i32::from(i16::from_le_bytes([frame[0x08], frame[0x09]])),
+        i32::from(i16::from_le_bytes([frame[0x0A], frame[0x0B]])),
+    ];
+
+    let mut samples = [0; SAMPLES_PER_FRAME];
+    let groups = frame[FRAME_HEADER_SIZE..].chunks_exact(GROUP_SIZE);
+
+    for (group, (data, out)) in groups.zip(samples.chunks_exact_mut(GROUP_SIZE * 2)).enumerate() {
+        let (coeff1, coeff2) =
+            COEFFS[(coeff_indices >> (group * 4) & 0x0F) as usize % COEFFS.len()];
+        let shift = 22 - (shifts >> (group * 4) & 0x0F);
+
+        let nibbles = data.iter().flat_map(|byte| [byte & 0x0F, byte >> 4]);
+
+        for (sample, nibble) in out.iter_mut().zip(nibbles) {
+            // the nibble is moved to the top bits to sign-extend it, then scaled down
+            let scaled = ((u32::from(nibble) << 28) as i32) >> shift;
+            let value = ((scaled + history[0] * coeff1 - history[1] * coeff2) >> 6)
+                .clamp(i16::MIN.into(), i16::MAX.into());
+
+            history[1] = history[0];
+            history[0] = value;
+            // the value is clamped to the range of i16, so truncation is harmless
+            *sample = value as i16;
+        }
+    }
+
+    samples
+}
+
+/// Represents an error that can occur when decoding a FADPCM stream.
+///
+/// See [`FadpcmErrorKind`] for the different kinds of errors that can occur.
+#[derive(Debug)]
+pub struct FadpcmError {
+    kind: FadpcmErrorKind,
+    source: Option<ReadError>,
+}
+
+/// A variant of a [`FadpcmError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FadpcmErrorKind {
+    /// The stream data was too small to contain the number of samples in the stream header.
+    Truncated,
+    /// Failed to read a frame from the stream data.
+    ReadFrame,
+}
+
+impl FadpcmError {
+    fn new(kind: FadpcmErrorKind) -> Self {
+        Self { kind, source: None }
+    }
+
+    fn from_read(kind: FadpcmErrorKind) -> impl FnOnce(ReadError) -> Self {
+        move |source| Self {
+            kind,
+            source: Some(source),
+        }
+    }
+
+    /// Returns the [`FadpcmErrorKind`] associated with this error.
+    #[must_use]
+    pub fn kind(&self) -> FadpcmErrorKind {
+        self.kind
+    }
+}
+
+impl Display for FadpcmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.kind.fmt(f)
+    }
+}
+
+impl Error for FadpcmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.source {
+            Some(e) => Some(e),
+            None => None,
+        }
+    }
+}
+
+impl Display for FadpcmErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(match self {
+            Self::Truncated => "FADPCM stream data was smaller than expected",
+            Self::ReadFrame => "failed to read frame from FADPCM stream data",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, FadpcmErrorKind, FRAME_SIZE};
+    use crate::header::StreamInfo;
+    use crate::read::Reader;
+
+    // builds a frame from its header fields, repeating `group` for every group of nibbles
+    fn frame(coeff_indices: u32, shifts: u32, history: [i16; 2], group: [u8; 4]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(FRAME_SIZE);
+        frame.extend_from_slice(&coeff_indices.to_le_bytes());
+        frame.extend_from_slice(&shifts.to_le_bytes());
+        frame.extend_from_slice(&history[0].to_le_bytes());
+        frame.extend_from_slice(&history[1].to_le_bytes());
+
+        for _ in 0..32 {
+            frame.extend_from_slice(&group);
+        }
+
+        frame
+    }
+
+    #[test]
+    fn decode_interleaved_frames() {
+        // without prediction, samples are the signed nibbles scaled by the shift
+        let data = [
+            frame(0, 0, [0, 0], [0x10, 0x32, 0x54, 0x76]),
+            frame(0, 0x1111_1111, [0, 0], [0x98, 0xBA, 0xDC, 0xFE]),
+        ]
+        .concat();
+
+        let info = StreamInfo::new_test(44100, 2, 256, 0x118);
+        let samples = decode(&info, &mut Reader::new(data.as_slice())).unwrap();
+
+        assert_eq!(samples.len(), 512);
+        assert_eq!(samples[..6], [0, -16, 1, -14, 2, -12]);
+        assert_eq!(samples[14..16], [7, -2]);
+        assert_eq!(samples[510..], [7, -2]);
+
+        // samples after the sample count are not decoded
+        let info = StreamInfo::new_test(44100, 2, 10, 0x118);
+        let samples = decode(&info, &mut Reader::new(data.as_slice())).unwrap();
+        assert_eq!(samples.len(), 20);
+    }
+
+    #[test]
+    fn predict_from_frame_history() {
+        // index 8 wraps around to the second coefficient pair, which predicts each sample as 60/64 of the previous sample
+        let data = frame(0x8888_8888, 0, [64, 0], [0; 4]);
+        let info = StreamInfo::new_test(44100, 1, 3, 0x8C);
+        let samples = decode(&info, &mut Reader::new(data.as_slice())).unwrap();
+        assert_eq!(samples, [60, 56, 52]);
+
+        let info = StreamInfo::new_test(44100, 1, 257, 0x8C);
+        let result = decode(&info, &mut Reader::new(data.as_slice()));
+        assert!(result.is_err_and(|e| e.kind() == FadpcmErrorKind::Truncated));
+    }
+}
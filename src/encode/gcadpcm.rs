@@ -0,0 +1,241 @@
+use crate::header::StreamInfo;
+use crate::read::{ReadError, Reader};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::Read,
+};
+
+// GC ADPCM (Nintendo DSP ADPCM) information taken from:
+// [1]: https://github.com/vgmstream/vgmstream/blob/master/src/coding/ngc_dsp_decoder.c
+// [2]: https://github.com/vgmstream/vgmstream/blob/master/src/meta/fsb5.c
+
+// Each frame has a 1-byte header followed by 14 samples stored as 4-bit nibbles.
+const FRAME_SIZE: usize = 8;
+const SAMPLES_PER_FRAME: usize = 14;
+// Frames of every channel are interleaved 2 bytes at a time (`coding_NGC_DSP_subint` in [2]).
+const SUBINTERLEAVE: usize = 2;
+
+// Decodes the entire stream into interleaved 16-bit samples.
+// Every block of stream data holds one frame per channel; within a block, channels are interleaved every 2 bytes,
+// so a stereo block is stored as 2 bytes of the left frame, 2 bytes of the right frame, the next 2 bytes of the left frame, and so on.
+pub(super) fn decode<R: Read>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+) -> Result<Vec<i16>, GcAdpcmError> {
+    let channels = usize::from(info.channels.get());
+
+    let coeffs = info
+        .dsp_coeffs
+        .as_deref()
+        .filter(|coeffs| coeffs.len() >= channels)
+        .ok_or_else(|| GcAdpcmError::new(GcAdpcmErrorKind::MissingCoefficients))?;
+
+    // Frames are padded to a whole number of samples, so trailing samples past the sample count are discarded.
+    let frame_samples = info.size.get() as usize / (FRAME_SIZE * channels) * SAMPLES_PER_FRAME;
+    let num_samples = info
+        .num_samples
+        .map_or(frame_samples, |samples| samples.get() as usize);
+
+    if num_samples > frame_samples {
+        return Err(GcAdpcmError::new(GcAdpcmErrorKind::Truncated));
+    }
+
+    let mut samples = vec![0; num_samples * channels];
+    let mut history = vec![[0; 2]; channels];
+
+    for frame_start in (0..num_samples).step_by(SAMPLES_PER_FRAME) {
+        let len = SAMPLES_PER_FRAME.min(num_samples - frame_start);
+        let block = source
+            .take(FRAME_SIZE * channels)
+            .map_err(GcAdpcmError::from_read(GcAdpcmErrorKind::ReadFrame))?;
+
+        for (channel, (coeffs, history)) in coeffs.iter().zip(&mut history).enumerate() {
+            let frame = block_frame(&block, channels, channel);
+            let decoded = decode_frame(frame, coeffs, history)?;
+
+            for (index, sample) in decoded.into_iter().take(len).enumerate() {
+                samples[(frame_start + index) * channels + channel] = sample;
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+// Gathers the frame of a channel from a block of sub-interleaved frames.
+fn block_frame(block: &[u8], channels: usize, channel: usize) -> [u8; FRAME_SIZE] {
+    let mut frame = [0; FRAME_SIZE];
+
+    for (index, byte) in frame.iter_mut().enumerate() {
+        let offset = index / SUBINTERLEAVE * SUBINTERLEAVE * channels
+            + SUBINTERLEAVE * channel
+            + index % SUBINTERLEAVE;
+        *byte = block[offset];
+    }
+
+    frame
+}
+
+// Decodes the samples of a single frame, updating the history of previous samples in the channel.
+#[allow(clippy::cast_possible_truncation)]
+fn decode_frame(
+    frame: [u8; FRAME_SIZE],
+    table: &[i16; 16],
+    history: &mut [i32; 2],
+) -> Result<[i16; SAMPLES_PER_FRAME], GcAdpcmError> {
+    // the upper nibble selects a pair of coefficients, and the lower nibble is the scale exponent
+    let predictor = usize::from(frame[0] >> 4);
+    let scale = 1 << (frame[0] & 0x0F);
+
+    if predictor >= table.len() / 2 {
+        return Err(GcAdpcmError::new(GcAdpcmErrorKind::InvalidFrame));
+    }
+
+    let coeff1 = i32::from(table[predictor * 2]);
+    let coeff2 = i32::from(table[predictor * 2 + 1]);
+    let mut samples = [0; SAMPLES_PER_FRAME];
+
+    for (index, sample) in samples.iter_mut().enumerate() {
+        let byte = frame[1 + index / 2];
+        let nibble = if index % 2 == 0 {
+            byte >> 4
+        } else {
+            byte & 0x0F
+        };
+        // nibbles are signed 4-bit integers
+        let nibble = i32::from(nibble) - if nibble >= 8 { 16 } else { 0 };
+
+        let value =
+            (((nibble * scale) << 11) + 1024 + coeff1 * history[0] + coeff2 * history[1]) >> 11;
+        let value = value.clamp(i16::MIN.into(), i16::MAX.into());
+
+        history[1] = history[0];
+        history[0] = value;
+        // the value is clamped to the range of i16, so truncation is harmless
+        *sample = value as i16;
+    }
+
+    Ok(samples)
+}
+
+/// Represents an error that can occur when decoding a GC ADPCM stream.
+///
+/// See [`GcAdpcmErrorKind`] for the different kinds of errors that can occur.
+#[derive(Debug)]
+pub struct GcAdpcmError {
+    kind: GcAdpcmErrorKind,
+    source: Option<ReadError>,
+}
+
+/// A variant of a [`GcAdpcmError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GcAdpcmErrorKind {
+    /// The stream header did not contain DSP coefficients for every channel.
+    /// See [`BankBuilder::fallback_dsp_coefficients`] for streams without coefficients.
+    ///
+    /// [`BankBuilder::fallback_dsp_coefficients`]: crate::BankBuilder::fallback_dsp_coefficients
+    MissingCoefficients,
+    /// The stream data was too small to contain the number of samples in the stream header.
+    Truncated,
+    /// Failed to read a frame from the stream data.
+    ReadFrame,
+    /// A frame header referred to coefficients that don't exist.
+    InvalidFrame,
+}
+
+impl GcAdpcmError {
+    fn new(kind: GcAdpcmErrorKind) -> Self {
+        Self { kind, source: None }
+    }
+
+    fn from_read(kind: GcAdpcmErrorKind) -> impl FnOnce(ReadError) -> Self {
+        move |source| Self {
+            kind,
+            source: Some(source),
+        }
+    }
+
+    /// Returns the [`GcAdpcmErrorKind`] associated with this error.
+    #[must_use]
+    pub fn kind(&self) -> GcAdpcmErrorKind {
+        self.kind
+    }
+}
+
+impl Display for GcAdpcmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.kind.fmt(f)
+    }
+}
+
+impl Error for GcAdpcmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.source {
+            Some(e) => Some(e),
+            None => None,
+        }
+    }
+}
+
+impl Display for GcAdpcmErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(match self {
+            Self::MissingCoefficients => "DSP coefficients were not found in stream header",
+            Self::Truncated => "GC ADPCM stream data was smaller than expected",
+            Self::ReadFrame => "failed to read frame from GC ADPCM stream data",
+            Self::InvalidFrame => "frame header of GC ADPCM stream data was invalid",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, GcAdpcmErrorKind};
+    use crate::header::StreamInfo;
+    use crate::read::Reader;
+
+    // the first coefficient pair predicts each sample as the previous sample
+    const COEFFS: [i16; 16] = [2048, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    #[test]
+    fn decode_subinterleaved_frames() {
+        let mut info = StreamInfo::new_test(44100, 2, 16, 32);
+        info.dsp_coeffs = Some(vec![COEFFS; 2].into_boxed_slice());
+
+        // left channel rises by 1 every sample, right channel falls by 2 every sample (scale of 2);
+        // every block interleaves the left and right frames 2 bytes at a time
+        let data = [
+            [0x00, 0x11, 0x01, 0xFF, 0x11, 0x11, 0xFF, 0xFF],
+            [0x11, 0x11, 0xFF, 0xFF, 0x11, 0x11, 0xFF, 0xFF],
+            [0x00, 0x11, 0x01, 0xFF, 0x00, 0x00, 0x00, 0x00],
+            [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        ]
+        .concat();
+
+        let samples = decode(&info, &mut Reader::new(data.as_slice())).unwrap();
+
+        // samples after the sample count are not decoded
+        assert_eq!(samples.len(), 32);
+        assert_eq!(samples[..4], [1, -2, 2, -4]);
+        assert_eq!(samples[30..], [16, -32]);
+    }
+
+    #[test]
+    fn reject_invalid_streams() {
+        let mut info = StreamInfo::new_test(44100, 1, 15, 8);
+        let data = [0; 8];
+
+        let result = decode(&info, &mut Reader::new(data.as_slice()));
+        assert!(result.is_err_and(|e| e.kind() == GcAdpcmErrorKind::MissingCoefficients));
+
+        info.dsp_coeffs = Some(vec![COEFFS].into_boxed_slice());
+        let result = decode(&info, &mut Reader::new(data.as_slice()));
+        assert!(result.is_err_and(|e| e.kind() == GcAdpcmErrorKind::Truncated));
+
+        info.num_samples = 14.try_into().ok();
+        let result = decode(&info, &mut Reader::new([0x80; 8].as_slice()));
+        assert!(result.is_err_and(|e| e.kind() == GcAdpcmErrorKind::InvalidFrame));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::header::StreamInfo;
+use crate::read::{ReadError, Reader};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::Read,
+};
+
+// IMA ADPCM information taken from:
+// [1]: https://github.com/vgmstream/vgmstream/blob/master/src/coding/ima_decoder.c
+// [2]: https://github.com/vgmstream/vgmstream/blob/master/src/meta/fsb5.c
+
+// FMOD stores IMA ADPCM in blocks of 0x24 bytes per channel (the Xbox IMA ADPCM layout).
+const FMOD_BLOCK_SIZE: usize = 0x24;
+// Every channel in a block starts with a 4-byte header: the initial predictor (i16), step index (u8), and a reserved byte.
+const BLOCK_HEADER_SIZE: usize = 4;
+// After the headers, channels are interleaved every 4 bytes (8 nibbles).
+const INTERLEAVE_SIZE: usize = 4;
+
+const STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+const INDEX_TABLE: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+// Decodes the entire stream into interleaved 16-bit samples, using FMOD's block layout.
+pub(super) fn decode<R: Read>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+) -> Result<Vec<i16>, ImaAdpcmError> {
+    decode_blocks(info, FMOD_BLOCK_SIZE, source)
+}
+
+// Decodes the stream in blocks of `block_size` bytes per channel.
+// The predictor and step index of every channel are reset at the start of each block.
+fn decode_blocks<R: Read>(
+    info: &StreamInfo,
+    block_size: usize,
+    source: &mut Reader<R>,
+) -> Result<Vec<i16>, ImaAdpcmError> {
+    let channels = usize::from(info.channels.get());
+
+    // The header sample is the first sample of a block, and the final nibble of each channel is unused,
+    // so every block contains 2 samples per byte of nibbles.
+    let block_samples = (block_size - BLOCK_HEADER_SIZE) * 2;
+    let available = info.size.get() as usize / (block_size * channels) * block_samples;
+    let num_samples = info.num_samples.map_or(available, |samples| samples.get() as usize);
+
+    if num_samples > available {
+        return Err(ImaAdpcmError::new(ImaAdpcmErrorKind::Truncated));
+    }
+
+    let mut samples = vec![0; num_samples * channels];
+
+    for block_start in (0..num_samples).step_by(block_samples) {
+        let len = block_samples.min(num_samples - block_start);
+
+        let block = source
+            .take(block_size * channels)
+            .map_err(ImaAdpcmError::from_read(ImaAdpcmErrorKind::ReadBlock))?;
+        let (headers, data) = block.split_at(BLOCK_HEADER_SIZE * channels);
+
+        for (channel, header) in headers.chunks_exact(BLOCK_HEADER_SIZE).enumerate() {
+            let mut state = State {
+                predictor: i16::from_le_bytes([header[0], header[1]]).into(),
+                step_index: header[2].min(88).into(),
+            };
+
+            // this channel's bytes within the interleaved data
+            let nibbles = data
+                .chunks_exact(INTERLEAVE_SIZE)
+                .skip(channel)
+                .step_by(channels)
+                .flatten()
+                .flat_map(|byte| [byte & 0x0F, byte >> 4]);
+
+            let mut outputs = samples[block_start * channels..]
+                .iter_mut()
+                .skip(channel)
+                .step_by(channels)
+                .take(len);
+
+            if let Some(sample) = outputs.next() {
+                *sample = state.sample();
+            }
+
+            for (sample, nibble) in outputs.zip(nibbles) {
+                state.expand(nibble);
+                *sample = state.sample();
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+struct State {
+    predictor: i32,
+    step_index: i32,
+}
+
+impl State {
+    fn expand(&mut self, nibble: u8) {
+        #[allow(clippy::cast_sign_loss)]
+        let step = STEP_TABLE[self.step_index as usize];
+
+        let mut delta = step >> 3;
+        if nibble & 1 != 0 {
+            delta += step >> 2;
+        }
+        if nibble & 2 != 0 {
+            delta += step >> 1;
+        }
+        if nibble & 4 != 0 {
+            delta += step;
+        }
+        if nibble & 8 != 0 {
+            delta = -delta;
+        }
+
+        self.predictor = (self.predictor + delta).clamp(i16::MIN.into(), i16::MAX.into());
+        self.step_index = (self.step_index + INDEX_TABLE[usize::from(nibble & 7)]).clamp(0, 88);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn sample(&self) -> i16 {
+        // the predictor is clamped to the range of i16, so truncation is harmless
+        self.predictor as i16
+    }
+}
+
+/// Represents an error that can occur when decoding an IMA ADPCM stream.
+///
+/// See [`ImaAdpcmErrorKind`] for the different kinds of errors that can occur.
+#[derive(Debug)]
+pub struct ImaAdpcmError {
+    kind: ImaAdpcmErrorKind,
+    source: Option<ReadError>,
+}
+
+/// A variant of an [`ImaAdpcmError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ImaAdpcmErrorKind {
+    /// The stream data was too small to contain the number of samples in the stream header.
+    Truncated,
+    /// Failed to read a block from the stream data.
+    ReadBlock,
+}
+
+impl ImaAdpcmError {
+    fn new(kind: ImaAdpcmErrorKind) -> Self {
+        Self { kind, source: None }
+    }
+
+    fn from_read(kind: ImaAdpcmErrorKind) -> impl FnOnce(ReadError) -> Self {
+        move |source| Self {
+            kind,
+            source: Some(source),
+        }
+    }
+
+    /// Returns the [`ImaAdpcmErrorKind`] associated with this error.
+    #[must_use]
+    pub fn kind(&self) -> ImaAdpcmErrorKind {
+        self.kind
+    }
+}
+
+impl Display for ImaAdpcmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.kind.fmt(f)
+    }
+}
+
+impl Error for ImaAdpcmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.source {
+            Some(e) => Some(e),
+            None => None,
+        }
+    }
+}
+
+impl Display for ImaAdpcmErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(match self {
+            Self::Truncated => "IMA ADPCM stream data was smaller than expected",
+            Self::ReadBlock => "failed to read block from IMA ADPCM stream data",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, decode_blocks, ImaAdpcmErrorKind};
+    use crate::header::StreamInfo;
+    use crate::read::Reader;
+
+    // a single stereo block: the left channel rises by 1 every sample, and the right channel falls by 1 every sample
+    fn stereo_block() -> Vec<u8> {
+        let mut block = Vec::new();
+        block.extend_from_slice(&100i16.to_le_bytes());
+        block.extend_from_slice(&[0, 0]);
+        block.extend_from_slice(&(-100i16).to_le_bytes());
+        block.extend_from_slice(&[0, 0]);
+
+        for _ in 0..8 {
+            block.extend_from_slice(&[0x11; 4]);
+            block.extend_from_slice(&[0x99; 4]);
+        }
+
+        block
+    }
+
+    #[test]
+    fn decode_stereo_block() {
+        let info = StreamInfo::new_test(44100, 2, 64, 0x48);
+        let data = stereo_block();
+
+        let samples = decode(&info, &mut Reader::new(data.as_slice())).unwrap();
+
+        // the header sample is the first sample of each channel
+        assert_eq!(samples.len(), 128);
+        assert_eq!(samples[..6], [100, -100, 101, -101, 102, -102]);
+        assert_eq!(samples[126..], [163, -163]);
+    }
+
+    #[test]
+    fn stop_at_sample_count() {
+        let info = StreamInfo::new_test(44100, 2, 10, 0x48);
+        let data = stereo_block();
+
+        let samples = decode(&info, &mut Reader::new(data.as_slice())).unwrap();
+        assert_eq!(samples.len(), 20);
+        assert_eq!(samples[18..], [109, -109]);
+
+        let info = StreamInfo::new_test(44100, 2, 65, 0x48);
+        let result = decode(&info, &mut Reader::new(data.as_slice()));
+        assert!(result.is_err_and(|e| e.kind() == ImaAdpcmErrorKind::Truncated));
+    }
+
+    #[test]
+    fn reset_state_every_block() {
+        // 2 mono blocks of 8 bytes, each decoding to 8 samples
+        let info = StreamInfo::new_test(44100, 1, 16, 16);
+        let data = [
+            [0x10, 0x00, 0x00, 0x00, 0x11, 0x11, 0x11, 0x11],
+            [0x20, 0x00, 0x00, 0x00, 0x99, 0x99, 0x99, 0x99],
+        ]
+        .concat();
+
+        let samples = decode_blocks(&info, 8, &mut Reader::new(data.as_slice())).unwrap();
+        assert_eq!(
+            samples,
+            [16, 17, 18, 19, 20, 21, 22, 23, 32, 31, 30, 29, 28, 27, 26, 25]
+        );
+    }
+}
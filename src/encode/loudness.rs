@@ -0,0 +1,196 @@
+use std::{
+    f64::consts::PI,
+    num::{NonZeroU32, NonZeroUsize},
+};
+
+// Loudness measurement information taken from:
+// [1]: https://www.itu.int/rec/R-REC-BS.1770
+// [2]: https://github.com/jiixyj/libebur128
+
+// blocks with a lower loudness are ignored entirely
+const ABSOLUTE_GATE: f64 = -70.0;
+// blocks quieter than the loudness of the remaining blocks by more than this are ignored
+const RELATIVE_GATE: f64 = -10.0;
+
+// Measures the integrated loudness (in LUFS) of interleaved samples, as specified in ITU-R BS.1770.
+// Negative infinity is returned if the samples are silent or shorter than a single 400 ms block.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+pub(super) fn integrated_loudness(
+    samples: &[f32],
+    channels: NonZeroUsize,
+    sample_rate: NonZeroU32,
+) -> f32 {
+    let channels = channels.get();
+    let rate = f64::from(sample_rate.get());
+    let weights = channel_weights(channels);
+
+    // Every frame is K-weighted, then the weighted energy of all channels is summed.
+    // Block energies are then calculated from a running total.
+    let mut filters = vec![KWeighting::new(rate); channels];
+    let mut totals = Vec::with_capacity(samples.len() / channels + 1);
+    let mut total = 0.0;
+    totals.push(total);
+
+    for frame in samples.chunks_exact(channels) {
+        for ((filter, weight), &sample) in filters.iter_mut().zip(weights).zip(frame) {
+            let filtered = filter.process(f64::from(sample));
+            total += weight * filtered * filtered;
+        }
+        totals.push(total);
+    }
+
+    // blocks are 400 ms long and overlap by 75%
+    let block_len = ((rate * 0.4).round() as usize).max(1);
+    let step = ((rate * 0.1).round() as usize).max(1);
+
+    let powers: Vec<f64> = (0..totals.len().saturating_sub(block_len))
+        .step_by(step)
+        .map(|start| (totals[start + block_len] - totals[start]) / block_len as f64)
+        .filter(|&power| loudness(power) > ABSOLUTE_GATE)
+        .collect();
+
+    if powers.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+
+    let threshold = loudness(mean(&powers)) + RELATIVE_GATE;
+    let gated: Vec<f64> = powers
+        .into_iter()
+        .filter(|&power| loudness(power) > threshold)
+        .collect();
+
+    loudness(mean(&gated)) as f32
+}
+
+fn loudness(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+// 5.1 streams (ordered L, R, C, LFE, Ls, Rs) ignore the LFE channel and emphasize the surround channels.
+// Every channel of other layouts has the same weight.
+fn channel_weights(channels: usize) -> &'static [f64] {
+    const SURROUND: [f64; 6] = [1.0, 1.0, 1.0, 0.0, 1.41, 1.41];
+    const EQUAL: [f64; 8] = [1.0; 8];
+
+    match channels {
+        6 => &SURROUND,
+        _ => &EQUAL[..channels.min(EQUAL.len())],
+    }
+}
+
+// The K-weighting filter: a high shelf that models the acoustic effect of the head, followed by a high-pass filter.
+// Coefficients are derived for the sample rate, so streams don't have to be resampled to 48 kHz.
+#[derive(Clone)]
+struct KWeighting {
+    shelf: Biquad,
+    high_pass: Biquad,
+}
+
+impl KWeighting {
+    fn new(rate: f64) -> Self {
+        let shelf = {
+            let (f0, gain, q) =
+                (1_681.974_450_955_533, 3.999_843_853_973_347, 0.707_175_236_955_419_6);
+            let k = (PI * f0 / rate).tan();
+            let vh = 10f64.powf(gain / 20.0);
+            let vb = vh.powf(0.499_666_774_154_541_6);
+            let a0 = 1.0 + k / q + k * k;
+
+            Biquad::new(
+                [
+                    (vh + vb * k / q + k * k) / a0,
+                    2.0 * (k * k - vh) / a0,
+                    (vh - vb * k / q + k * k) / a0,
+                ],
+                [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            )
+        };
+
+        let high_pass = {
+            let (f0, q) = (38.135_470_876_024_44, 0.500_327_037_323_877_3);
+            let k = (PI * f0 / rate).tan();
+            let a0 = 1.0 + k / q + k * k;
+
+            Biquad::new(
+                [1.0, -2.0, 1.0],
+                [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+            )
+        };
+
+        Self { shelf, high_pass }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.high_pass.process(self.shelf.process(sample))
+    }
+}
+
+// A second-order IIR filter in transposed direct form II.
+#[derive(Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            state: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.state[0];
+        self.state[0] = self.b[1] * input - self.a[0] * output + self.state[1];
+        self.state[1] = self.b[2] * input - self.a[1] * output;
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::integrated_loudness;
+    use std::{
+        f32::consts::TAU,
+        num::{NonZeroU32, NonZeroUsize},
+    };
+
+    #[allow(clippy::cast_precision_loss)]
+    fn sine(frequency: f32, amplitude: f32, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| amplitude * (TAU * frequency * n as f32 / rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn measure_sine_loudness() {
+        let rate = NonZeroU32::new(48000).unwrap();
+        let mono = NonZeroUsize::new(1).unwrap();
+
+        // a full-scale 1 kHz sine in a single channel measures -3.01 LUFS
+        let samples = sine(1000.0, 1.0, 48000, 96000);
+        let loudness = integrated_loudness(&samples, mono, rate);
+        assert!((loudness + 3.01).abs() < 0.05, "{loudness}");
+
+        // halving the amplitude lowers the loudness by 6.02 LU
+        let samples = sine(1000.0, 0.5, 48000, 96000);
+        let loudness = integrated_loudness(&samples, mono, rate);
+        assert!((loudness + 9.03).abs() < 0.05, "{loudness}");
+
+        let is_silent = |loudness: f32| loudness.is_infinite() && loudness.is_sign_negative();
+        assert!(is_silent(integrated_loudness(&vec![0.0; 48000], mono, rate)));
+        assert!(is_silent(integrated_loudness(&[1.0; 100], mono, rate)));
+    }
+}
@@ -2,17 +2,54 @@
 
 use crate::header::{AudioFormat, StreamInfo};
 use crate::read::Reader;
-use std::io::{Read, Write};
+#[cfg(feature = "resample")]
+use std::num::NonZeroU32;
+use std::num::NonZeroUsize;
+use std::{
+    borrow::Cow,
+    io::{copy, BufWriter, Read, Seek, Write},
+    iter::from_fn,
+    ops::Range,
+    time::Duration,
+};
 
+mod atrac9;
+mod bext;
+// shared by decoders that write 16-bit PCM
+mod convert;
 mod error;
+mod fadpcm;
+mod gcadpcm;
+mod ima;
+#[cfg(feature = "loudness")]
+mod loudness;
+mod mpeg;
+mod options;
+mod opus;
 mod pcm;
+#[cfg(feature = "resample")]
+mod resample;
+mod stereo;
 mod vorbis;
 mod vorbis_lookup;
+mod xwma;
 
+pub use atrac9::{Atrac9Error, Atrac9ErrorKind};
+pub use bext::BroadcastMetadata;
 pub use error::EncodeError;
-use pcm::{Endianness, Format};
+pub use fadpcm::{FadpcmError, FadpcmErrorKind};
+pub use gcadpcm::{GcAdpcmError, GcAdpcmErrorKind};
+pub use ima::{ImaAdpcmError, ImaAdpcmErrorKind};
+pub use mpeg::{MpegError, MpegErrorKind};
+pub use options::{EncodeOptions, VorbisLayerMode, VorbisOutput};
+pub use opus::{OpusError, OpusErrorKind};
+pub(crate) use pcm::Endianness;
+use pcm::Format;
 pub use pcm::{PcmError, PcmErrorKind};
-pub use vorbis::{VorbisError, VorbisErrorKind};
+pub use stereo::ChannelCorrelation;
+pub(crate) use vorbis::SetupHeaderCache;
+pub use vorbis::{vorbis_setup_header, VorbisError, VorbisErrorKind};
+pub use xwma::{XwmaError, XwmaErrorKind};
 
 pub(crate) fn encode<R: Read, W: Write>(
     format: AudioFormat,
@@ -20,33 +57,958 @@ pub(crate) fn encode<R: Read, W: Write>(
     info: &StreamInfo,
     source: &mut Reader<R>,
     sink: W,
+    options: &EncodeOptions,
 ) -> Result<W, EncodeError> {
-    // method of determining sample endianness for PCM24, PCM32, and PCMFLOAT is currently unknown
-    Ok(match format {
+    encode_counted(format, flags, info, source, sink, options).map(|(sink, _)| sink)
+}
+
+// Encodes the stream, returning the writer and the number of sample frames written.
+// PCM frames are counted from the sample data actually read and written, and decoded streams (e.g. Vorbis or ADPCM)
+// from their decoded samples. Streams that are copied without decoding report the sample count from the stream header.
+pub(crate) fn encode_counted<R: Read, W: Write>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    sink: W,
+    options: &EncodeOptions,
+) -> Result<(W, u64), EncodeError> {
+    encode_cached(
+        format,
+        flags,
+        info,
+        source,
+        sink,
+        options,
+        &mut SetupHeaderCache::default(),
+    )
+}
+
+// Encodes the stream like `encode_counted`, taking Vorbis setup headers from `cache` (and adding them if missing),
+// so setup headers shared by many streams are only parsed once.
+#[allow(clippy::too_many_lines)]
+pub(crate) fn encode_cached<R: Read, W: Write>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    sink: W,
+    options: &EncodeOptions,
+    cache: &mut SetupHeaderCache,
+) -> Result<(W, u64), EncodeError> {
+    if let (AudioFormat::Vorbis, Some(crc32)) = (format, options.vorbis_crc32) {
+        if info.vorbis_crc32 != Some(crc32) {
+            let info = StreamInfo {
+                vorbis_crc32: Some(crc32),
+                ..info.clone()
+            };
+            return encode_cached(format, flags, &info, source, sink, options, cache);
+        }
+    }
+
+    if let (true, 2, Some(byte_depth)) = (
+        options.swap_stereo_channels,
+        info.channels.get(),
+        pcm_byte_depth(format),
+    ) {
+        let data = pcm::read_swapped(info, byte_depth, source)?;
+        let options = EncodeOptions {
+            swap_stereo_channels: false,
+            ..options.clone()
+        };
+        return encode_cached(
+            format,
+            flags,
+            info,
+            &mut Reader::new(data.as_slice()),
+            sink,
+            &options,
+            cache,
+        );
+    }
+
+    if let (true, 2.., Some(byte_depth)) =
+        (options.downmix_dual_mono, info.channels.get(), pcm_byte_depth(format))
+    {
+        let (info, data) = pcm::read_dual_mono(info, byte_depth, source)?;
+        let options = EncodeOptions {
+            downmix_dual_mono: false,
+            ..options.clone()
+        };
+        return encode_cached(
+            format,
+            flags,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            sink,
+            &options,
+            cache,
+        );
+    }
+
+    #[cfg(feature = "resample")]
+    if let Some(rate) = options.resample_to.filter(|rate| *rate != info.sample_rate) {
+        // streams that can't be decoded are encoded as usual, without resampling
+        if can_decode(format) {
+            return encode_resampled(format, flags, info, rate, source, sink, options, cache);
+        }
+    }
+
+    if format == AudioFormat::Vorbis && options.vorbis_output != VorbisOutput::Ogg {
+        let samples =
+            vorbis::decode_interleaved(info, source, cache, &options.vorbis_setup_headers)?;
+        let frames = (samples.len() / usize::from(info.channels.get())) as u64;
+
+        let sink = if options.vorbis_output == VorbisOutput::Pcm {
+            let mut pcm_samples = Vec::new();
+            // a fixed seed keeps the output the same across runs
+            let mut dither = options.dither.then(|| convert::Dither::new(1));
+            convert::f32_to_i16(&samples, &mut pcm_samples, dither.as_mut());
+            pcm::encode_i16_samples(info, &pcm_samples, sink)?
+        } else {
+            pcm::encode_float_samples(info, &samples, sink)?
+        };
+        return Ok((sink, frames));
+    }
+
+    if format == AudioFormat::Vorbis {
+        return Ok(vorbis::encode(info, source, sink, options, cache)?);
+    }
+
+    if format == AudioFormat::GcAdpcm {
+        let samples = gcadpcm::decode(info, source)?;
+        let frames = (samples.len() / usize::from(info.channels.get())) as u64;
+        return Ok((pcm::encode_i16_samples(info, &samples, sink)?, frames));
+    }
+
+    if format == AudioFormat::ImaAdpcm {
+        let samples = ima::decode(info, source)?;
+        let frames = (samples.len() / usize::from(info.channels.get())) as u64;
+        return Ok((pcm::encode_i16_samples(info, &samples, sink)?, frames));
+    }
+
+    if format == AudioFormat::FAdpcm {
+        let samples = fadpcm::decode(info, source)?;
+        let frames = (samples.len() / usize::from(info.channels.get())) as u64;
+        return Ok((pcm::encode_i16_samples(info, &samples, sink)?, frames));
+    }
+
+    if format == AudioFormat::Mpeg {
+        let sink = mpeg::encode(info, source, sink)?;
+        return Ok((sink, info.num_samples.map_or(0, |samples| samples.get().into())));
+    }
+
+    if format == AudioFormat::Atrac9 {
+        let sink = atrac9::encode(info, source, sink)?;
+        return Ok((sink, info.num_samples.map_or(0, |samples| samples.get().into())));
+    }
+
+    if format == AudioFormat::Xwma {
+        let sink = xwma::encode(info, source, sink)?;
+        return Ok((sink, info.num_samples.map_or(0, |samples| samples.get().into())));
+    }
+
+    if format == AudioFormat::Opus {
+        let sink = opus::encode(info, source, sink)?;
+        return Ok((sink, info.num_samples.map_or(0, |samples| samples.get().into())));
+    }
+
+    encode_pcm(format, flags, info, source, sink, options)
+}
+
+// Encodes a PCM stream, returning the writer and the number of sample frames written.
+fn encode_pcm<R: Read, W: Write>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    sink: W,
+    options: &EncodeOptions,
+) -> Result<(W, u64), EncodeError> {
+    let order = pcm_endianness(format, flags).unwrap_or(Endianness::Little);
+    let bext = options.broadcast_metadata.as_ref();
+    let start_pos = source.position();
+
+    let sink = match format {
+        AudioFormat::Pcm8 if options.pcm_as_float => {
+            pcm::encode_float::<_, _, 1>(order, info, bext, source, sink)?
+        }
+        AudioFormat::Pcm16 if options.pcm_as_float => {
+            pcm::encode_float::<_, _, 2>(order, info, bext, source, sink)?
+        }
+        AudioFormat::Pcm24 if options.pcm_as_float => {
+            pcm::encode_float::<_, _, 3>(order, info, bext, source, sink)?
+        }
+        AudioFormat::Pcm32 if options.pcm_as_float => {
+            pcm::encode_float::<_, _, 4>(order, info, bext, source, sink)?
+        }
         AudioFormat::Pcm8 => {
-            // endianness doesn't matter when samples are 1 byte wide
-            pcm::encode::<_, _, 1>(Format::Integer, Endianness::Little, info, source, sink)?
+            pcm::encode::<_, _, 1>(Format::Integer, order, info, bext, source, sink)?
         }
         AudioFormat::Pcm16 => {
-            // determine sample endianness from flags in file header
-            let order = if flags & 0x01 == 1 {
-                Endianness::Big
-            } else {
-                Endianness::Little
+            pcm::encode::<_, _, 2>(Format::Integer, order, info, bext, source, sink)?
+        }
+        AudioFormat::Pcm24 => {
+            pcm::encode::<_, _, 3>(Format::Integer, order, info, bext, source, sink)?
+        }
+        AudioFormat::Pcm32 => {
+            pcm::encode::<_, _, 4>(Format::Integer, order, info, bext, source, sink)?
+        }
+        AudioFormat::PcmFloat => {
+            pcm::encode::<_, _, 4>(Format::Float, order, info, bext, source, sink)?
+        }
+        _ => return Err(EncodeError::UnsupportedFormat { format }),
+    };
+
+    // frames are counted from the samples that were actually read and written,
+    // so a source that ends early results in fewer frames than the stream size implies
+    let byte_depth = pcm_byte_depth(format).expect("format is a PCM format");
+    let frame_size = byte_depth * usize::from(info.channels.get());
+
+    Ok((sink, ((source.position() - start_pos) / frame_size) as u64))
+}
+
+// Encodes the stream through an internal buffer, so small writes (e.g. of single samples) are batched.
+// The buffer is flushed before the original writer is returned.
+pub(crate) fn encode_buffered<R: Read, W: Write>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    sink: W,
+    options: &EncodeOptions,
+) -> Result<W, EncodeError> {
+    encode(format, flags, info, source, BufWriter::new(sink), options)?
+        .into_inner()
+        .map_err(|e| EncodeError::Flush(e.into_error()))
+}
+
+// Decodes the stream, then resamples it and writes it with 32-bit float samples.
+#[cfg(feature = "resample")]
+#[allow(clippy::too_many_arguments)]
+fn encode_resampled<R: Read, W: Write>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    sample_rate: NonZeroU32,
+    source: &mut Reader<R>,
+    sink: W,
+    options: &EncodeOptions,
+    cache: &mut SetupHeaderCache,
+) -> Result<(W, u64), EncodeError> {
+    let samples = decode_interleaved(format, flags, info, source, options, cache)?;
+    let channels = NonZeroUsize::from(info.channels);
+    let resampled = resample::resample_linear(&samples, channels, info.sample_rate, sample_rate);
+
+    let mut info = info.clone();
+    info.sample_rate = sample_rate;
+
+    let frames = (resampled.len() / channels.get()) as u64;
+    Ok((pcm::encode_float_samples(&info, &resampled, sink)?, frames))
+}
+
+// Returns whether the headers needed to decode a Vorbis stream can be reconstructed,
+// honoring the setup header CRC32 override and setup headers in `options` like `encode_cached`.
+pub(crate) fn can_init_vorbis_headers(
+    info: &StreamInfo,
+    options: &EncodeOptions,
+    cache: &mut SetupHeaderCache,
+) -> bool {
+    let crc32 = options.vorbis_crc32.or(info.vorbis_crc32);
+    vorbis::can_init_headers(info, crc32, cache, &options.vorbis_setup_headers)
+}
+
+// Decodes the stream and returns the maximum absolute amplitude of each channel.
+pub(crate) fn measure_peaks<R: Read>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    options: &EncodeOptions,
+) -> Result<Vec<f32>, EncodeError> {
+    let samples =
+        decode_interleaved(format, flags, info, source, options, &mut SetupHeaderCache::default())?;
+    let channels = NonZeroUsize::from(info.channels).get();
+    let mut peaks = vec![0.0_f32; channels];
+
+    for frame in samples.chunks(channels) {
+        for (peak, sample) in peaks.iter_mut().zip(frame) {
+            *peak = peak.max(sample.abs());
+        }
+    }
+
+    Ok(peaks)
+}
+
+// Decodes the stream and measures its integrated loudness, in LUFS.
+#[cfg(feature = "loudness")]
+pub(crate) fn measure_loudness<R: Read>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    options: &EncodeOptions,
+) -> Result<f32, EncodeError> {
+    let samples =
+        decode_interleaved(format, flags, info, source, options, &mut SetupHeaderCache::default())?;
+    let channels = NonZeroUsize::from(info.channels);
+
+    Ok(loudness::integrated_loudness(&samples, channels, info.sample_rate))
+}
+
+// Decodes the stream, then splits the interleaved samples into chunks of `frames_per_chunk` frames.
+// The final chunk can be shorter. If decoding fails, the error is the only item.
+pub(crate) fn decode_chunked<R: Read>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    frames_per_chunk: NonZeroUsize,
+    source: &mut Reader<R>,
+    options: &EncodeOptions,
+) -> impl Iterator<Item = Result<Vec<f32>, EncodeError>> {
+    let chunk_len = frames_per_chunk.get().saturating_mul(info.channels.get().into());
+
+    let cache = &mut SetupHeaderCache::default();
+    let (samples, error) = match decode_interleaved(format, flags, info, source, options, cache) {
+        Ok(samples) => (samples, None),
+        Err(e) => (Vec::new(), Some(e)),
+    };
+    let mut samples = samples.into_iter();
+
+    error.map(Err).into_iter().chain(from_fn(move || {
+        let chunk: Vec<_> = samples.by_ref().take(chunk_len).collect();
+        (!chunk.is_empty()).then_some(Ok(chunk))
+    }))
+}
+
+// Decodes the entire stream into interleaved 32-bit float samples.
+// Vorbis setup headers are looked up like `encode_cached`, honoring the setup headers and CRC32 override in `options`.
+fn decode_interleaved<R: Read>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    options: &EncodeOptions,
+    cache: &mut SetupHeaderCache,
+) -> Result<Vec<f32>, EncodeError> {
+    let order = pcm_endianness(format, flags).unwrap_or(Endianness::Little);
+
+    Ok(match format {
+        AudioFormat::Pcm8 => pcm::decode_float::<_, 1>(Format::Integer, order, info, source)?,
+        AudioFormat::Pcm16 => pcm::decode_float::<_, 2>(Format::Integer, order, info, source)?,
+        AudioFormat::Pcm24 => pcm::decode_float::<_, 3>(Format::Integer, order, info, source)?,
+        AudioFormat::Pcm32 => pcm::decode_float::<_, 4>(Format::Integer, order, info, source)?,
+        AudioFormat::PcmFloat => pcm::decode_float::<_, 4>(Format::Float, order, info, source)?,
+        AudioFormat::Vorbis => vorbis::decode_interleaved(
+            &with_vorbis_crc32(info, options),
+            source,
+            cache,
+            &options.vorbis_setup_headers,
+        )?,
+        AudioFormat::GcAdpcm => gcadpcm::decode(info, source)?
+            .into_iter()
+            .map(|sample| f32::from(sample) / 32768.0)
+            .collect(),
+        AudioFormat::ImaAdpcm => ima::decode(info, source)?
+            .into_iter()
+            .map(|sample| f32::from(sample) / 32768.0)
+            .collect(),
+        AudioFormat::FAdpcm => fadpcm::decode(info, source)?
+            .into_iter()
+            .map(|sample| f32::from(sample) / 32768.0)
+            .collect(),
+        _ => return Err(EncodeError::UnsupportedFormat { format }),
+    })
+}
+
+// Returns the stream info with the setup header CRC32 from `options`, if one was supplied.
+fn with_vorbis_crc32<'a>(info: &'a StreamInfo, options: &EncodeOptions) -> Cow<'a, StreamInfo> {
+    match options.vorbis_crc32 {
+        Some(crc32) if info.vorbis_crc32 != Some(crc32) => Cow::Owned(StreamInfo {
+            vorbis_crc32: Some(crc32),
+            ..info.clone()
+        }),
+        _ => Cow::Borrowed(info),
+    }
+}
+
+// Returns whether streams in the format can be decoded to samples with `decode_interleaved`.
+#[cfg(feature = "resample")]
+fn can_decode(format: AudioFormat) -> bool {
+    pcm_byte_depth(format).is_some()
+        || matches!(
+            format,
+            AudioFormat::Vorbis
+                | AudioFormat::GcAdpcm
+                | AudioFormat::ImaAdpcm
+                | AudioFormat::FAdpcm
+        )
+}
+
+// Returns the size of a single sample in bytes, or `None` for formats other than PCM.
+fn pcm_byte_depth(format: AudioFormat) -> Option<usize> {
+    match format {
+        AudioFormat::Pcm8 => Some(1),
+        AudioFormat::Pcm16 => Some(2),
+        AudioFormat::Pcm24 => Some(3),
+        AudioFormat::Pcm32 | AudioFormat::PcmFloat => Some(4),
+        _ => None,
+    }
+}
+
+// Decodes a stereo stream and compares its channels, or returns `None` for other channel counts.
+pub(crate) fn stereo_correlation<R: Read>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    options: &EncodeOptions,
+) -> Result<Option<ChannelCorrelation>, EncodeError> {
+    if info.channels.get() != 2 {
+        return Ok(None);
+    }
+
+    let samples =
+        decode_interleaved(format, flags, info, source, options, &mut SetupHeaderCache::default())?;
+    Ok(Some(stereo::correlate(&samples)))
+}
+
+// Decodes the stream and checks whether every channel has the same samples, within a tolerance.
+// Streams with a single channel are not dual mono.
+pub(crate) fn is_dual_mono<R: Read>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    tolerance: f32,
+    source: &mut Reader<R>,
+    options: &EncodeOptions,
+) -> Result<bool, EncodeError> {
+    let channels = usize::from(info.channels.get());
+
+    if channels == 1 {
+        return Ok(false);
+    }
+
+    let samples =
+        decode_interleaved(format, flags, info, source, options, &mut SetupHeaderCache::default())?;
+
+    Ok(samples
+        .chunks_exact(channels)
+        .all(|frame| frame[1..].iter().all(|sample| (sample - frame[0]).abs() <= tolerance)))
+}
+
+// Returns the sample endianness used when encoding PCM streams, or `None` for other formats.
+pub(crate) fn pcm_endianness(format: AudioFormat, flags: u32) -> Option<Endianness> {
+    match format {
+        // endianness doesn't matter when samples are 1 byte wide
+        AudioFormat::Pcm8 => Some(Endianness::Little),
+        // determine sample endianness from flags in file header
+        // (big-endian banks, e.g. from the GameCube and Wii, set the same flag for every sample width)
+        AudioFormat::Pcm16 | AudioFormat::Pcm24 | AudioFormat::Pcm32 | AudioFormat::PcmFloat
+            if flags & 0x01 == 1 =>
+        {
+            Some(Endianness::Big)
+        }
+        AudioFormat::Pcm16 | AudioFormat::Pcm24 | AudioFormat::Pcm32 | AudioFormat::PcmFloat => {
+            Some(Endianness::Little)
+        }
+        _ => None,
+    }
+}
+
+pub(crate) fn encode_looped<R: Read, W: Write>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    repeats: u32,
+    source: &mut Reader<R>,
+    sink: W,
+    options: &EncodeOptions,
+) -> Result<W, EncodeError> {
+    let Some(byte_depth) = pcm_byte_depth(format) else {
+        return Err(EncodeError::UnsupportedFormat { format });
+    };
+
+    let Some(stream_loop) = info.stream_loop else {
+        return encode(format, flags, info, source, sink, options);
+    };
+
+    let frame_size = byte_depth * usize::from(info.channels.get());
+    let (info, data) = pcm::read_looped(info, stream_loop, frame_size, repeats.max(1), source)?;
+    encode(format, flags, &info, &mut Reader::new(data.as_slice()), sink, options)
+}
+
+// Only the Vorbis setup header options in `options` are used; PCM streams are written with their original samples.
+pub(crate) fn encode_time_range<R: Read, W: Write>(
+    format: AudioFormat,
+    flags: u32,
+    info: &StreamInfo,
+    range: Range<Duration>,
+    source: &mut Reader<R>,
+    sink: W,
+    options: &EncodeOptions,
+) -> Result<W, EncodeError> {
+    let to_sample = |time: Duration| {
+        // sample positions are clamped to the length of the stream, so truncation is harmless
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let sample = (time.as_secs_f64() * f64::from(info.sample_rate.get())).round() as u64;
+        sample.min(info.num_samples.map_or(0, |samples| samples.get().into()))
+    };
+    let (first, last) = (to_sample(range.start), to_sample(range.end));
+
+    if first >= last {
+        return Err(EncodeError::EmptyRange);
+    }
+
+    if format == AudioFormat::Vorbis {
+        let mut samples = Vec::new();
+        let mut position = 0;
+
+        // Vorbis streams can't be seeked without a seek table, so samples before the range are decoded and discarded.
+        let vorbis_info = with_vorbis_crc32(info, options);
+        let setup_headers = &options.vorbis_setup_headers;
+        let mut cache = SetupHeaderCache::default();
+        vorbis::decode(&vorbis_info, source, &mut cache, setup_headers, |block| {
+            let len = block.first().map_or(0, Vec::len);
+            let clamp = |sample: u64| {
+                usize::try_from(sample.saturating_sub(position)).map_or(len, |index| index.min(len))
             };
 
-            pcm::encode::<_, _, 2>(Format::Integer, order, info, source, sink)?
+            for index in clamp(first)..clamp(last) {
+                samples.extend(block.iter().map(|channel| channel[index]));
+            }
+
+            position += len as u64;
+            Ok(())
+        })?;
+
+        return Ok(pcm::encode_float_samples(info, &samples, sink)?);
+    }
+
+    let byte_depth = pcm_byte_depth(format).ok_or(EncodeError::UnsupportedFormat { format })?;
+    let frame_size = usize::from(info.channels.get()) * byte_depth;
+
+    let (info, data) = pcm::read_range(info, frame_size, first, last, source)?;
+    encode(
+        format,
+        flags,
+        &info,
+        &mut Reader::new(data.as_slice()),
+        sink,
+        &EncodeOptions::default(),
+    )
+}
+
+pub(crate) fn encode_combined<R: Read, W: Write + Seek>(
+    format: AudioFormat,
+    flags: u32,
+    info: &[StreamInfo],
+    source: &mut Reader<R>,
+    sink: W,
+) -> Result<W, EncodeError> {
+    let order = pcm_endianness(format, flags).unwrap_or(Endianness::Little);
+
+    Ok(match format {
+        AudioFormat::Pcm8 => {
+            pcm::encode_combined::<_, _, 1>(Format::Integer, order, info, source, sink)?
+        }
+        AudioFormat::Pcm16 => {
+            pcm::encode_combined::<_, _, 2>(Format::Integer, order, info, source, sink)?
         }
         AudioFormat::Pcm24 => {
-            pcm::encode::<_, _, 3>(Format::Integer, Endianness::Little, info, source, sink)?
+            pcm::encode_combined::<_, _, 3>(Format::Integer, order, info, source, sink)?
         }
         AudioFormat::Pcm32 => {
-            pcm::encode::<_, _, 4>(Format::Integer, Endianness::Little, info, source, sink)?
+            pcm::encode_combined::<_, _, 4>(Format::Integer, order, info, source, sink)?
         }
         AudioFormat::PcmFloat => {
-            pcm::encode::<_, _, 4>(Format::Float, Endianness::Little, info, source, sink)?
+            pcm::encode_combined::<_, _, 4>(Format::Float, order, info, source, sink)?
         }
-        AudioFormat::Vorbis => vorbis::encode(info, source, sink)?,
         _ => return Err(EncodeError::UnsupportedFormat { format }),
     })
 }
+
+pub(crate) fn write_raw<R: Read, W: Write>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    mut sink: W,
+) -> Result<W, EncodeError> {
+    // There could be more data after the stream, so a limit is placed on the number of bytes read.
+    copy(&mut source.limit(info.size.get() as usize), &mut sink)
+        .and_then(|_| sink.flush())
+        .map(|()| sink)
+        .map_err(EncodeError::Raw)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decode_chunked, encode, encode_cached, encode_counted, measure_peaks, vorbis_setup_header,
+        write_raw, EncodeError, EncodeOptions, SetupHeaderCache, VorbisErrorKind, VorbisOutput,
+    };
+    use crate::header::{AudioFormat, StreamInfo};
+    use crate::read::Reader;
+    #[cfg(feature = "resample")]
+    use std::num::NonZeroU32;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn measure_peaks_per_channel() {
+        let info = StreamInfo::new_test(44100, 2, 3, 12);
+        let samples: [i16; 6] = [100, -16_384, -8_192, 0, 4_096, 8_192];
+        let data: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+
+        let peaks = measure_peaks(
+            AudioFormat::Pcm16,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            &EncodeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(peaks, [0.25, 0.5]);
+    }
+
+    #[test]
+    fn copy_raw_stream_data() {
+        let info = StreamInfo::new_test(44100, 2, 16, 6);
+        // the trailing bytes belong to the next stream
+        let data = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        let mut reader = Reader::new(data.as_slice());
+        assert_eq!(write_raw(&info, &mut reader, Vec::new()).unwrap(), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(reader.position(), 6);
+    }
+
+    #[test]
+    fn count_written_pcm_frames() {
+        let info = StreamInfo::new_test(44100, 2, 16, 64);
+        let data = [0; 64];
+
+        let (_, frames) = encode_counted(
+            AudioFormat::Pcm16,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &EncodeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(frames, 16);
+
+        // frames missing from a truncated source are not counted
+        let (_, frames) = encode_counted(
+            AudioFormat::Pcm16,
+            0,
+            &info,
+            &mut Reader::new(&data[..40]),
+            Vec::new(),
+            &EncodeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(frames, 10);
+
+        // options are applied to the output
+        let mut options = EncodeOptions::default();
+        let _ = options.pcm_as_float(true);
+        let (output, frames) = encode_counted(
+            AudioFormat::Pcm16,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(output[20..22], [3, 0]);
+        assert_eq!(frames, 16);
+    }
+
+    #[test]
+    fn swap_stereo_channels() {
+        const HEADER_SIZE: usize = 44;
+
+        // 2 frames of 16-bit stereo samples
+        let info = StreamInfo::new_test(44100, 2, 2, 8);
+        let data = [1, 0, 2, 0, 3, 0, 4, 0];
+        let mut options = EncodeOptions::default();
+        let _ = options.swap_stereo_channels(true);
+
+        let file = encode(
+            AudioFormat::Pcm16,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(file[HEADER_SIZE..], [2, 0, 1, 0, 4, 0, 3, 0]);
+
+        // mono streams are not affected
+        let info = StreamInfo::new_test(44100, 1, 4, 8);
+        let file = encode(
+            AudioFormat::Pcm16,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(file[HEADER_SIZE..], data);
+    }
+
+    #[test]
+    fn swap_big_endian_samples() {
+        const HEADER_SIZE: usize = 44;
+
+        // big-endian 24-bit samples, laid out like PCM24 data in GameCube-era banks (flag 0x01 set)
+        let info = StreamInfo::new_test(32000, 1, 2, 6);
+        let data = [0x12, 0x34, 0x56, 0xFF, 0xFF, 0xFE];
+
+        let file = encode(
+            AudioFormat::Pcm24,
+            0x01,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &EncodeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(file[HEADER_SIZE..], [0x56, 0x34, 0x12, 0xFE, 0xFF, 0xFF]);
+
+        // without the flag, samples are copied as-is
+        let file = encode(
+            AudioFormat::Pcm24,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &EncodeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(file[HEADER_SIZE..], data);
+
+        // the flag applies to 32-bit integer and float samples too
+        let info = StreamInfo::new_test(32000, 1, 1, 4);
+        let data = 0.5f32.to_be_bytes();
+
+        for format in [AudioFormat::Pcm32, AudioFormat::PcmFloat] {
+            let file = encode(
+                format,
+                0x01,
+                &info,
+                &mut Reader::new(data.as_slice()),
+                Vec::new(),
+                &EncodeOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(file[file.len() - 4..], 0.5f32.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn override_vorbis_crc32() {
+        let info = StreamInfo::new_test(44100, 2, 16, 64);
+        let data = [0; 64];
+        let encode_with = |options: &EncodeOptions| {
+            encode_counted(
+                AudioFormat::Vorbis,
+                0,
+                &info,
+                &mut Reader::new(data.as_slice()),
+                Vec::new(),
+                options,
+            )
+        };
+
+        assert!(encode_with(&EncodeOptions::default()).is_err_and(|e| matches!(e,
+            EncodeError::Vorbis(e) if e.kind() == VorbisErrorKind::MissingCrc32)));
+
+        let mut options = EncodeOptions::new();
+        let _ = options.vorbis_crc32(Some(0));
+        assert!(encode_with(&options).is_err_and(|e| matches!(e,
+            EncodeError::Vorbis(e) if e.kind() == VorbisErrorKind::Crc32Lookup { crc32: 0 })));
+    }
+
+    #[test]
+    fn add_vorbis_setup_header() {
+        // audio packets of zeros, which decode to silence
+        let data = [[8, 0, 0, 0, 0, 0, 0, 0, 0, 0]; 4].concat();
+        let mut info = StreamInfo::new_test(44100, 2, 16, 40);
+        info.vorbis_crc32 = Some(0x1234_ABCD);
+
+        let mut options = EncodeOptions::new();
+        let _ = options.vorbis_output(VorbisOutput::Pcm);
+        let mut cache = SetupHeaderCache::default();
+        let mut encode_with = |options: &EncodeOptions| {
+            encode_cached(
+                AudioFormat::Vorbis,
+                0,
+                &info,
+                &mut Reader::new(data.as_slice()),
+                Vec::new(),
+                options,
+                &mut cache,
+            )
+        };
+
+        assert!(encode_with(&options).is_err_and(|e| matches!(e,
+            EncodeError::Vorbis(e) if e.kind() == VorbisErrorKind::Crc32Lookup { crc32: 0x1234_ABCD })));
+
+        let mut with_header = options.clone();
+        let _ =
+            with_header.vorbis_setup_header(0x1234_ABCD, vorbis_setup_header(0xA722_97FF).unwrap());
+        assert!(encode_with(&with_header).is_ok_and(|(output, _)| &output[..4] == b"RIFF"));
+
+        // setup headers from options aren't added to the shared cache
+        assert!(encode_with(&options).is_err_and(|e| matches!(e,
+            EncodeError::Vorbis(e) if e.kind() == VorbisErrorKind::Crc32Lookup { crc32: 0x1234_ABCD })));
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn measure_vorbis_peaks_with_setup_header_from_options() {
+        // audio packets of zeros, which decode to silence
+        let data = [[8, 0, 0, 0, 0, 0, 0, 0, 0, 0]; 4].concat();
+        let mut info = StreamInfo::new_test(44100, 2, 16, 40);
+        info.vorbis_crc32 = Some(0x1234_ABCD);
+        let measure_with = |options: &EncodeOptions| {
+            measure_peaks(
+                AudioFormat::Vorbis,
+                0,
+                &info,
+                &mut Reader::new(data.as_slice()),
+                options,
+            )
+        };
+
+        assert!(measure_with(&EncodeOptions::default()).is_err_and(|e| matches!(e,
+            EncodeError::Vorbis(e) if e.kind() == VorbisErrorKind::Crc32Lookup { crc32: 0x1234_ABCD })));
+
+        let mut options = EncodeOptions::new();
+        let _ = options.vorbis_setup_header(0x1234_ABCD, vorbis_setup_header(0xA722_97FF).unwrap());
+        assert_eq!(measure_with(&options).unwrap(), [0.0, 0.0]);
+    }
+
+    #[cfg(feature = "resample")]
+    #[test]
+    fn resample_vorbis_with_setup_header_from_options() {
+        // audio packets of zeros, which decode to silence
+        let data = [[8, 0, 0, 0, 0, 0, 0, 0, 0, 0]; 4].concat();
+        let mut info = StreamInfo::new_test(44100, 2, 16, 40);
+        info.vorbis_crc32 = Some(0x1234_ABCD);
+
+        let mut options = EncodeOptions::new();
+        let _ = options
+            .resample_to(NonZeroU32::new(22050))
+            .vorbis_setup_header(0x1234_ABCD, vorbis_setup_header(0xA722_97FF).unwrap());
+
+        let file = encode(
+            AudioFormat::Vorbis,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(&file[..4], b"RIFF");
+        assert_eq!(&file[24..28], &22050u32.to_le_bytes());
+    }
+
+    #[test]
+    fn write_vorbis_as_pcm_wav() {
+        // audio packets of zeros, which decode to silence
+        let data = [[8, 0, 0, 0, 0, 0, 0, 0, 0, 0]; 4].concat();
+        let mut info = StreamInfo::new_test(44100, 2, 16, 40);
+        info.vorbis_crc32 = Some(0xA722_97FF);
+
+        let mut options = EncodeOptions::new();
+        let _ = options.vorbis_output(VorbisOutput::Pcm);
+
+        let (output, frames) = encode_counted(
+            AudioFormat::Vorbis,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &options,
+        )
+        .unwrap();
+
+        // 16-bit integer samples
+        assert_eq!(output[20..22], [1, 0]);
+        assert_eq!(output[34..36], [16, 0]);
+        assert_eq!(output.len() as u64, 44 + frames * 4);
+        assert!(frames > 0);
+        assert!(output[44..].iter().all(|&byte| byte == 0));
+
+        // dithered silence stays within 1 step of 0
+        let _ = options.dither(true);
+        let (output, _) = encode_counted(
+            AudioFormat::Vorbis,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &options,
+        )
+        .unwrap();
+
+        let samples: Vec<_> = output[44..]
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect();
+        assert!(samples.iter().all(|sample| (-1..=1).contains(sample)));
+        assert!(samples.iter().any(|&sample| sample != 0));
+    }
+
+    #[cfg(feature = "resample")]
+    #[test]
+    fn skip_resampling_undecodable_streams() {
+        let info = StreamInfo::new_test(44100, 2, 1152, 4);
+        let data = [0xFF, 0xFB, 0x90, 0x64];
+        let mut options = EncodeOptions::default();
+        let _ = options.resample_to(NonZeroU32::new(22050));
+
+        // MPEG streams are copied as-is instead of failing to decode
+        let file = encode(
+            AudioFormat::Mpeg,
+            0,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(file, data);
+    }
+
+    #[test]
+    fn decode_in_fixed_size_chunks() {
+        let info = StreamInfo::new_test(44100, 2, 5, 10);
+        let data = [128, 192, 64, 128, 128, 128, 0, 128, 128, 128];
+
+        let chunks: Vec<_> = decode_chunked(
+            AudioFormat::Pcm8,
+            0,
+            &info,
+            NonZeroUsize::new(2).unwrap(),
+            &mut Reader::new(data.as_slice()),
+            &EncodeOptions::default(),
+        )
+        .map(Result::unwrap)
+        .collect();
+
+        assert_eq!(
+            chunks,
+            [
+                vec![0.0, 0.5, -0.5, 0.0],
+                vec![0.0, 0.0, -1.0, 0.0],
+                vec![0.0, 0.0]
+            ]
+        );
+    }
+}
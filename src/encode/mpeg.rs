@@ -0,0 +1,92 @@
+use crate::header::StreamInfo;
+use crate::read::Reader;
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{copy, Error as IoError, Read, Write},
+};
+
+// MPEG streams are stored as concatenated MP3 frames, which already form a valid MP3 file.
+// The stream data is copied as-is, so no decoding takes place.
+pub(super) fn encode<R: Read, W: Write>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    mut sink: W,
+) -> Result<W, MpegError> {
+    // There could be more data after the stream, so a limit is placed on the number of bytes read.
+    let _ = copy(&mut source.limit(info.size.get() as usize), &mut sink)
+        .map_err(MpegError::from_io(MpegErrorKind::EncodeStream))?;
+
+    sink.flush()
+        .map(|()| sink)
+        .map_err(MpegError::from_io(MpegErrorKind::FinishStream))
+}
+
+/// Represents an error that can occur when encoding an MPEG stream.
+///
+/// See [`MpegErrorKind`] for the different kinds of errors that can occur.
+#[derive(Debug)]
+pub struct MpegError {
+    kind: MpegErrorKind,
+    source: IoError,
+}
+
+/// A variant of an [`MpegError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MpegErrorKind {
+    /// Failed to copy the stream data from reader to writer.
+    EncodeStream,
+    /// Failed to flush the writer after encoding the entire stream.
+    FinishStream,
+}
+
+impl MpegError {
+    fn from_io(kind: MpegErrorKind) -> impl FnOnce(IoError) -> Self {
+        move |source| Self { kind, source }
+    }
+
+    /// Returns the [`MpegErrorKind`] associated with this error.
+    #[must_use]
+    pub fn kind(&self) -> MpegErrorKind {
+        self.kind
+    }
+}
+
+impl Display for MpegError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.kind.fmt(f)
+    }
+}
+
+impl Error for MpegError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl Display for MpegErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(match self {
+            Self::EncodeStream => "failed to copy MPEG stream data",
+            Self::FinishStream => "failed to finalize writing MPEG stream data",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::encode;
+    use crate::header::StreamInfo;
+    use crate::read::Reader;
+
+    #[test]
+    fn copy_stream_data() {
+        let info = StreamInfo::new_test(44100, 2, 1152, 4);
+        // the trailing bytes belong to the next stream
+        let data = [0xFF, 0xFB, 0x90, 0x64, 0xFF, 0xFB];
+
+        let file = encode(&info, &mut Reader::new(data.as_slice()), Vec::new()).unwrap();
+        assert_eq!(file, [0xFF, 0xFB, 0x90, 0x64]);
+    }
+}
@@ -0,0 +1,196 @@
+use super::bext::BroadcastMetadata;
+use std::collections::BTreeMap;
+#[cfg(feature = "resample")]
+use std::num::NonZeroU32;
+
+/// Options that change how stream data is encoded.
+///
+/// All options default to the same behavior as `write()` on [`Stream`] and [`LazyStream`].
+///
+/// # Examples
+///
+/// ```
+/// use fsbex::{encode::{EncodeOptions, VorbisLayerMode}, Stream};
+/// use std::error::Error;
+///
+/// fn write_chained(stream: Stream) -> Result<Vec<u8>, Box<dyn Error>> {
+///     let mut options = EncodeOptions::new();
+///     options.vorbis_layers(VorbisLayerMode::Chained);
+///     Ok(stream.write_with(Vec::new(), &options)?)
+/// }
+/// ```
+///
+/// [`Stream`]: crate::Stream
+/// [`LazyStream`]: crate::LazyStream
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct EncodeOptions {
+    pub(crate) vorbis_layers: VorbisLayerMode,
+    pub(crate) vorbis_output: VorbisOutput,
+    pub(crate) dither: bool,
+    pub(crate) pcm_as_float: bool,
+    pub(crate) swap_stereo_channels: bool,
+    pub(crate) downmix_dual_mono: bool,
+    pub(crate) broadcast_metadata: Option<BroadcastMetadata>,
+    pub(crate) vorbis_crc32: Option<u32>,
+    pub(crate) vorbis_setup_headers: BTreeMap<u32, Box<[u8]>>,
+    #[cfg(feature = "resample")]
+    pub(crate) resample_to: Option<NonZeroU32>,
+}
+
+impl EncodeOptions {
+    /// Creates a new [`EncodeOptions`] with default options.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how Vorbis streams with multiple layers per channel are encoded.
+    /// See [`VorbisLayerMode`] for more information.
+    pub fn vorbis_layers(&mut self, mode: VorbisLayerMode) -> &mut Self {
+        self.vorbis_layers = mode;
+        self
+    }
+
+    /// Sets the kind of file that Vorbis streams are written as.
+    /// See [`VorbisOutput`] for more information.
+    pub fn vorbis_output(&mut self, output: VorbisOutput) -> &mut Self {
+        self.vorbis_output = output;
+        self
+    }
+
+    /// Sets whether triangular dither is added when decoded float samples are converted to 16-bit integer samples,
+    /// as with [`VorbisOutput::Pcm`].
+    ///
+    /// Dither adds a small amount of noise (at most 1 LSB) that removes quantization distortion in quiet passages.
+    /// The noise is generated deterministically, so encoding the same stream twice gives the same output.
+    /// This is disabled by default.
+    pub fn dither(&mut self, value: bool) -> &mut Self {
+        self.dither = value;
+        self
+    }
+
+    /// Sets whether PCM streams with integer samples are encoded with 32-bit float samples.
+    ///
+    /// Samples are scaled by the full-scale value of their bit depth, so they lie in the range [-1.0, 1.0).
+    /// PCM streams that already have float samples are not affected.
+    /// This is disabled by default.
+    pub fn pcm_as_float(&mut self, value: bool) -> &mut Self {
+        self.pcm_as_float = value;
+        self
+    }
+
+    /// Sets whether the left and right channels of stereo PCM streams are swapped.
+    ///
+    /// This is useful for sound banks where stereo data was stored with its channels reversed.
+    /// Streams with other channel counts and streams in other formats are not affected.
+    /// See [`Stream::stereo_correlation`] for diagnosing other problems with stereo data.
+    /// This is disabled by default.
+    ///
+    /// [`Stream::stereo_correlation`]: crate::Stream::stereo_correlation
+    pub fn swap_stereo_channels(&mut self, value: bool) -> &mut Self {
+        self.swap_stereo_channels = value;
+        self
+    }
+
+    /// Sets whether PCM streams with identical samples in every channel ("dual mono") are written with a single channel.
+    ///
+    /// Streams with any difference between channels are written unchanged, so true stereo content is never downmixed.
+    /// Streams in other formats are not affected; see [`Stream::is_dual_mono`] for detecting dual mono in any decodable stream.
+    /// This is disabled by default.
+    ///
+    /// [`Stream::is_dual_mono`]: crate::Stream::is_dual_mono
+    pub fn downmix_dual_mono(&mut self, value: bool) -> &mut Self {
+        self.downmix_dual_mono = value;
+        self
+    }
+
+    /// Sets the Broadcast Wave Format (BWF) metadata that is written to PCM streams, if any.
+    ///
+    /// When set, WAVE files written from PCM streams contain a `bext` chunk with the given metadata,
+    /// and the stream name (if it exists) as the description.
+    /// This is needed by some archival and broadcast systems that only accept Broadcast Wave files.
+    /// Streams in other formats are not affected.
+    /// This is unset by default.
+    pub fn broadcast_metadata(&mut self, metadata: Option<BroadcastMetadata>) -> &mut Self {
+        self.broadcast_metadata = metadata;
+        self
+    }
+
+    /// Sets the CRC32 of the Vorbis setup header used to decode Vorbis streams, if any.
+    ///
+    /// FMOD identifies the setup header of a Vorbis stream by its CRC32, which is usually stored in a stream header chunk.
+    /// Some sound banks store it elsewhere, so it can't be found when parsing and encoding fails.
+    /// When set, the given CRC32 is used instead of the parsed value.
+    /// Streams in other formats are not affected.
+    /// This is unset by default.
+    pub fn vorbis_crc32(&mut self, crc32: Option<u32>) -> &mut Self {
+        self.vorbis_crc32 = crc32;
+        self
+    }
+
+    /// Adds a Vorbis setup header to use for streams with the given setup header CRC32 checksum.
+    ///
+    /// `data` must be a complete setup header packet, like the ones returned by [`vorbis_setup_header`].
+    /// Added setup headers take precedence over the built-in table,
+    /// so this can be used for games with setup headers that aren't in the table.
+    /// Invalid setup headers are only detected when a stream using them is encoded.
+    /// Streams in other formats are not affected.
+    ///
+    /// [`vorbis_setup_header`]: crate::encode::vorbis_setup_header
+    pub fn vorbis_setup_header(&mut self, crc32: u32, data: &[u8]) -> &mut Self {
+        drop(self.vorbis_setup_headers.insert(crc32, data.into()));
+        self
+    }
+
+    /// Sets the sample rate (Hz) that streams are resampled to, if any.
+    ///
+    /// When set, streams with a different sample rate are decoded, resampled, and written as WAVE files with 32-bit float samples
+    /// (including streams that would otherwise be written in another container, such as Vorbis streams).
+    /// Streams that already have the target sample rate are not affected.
+    /// Streams in formats that are not decoded (e.g. ATRAC9, MPEG, xWMA, and Opus) are encoded as usual, without resampling.
+    ///
+    /// Resampling uses linear interpolation, which is fast but doesn't filter out high frequencies,
+    /// so downsampling can introduce audible aliasing. It is suitable for previews and analysis rather than mastering.
+    /// This is unset by default.
+    #[cfg(feature = "resample")]
+    pub fn resample_to(&mut self, sample_rate: Option<NonZeroU32>) -> &mut Self {
+        self.resample_to = sample_rate;
+        self
+    }
+}
+
+/// Represents how Vorbis streams with multiple layers per channel are encoded.
+///
+/// Some Vorbis streams store multiple "layers" of audio, each with the same number of channels.
+/// FMOD plays back all layers of a stream at the same time,
+/// so [`VorbisLayerMode::Channels`] is the representation that matches the original intent.
+/// Streams without layers are encoded the same way in every mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum VorbisLayerMode {
+    /// Layers are encoded as extra channels within a single logical Ogg stream.
+    #[default]
+    Channels,
+    /// Each layer is encoded as its own logical Ogg stream (with its own serial number and headers),
+    /// and the logical streams are chained one after another.
+    Chained,
+}
+
+/// Represents the kind of file that Vorbis streams are written as.
+///
+/// Vorbis streams are decoded to float samples, which are normally encoded again as Vorbis.
+/// Writing the decoded samples to a WAVE file instead avoids the quality loss of re-encoding and is much faster,
+/// at the cost of much larger files. The entire stream is decoded into memory before a WAVE file is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum VorbisOutput {
+    /// Decoded samples are encoded again as an Ogg Vorbis file.
+    /// If the stream has loop information, it is written as `LOOPSTART` and `LOOPLENGTH` comments.
+    #[default]
+    Ogg,
+    /// Decoded samples are written to a WAVE file with 16-bit integer samples.
+    Pcm,
+    /// Decoded samples are written to a WAVE file with 32-bit float samples.
+    Float,
+}
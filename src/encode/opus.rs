@@ -0,0 +1,473 @@
+use crate::header::StreamInfo;
+use crate::read::{ReadError, Reader};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{Error as IoError, Read, Write},
+};
+
+// Ogg Opus granule positions are always counted at 48 kHz, regardless of the input sample rate
+const GRANULE_RATE: u64 = 48000;
+
+// arbitrary serial number for the only logical stream in the Ogg file
+const STREAM_SERIAL: u32 = 0x6673_6278;
+
+// Pages are flushed once they hold this many bytes of packet data, so that players can seek reasonably well.
+const PAGE_TARGET_SIZE: usize = 4096;
+
+const VENDOR: &str = concat!("fsbex ", env!("CARGO_PKG_VERSION"));
+
+// Every Opus packet in the stream data is preceded by a frame header:
+// a 2-byte sync marker, followed by the size of the packet as a big-endian u16.
+const FRAME_HEADER_SIZE: usize = 4;
+
+// Wraps the stream data in an Ogg Opus file.
+// The Opus packets are copied as-is, so no decoding takes place.
+pub(super) fn encode<R: Read, W: Write>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    sink: W,
+) -> Result<W, OpusError> {
+    // Only channel mapping family 0 (mono and stereo) can be described without extra stream information.
+    let channels = info.channels.get();
+    if channels > 2 {
+        return Err(OpusError::new(OpusErrorKind::UnsupportedChannels { channels }));
+    }
+
+    let mut remaining = info.size.get() as usize;
+    let mut next_packet = read_packet(source, &mut remaining)?;
+    if next_packet.is_none() {
+        return Err(OpusError::new(OpusErrorKind::NoPackets));
+    }
+
+    // FMOD sample counts are measured at the stream's sample rate,
+    // so they are converted to 48 kHz to trim padding from the end of the last packet.
+    let end_granule = info
+        .num_samples
+        .map(|samples| u64::from(samples.get()) * GRANULE_RATE / u64::from(info.sample_rate.get()));
+
+    let mut writer = PageWriter::new(sink);
+
+    writer
+        .write_page(&[opus_head(info)], 0, PageKind::First)
+        .map_err(OpusError::from_io(OpusErrorKind::CreateHeader))?;
+    writer
+        .write_page(&[opus_tags()], 0, PageKind::Middle)
+        .map_err(OpusError::from_io(OpusErrorKind::CreateHeader))?;
+
+    let mut packets = Vec::new();
+    let mut page_size = 0;
+    let mut page_segments = 0;
+    let mut granule = 0;
+
+    while let Some(packet) = next_packet {
+        next_packet = read_packet(source, &mut remaining)?;
+
+        let samples =
+            packet_samples(&packet).ok_or_else(|| OpusError::new(OpusErrorKind::InvalidPacket))?;
+
+        // each page can hold at most 255 lacing values
+        let segments = packet.len() / 255 + 1;
+        if segments > 255 {
+            return Err(OpusError::new(OpusErrorKind::InvalidPacket));
+        }
+        if page_segments + segments > 255 {
+            writer
+                .write_page(&packets, granule, PageKind::Middle)
+                .map_err(OpusError::from_io(OpusErrorKind::EncodeStream))?;
+            packets.clear();
+            page_size = 0;
+            page_segments = 0;
+        }
+
+        granule += u64::from(samples);
+        page_size += packet.len();
+        page_segments += segments;
+        packets.push(packet);
+
+        if next_packet.is_none() {
+            // the last page marks where the stream ends, so padding in the last packet is trimmed
+            let final_granule = end_granule.map_or(granule, |end| end.min(granule));
+            writer
+                .write_page(&packets, final_granule, PageKind::Last)
+                .map_err(OpusError::from_io(OpusErrorKind::EncodeStream))?;
+        } else if page_size >= PAGE_TARGET_SIZE {
+            writer
+                .write_page(&packets, granule, PageKind::Middle)
+                .map_err(OpusError::from_io(OpusErrorKind::EncodeStream))?;
+            packets.clear();
+            page_size = 0;
+            page_segments = 0;
+        }
+    }
+
+    let mut sink = writer.into_inner();
+    sink.flush()
+        .map(|()| sink)
+        .map_err(OpusError::from_io(OpusErrorKind::FinishStream))
+}
+
+// Reads the next packet from the stream data, or returns `None` if no packets are left.
+// This follows vgmstream, which reads FSB5 Opus streams as a sequence of framed packets.
+// The sync marker isn't checked, and a frame header with a packet size of 0 is treated as padding at the end of the stream.
+fn read_packet<R: Read>(
+    source: &mut Reader<R>,
+    remaining: &mut usize,
+) -> Result<Option<Vec<u8>>, OpusError> {
+    if *remaining < FRAME_HEADER_SIZE {
+        return Ok(None);
+    }
+
+    let header = source
+        .take_const::<FRAME_HEADER_SIZE>()
+        .map_err(OpusError::from_read(OpusErrorKind::ReadPacket))?;
+    *remaining -= FRAME_HEADER_SIZE;
+
+    let size = usize::from(u16::from_be_bytes([header[2], header[3]]));
+    if size == 0 {
+        return Ok(None);
+    }
+    if size > *remaining {
+        return Err(OpusError::new(OpusErrorKind::InvalidFrameHeader));
+    }
+
+    let packet = source
+        .take(size)
+        .map_err(OpusError::from_read(OpusErrorKind::ReadPacket))?;
+    *remaining -= size;
+
+    Ok(Some(packet))
+}
+
+fn opus_head(info: &StreamInfo) -> Vec<u8> {
+    // Ogg Opus header information taken from:
+    // [1]: https://www.rfc-editor.org/rfc/rfc7845#section-5.1
+
+    let mut head = Vec::with_capacity(19);
+    head.extend_from_slice(b"OpusHead");
+    // version
+    head.push(1);
+    head.push(info.channels.get());
+    // FMOD sample counts already account for the encoder delay, so no samples are skipped
+    head.extend_from_slice(&0u16.to_le_bytes());
+    head.extend_from_slice(&info.sample_rate.get().to_le_bytes());
+    // output gain
+    head.extend_from_slice(&0u16.to_le_bytes());
+    // channel mapping family 0 (mono or stereo)
+    head.push(0);
+    head
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn opus_tags() -> Vec<u8> {
+    let mut tags = Vec::with_capacity(16 + VENDOR.len());
+    tags.extend_from_slice(b"OpusTags");
+    tags.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+    tags.extend_from_slice(VENDOR.as_bytes());
+    // no user comments
+    tags.extend_from_slice(&0u32.to_le_bytes());
+    tags
+}
+
+// Returns the number of samples (at 48 kHz) in an Opus packet, based on its table of contents byte.
+// See https://www.rfc-editor.org/rfc/rfc6716#section-3.1 for more information.
+fn packet_samples(packet: &[u8]) -> Option<u32> {
+    let toc = *packet.first()?;
+    let config = toc >> 3;
+
+    let frame_samples = match config {
+        // SILK: 10, 20, 40, or 60 ms
+        0..=11 => [480, 960, 1920, 2880][usize::from(config % 4)],
+        // hybrid: 10 or 20 ms
+        12..=15 => [480, 960][usize::from(config % 2)],
+        // CELT: 2.5, 5, 10, or 20 ms
+        _ => [120, 240, 480, 960][usize::from(config % 4)],
+    };
+
+    let frames = match toc & 0b11 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => u32::from(*packet.get(1)? & 0b0011_1111),
+    };
+
+    Some(frame_samples * frames)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PageKind {
+    First,
+    Middle,
+    Last,
+}
+
+struct PageWriter<W: Write> {
+    sink: W,
+    sequence: u32,
+}
+
+impl<W: Write> PageWriter<W> {
+    fn new(sink: W) -> Self {
+        Self { sink, sequence: 0 }
+    }
+
+    // Writes a page containing every packet in `packets`. The caller ensures that there are at most 255 segments.
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_page<P: AsRef<[u8]>>(
+        &mut self,
+        packets: &[P],
+        granule: u64,
+        kind: PageKind,
+    ) -> Result<(), IoError> {
+        // Ogg page information taken from:
+        // [1]: https://www.rfc-editor.org/rfc/rfc3533#section-6
+
+        let mut lacing = Vec::new();
+        let mut data = Vec::new();
+        for packet in packets {
+            let packet = packet.as_ref();
+            lacing.resize(lacing.len() + packet.len() / 255, 255);
+            lacing.push((packet.len() % 255) as u8);
+            data.extend_from_slice(packet);
+        }
+
+        let mut page = Vec::with_capacity(27 + lacing.len() + data.len());
+        page.extend_from_slice(b"OggS");
+        // version
+        page.push(0);
+        page.push(match kind {
+            PageKind::First => 0x02,
+            PageKind::Middle => 0x00,
+            PageKind::Last => 0x04,
+        });
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&STREAM_SERIAL.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        // the checksum is calculated with this field set to 0
+        page.extend_from_slice(&0u32.to_le_bytes());
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(&lacing);
+        page.extend_from_slice(&data);
+
+        let checksum = crc32(&page);
+        page[22..26].copy_from_slice(&checksum.to_le_bytes());
+
+        self.sequence += 1;
+        self.sink.write_all(&page)
+    }
+
+    fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+// CRC32 lookup table for the checksum used by Ogg pages
+// (polynomial 0x04C11DB7, no reflection, initial value 0, no final XOR)
+#[allow(clippy::cast_possible_truncation)]
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x04C1_1DB7
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, byte| {
+        (crc << 8) ^ CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
+
+/// Represents an error that can occur when encoding an Opus stream.
+///
+/// See [`OpusErrorKind`] for the different kinds of errors that can occur.
+#[derive(Debug)]
+pub struct OpusError {
+    kind: OpusErrorKind,
+    source: Option<OpusErrorSource>,
+}
+
+#[derive(Debug)]
+enum OpusErrorSource {
+    Io(IoError),
+    Read(ReadError),
+}
+
+/// A variant of an [`OpusError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum OpusErrorKind {
+    /// The stream data did not contain any Opus packets.
+    NoPackets,
+    /// A frame header in the stream data declared a packet that extends past the end of the stream.
+    InvalidFrameHeader,
+    /// The stream has more than 2 channels, which needs channel mapping information that isn't stored in the sound bank.
+    UnsupportedChannels {
+        /// The number of channels in the stream.
+        channels: u8,
+    },
+    /// An Opus packet was empty or malformed.
+    InvalidPacket,
+    /// Failed to read an Opus packet from the stream data.
+    ReadPacket,
+    /// Failed to write the Ogg Opus headers due to an underlying I/O error.
+    CreateHeader,
+    /// Failed to write Ogg pages containing the stream data.
+    EncodeStream,
+    /// Failed to flush the writer after encoding the entire stream.
+    FinishStream,
+}
+
+impl OpusError {
+    fn new(kind: OpusErrorKind) -> Self {
+        Self { kind, source: None }
+    }
+
+    fn from_io(kind: OpusErrorKind) -> impl FnOnce(IoError) -> Self {
+        move |source| Self {
+            kind,
+            source: Some(OpusErrorSource::Io(source)),
+        }
+    }
+
+    fn from_read(kind: OpusErrorKind) -> impl FnOnce(ReadError) -> Self {
+        move |source| Self {
+            kind,
+            source: Some(OpusErrorSource::Read(source)),
+        }
+    }
+
+    /// Returns the [`OpusErrorKind`] associated with this error.
+    #[must_use]
+    pub fn kind(&self) -> OpusErrorKind {
+        self.kind
+    }
+}
+
+impl Display for OpusError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.kind.fmt(f)
+    }
+}
+
+impl Error for OpusError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.source {
+            Some(OpusErrorSource::Io(e)) => Some(e),
+            Some(OpusErrorSource::Read(e)) => Some(e),
+            None => None,
+        }
+    }
+}
+
+impl Display for OpusErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::NoPackets => f.write_str("Opus stream data did not contain any packets"),
+            Self::InvalidFrameHeader => {
+                f.write_str("Opus packet size in frame header exceeded the size of the stream")
+            }
+            Self::UnsupportedChannels { channels } => f.write_fmt(format_args!(
+                "Opus streams with more than 2 channels are not supported ({channels} channels)"
+            )),
+            Self::InvalidPacket => f.write_str("Opus packet was invalid"),
+            Self::ReadPacket => f.write_str("failed to read Opus packet"),
+            Self::CreateHeader => f.write_str("failed to encode Ogg Opus headers"),
+            Self::EncodeStream => f.write_str("failed to write Ogg pages"),
+            Self::FinishStream => f.write_str("failed to finalize writing Opus stream data"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{crc32, encode, packet_samples, OpusErrorKind};
+    use crate::header::StreamInfo;
+    use crate::read::Reader;
+
+    #[test]
+    fn calculate_checksum() {
+        assert_eq!(crc32(b""), 0);
+        // CRC-32/CKSUM check value without the final XOR
+        assert_eq!(crc32(b"123456789"), 0x89A1_897F);
+    }
+
+    #[test]
+    fn count_packet_samples() {
+        // CELT 20 ms, 1 frame
+        assert_eq!(packet_samples(&[0xFC]), Some(960));
+        // SILK 60 ms, 2 frames
+        assert_eq!(packet_samples(&[0x19]), Some(5760));
+        // CELT 2.5 ms, 3 frames
+        assert_eq!(packet_samples(&[0x83, 0x03]), Some(360));
+        assert_eq!(packet_samples(&[]), None);
+    }
+
+    // Prepends a frame header to an Opus packet. The sync marker isn't checked, so any value works.
+    fn frame(packet: &[u8]) -> Vec<u8> {
+        let size = u16::try_from(packet.len()).unwrap();
+        let mut frame = vec![0xAB, 0xCD];
+        frame.extend_from_slice(&size.to_be_bytes());
+        frame.extend_from_slice(packet);
+        frame
+    }
+
+    #[test]
+    fn wrap_packets() {
+        let packets: [&[u8]; 2] = [&[0xFC, 1, 2], &[0xFC, 3, 4, 5]];
+        // the packets are followed by padding
+        let data = [frame(packets[0]), frame(packets[1]), vec![0; 5]].concat();
+        let info = StreamInfo::new_test(48000, 2, 1500, data.len().try_into().unwrap());
+
+        let file = encode(&info, &mut Reader::new(data.as_slice()), Vec::new()).unwrap();
+
+        // OpusHead page
+        assert_eq!(&file[..4], b"OggS");
+        assert_eq!(file[5], 0x02);
+        assert_eq!(&file[28..36], b"OpusHead");
+        assert_eq!(file[37], 2);
+
+        // the last page holds both packets without their frame headers,
+        // and its granule position is trimmed to the sample count
+        let last = file.windows(4).rposition(|w| w == b"OggS").unwrap();
+        assert_eq!(file[last + 5], 0x04);
+        assert_eq!(&file[last + 6..last + 14], 1500u64.to_le_bytes());
+        assert_eq!(&file[last + 26..last + 29], [2, 3, 4]);
+        assert_eq!(&file[last + 29..], packets.concat());
+    }
+
+    #[test]
+    fn reject_invalid_streams() {
+        let source = [0; 8];
+        let info = StreamInfo::new_test(48000, 2, 960, 8);
+        let result = encode(&info, &mut Reader::new(source.as_slice()), Vec::new());
+        assert!(result.is_err_and(|e| e.kind() == OpusErrorKind::NoPackets));
+
+        // the frame header declares a packet of 5 bytes, but only 4 bytes are left
+        let source = [0xAB, 0xCD, 0, 5, 0xFC, 0, 0, 0];
+        let result = encode(&info, &mut Reader::new(source.as_slice()), Vec::new());
+        assert!(result.is_err_and(|e| e.kind() == OpusErrorKind::InvalidFrameHeader));
+
+        let source = frame(&[0xFC; 4]);
+        let info = StreamInfo::new_test(48000, 6, 960, 8);
+        let result = encode(&info, &mut Reader::new(source.as_slice()), Vec::new());
+        assert!(
+            result.is_err_and(|e| e.kind() == OpusErrorKind::UnsupportedChannels { channels: 6 })
+        );
+    }
+}
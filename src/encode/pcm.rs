@@ -1,17 +1,21 @@
+use super::bext::{self, BroadcastMetadata};
 use crate::{
-    header::StreamInfo,
+    header::{Loop, StreamInfo},
     read::{ReadError, Reader},
 };
 use std::{
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
-    io::{copy, Error as IoError, Read, Write},
+    io::{copy, Error as IoError, ErrorKind, Read, Seek, SeekFrom, Write},
+    num::{NonZeroU32, NonZeroU8},
 };
+use tap::Pipe;
 
 pub(super) fn encode<R: Read, W: Write, const BYTE_DEPTH: usize>(
     format: Format,
     order: Endianness,
     info: &StreamInfo,
+    bext: Option<&BroadcastMetadata>,
     source: &mut Reader<R>,
     mut sink: W,
 ) -> Result<W, PcmError> {
@@ -22,21 +26,122 @@ pub(super) fn encode<R: Read, W: Write, const BYTE_DEPTH: usize>(
         info.sample_rate.get(),
         format,
         BYTE_DEPTH.try_into().expect("byte depth is less than u16::MAX"),
+        &metadata_chunks(info, bext)?,
         &mut sink,
-    )
-    .map_err(PcmError::from_io(PcmErrorKind::CreateHeader))?;
+    )?;
+
+    write_samples::<_, _, BYTE_DEPTH>(order, info, source, &mut sink)?;
+
+    sink.flush()
+        .map(|()| sink)
+        .map_err(PcmError::from_io(PcmErrorKind::FinishStream))
+}
+
+// Encodes integer samples as 32-bit float samples, scaled to the range [-1.0, 1.0).
+pub(super) fn encode_float<R: Read, W: Write, const BYTE_DEPTH: usize>(
+    order: Endianness,
+    info: &StreamInfo,
+    bext: Option<&BroadcastMetadata>,
+    source: &mut Reader<R>,
+    mut sink: W,
+) -> Result<W, PcmError> {
+    let float_size = (info.size.get() as usize / BYTE_DEPTH)
+        .checked_mul(4)
+        .and_then(|size| u32::try_from(size).ok())
+        .ok_or_else(|| PcmError::new(PcmErrorKind::FileSize))?;
+
+    write_header(
+        float_size,
+        info.channels.get().into(),
+        info.sample_rate.get(),
+        Format::Float,
+        4,
+        &metadata_chunks(info, bext)?,
+        &mut sink,
+    )?;
 
     let start_pos = source.position();
     let stream_size = info.size.get() as usize;
 
+    while source.position() - start_pos < stream_size {
+        let mut sample = source
+            .take_const::<BYTE_DEPTH>()
+            .map_err(PcmError::from_read(PcmErrorKind::DecodeSample))?;
+
+        if order == Endianness::Big {
+            sample.reverse();
+        }
+
+        sink.write_all(&int_to_float(&sample).to_le_bytes())
+            .map_err(PcmError::from_io(PcmErrorKind::EncodeSample))?;
+    }
+
+    sink.flush()
+        .map(|()| sink)
+        .map_err(PcmError::from_io(PcmErrorKind::FinishStream))
+}
+
+// Decodes the entire stream into interleaved 32-bit float samples.
+pub(super) fn decode_float<R: Read, const BYTE_DEPTH: usize>(
+    format: Format,
+    order: Endianness,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+) -> Result<Vec<f32>, PcmError> {
+    let start_pos = source.position();
+    let stream_size = info.size.get() as usize;
+    let mut samples = Vec::with_capacity(stream_size / BYTE_DEPTH);
+
+    while source.position() - start_pos < stream_size {
+        let mut sample = source
+            .take_const::<BYTE_DEPTH>()
+            .map_err(PcmError::from_read(PcmErrorKind::DecodeSample))?;
+
+        if order == Endianness::Big {
+            sample.reverse();
+        }
+
+        if let (Format::Float, Ok(bytes)) = (format, sample.as_slice().try_into()) {
+            samples.push(f32::from_le_bytes(bytes));
+        } else {
+            samples.push(int_to_float(&sample));
+        }
+    }
+
+    Ok(samples)
+}
+
+// Converts a little-endian integer sample to a float sample by dividing by the full-scale value.
+// 8-bit samples are unsigned, while samples of other widths are signed.
+#[allow(clippy::cast_precision_loss)]
+fn int_to_float(sample: &[u8]) -> f32 {
+    match *sample {
+        [b0] => (f32::from(b0) - 128.0) / 128.0,
+        [b0, b1] => f32::from(i16::from_le_bytes([b0, b1])) / 32_768.0,
+        // shift into the upper 3 bytes so the sign bit is preserved, then shift back
+        [b0, b1, b2] => (i32::from_le_bytes([0, b0, b1, b2]) >> 8) as f32 / 8_388_608.0,
+        [b0, b1, b2, b3] => i32::from_le_bytes([b0, b1, b2, b3]) as f32 / 2_147_483_648.0,
+        _ => unreachable!("integer samples are 1 to 4 bytes wide"),
+    }
+}
+
+fn write_samples<R: Read, W: Write, const BYTE_DEPTH: usize>(
+    order: Endianness,
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    sink: &mut W,
+) -> Result<(), PcmError> {
+    let start_pos = source.position();
+    let stream_size = info.size.get() as usize;
+
     // Stream samples are encoded as little-endian.
     // However, samples can be stored as big-endian; when this happens, the samples have to be converted.
     // Otherwise, the stream data can be directly copied from reader to writer.
 
-    if format == Format::Float || order == Endianness::Little {
+    if order == Endianness::Little {
         // There could be more data after the stream, so a limit is placed on the number of bytes read.
-        return copy(&mut source.limit(stream_size), &mut sink)
-            .map(|_| sink)
+        return copy(&mut source.limit(stream_size), sink)
+            .map(|_| ())
             .map_err(PcmError::from_io(PcmErrorKind::EncodeStream));
     }
 
@@ -52,19 +157,455 @@ pub(super) fn encode<R: Read, W: Write, const BYTE_DEPTH: usize>(
             .map_err(PcmError::from_io(PcmErrorKind::EncodeSample))?;
     }
 
+    Ok(())
+}
+
+// Writes all streams into a single WAVE file, with a cue point at the start of every stream.
+// Cue points of named streams are labeled with the stream name.
+// All streams must have the same number of channels and sample rate.
+pub(super) fn encode_combined<R: Read, W: Write + Seek, const BYTE_DEPTH: usize>(
+    format: Format,
+    order: Endianness,
+    info: &[StreamInfo],
+    source: &mut Reader<R>,
+    mut sink: W,
+) -> Result<W, PcmError> {
+    let first = info
+        .first()
+        .ok_or_else(|| PcmError::new(PcmErrorKind::MismatchedStreams))?;
+    let channels = first.channels;
+    let sample_rate = first.sample_rate;
+
+    if info
+        .iter()
+        .any(|stream| stream.channels != channels || stream.sample_rate != sample_rate)
+    {
+        return Err(PcmError::new(PcmErrorKind::MismatchedStreams));
+    }
+
+    let byte_depth = BYTE_DEPTH.try_into().expect("byte depth is less than u16::MAX");
+    let frame_size = u64::from(channels.get()) * BYTE_DEPTH as u64;
+
+    let file_start = sink
+        .stream_position()
+        .map_err(PcmError::from_io(PcmErrorKind::CreateHeader))?;
+
+    // The sizes in the file header aren't known yet, so they are patched after all streams are written.
+    write_header(
+        0,
+        channels.get().into(),
+        sample_rate.get(),
+        format,
+        byte_depth,
+        &[],
+        &mut sink,
+    )?;
+
+    let mut data_size = 0u64;
+    let mut cue_positions = Vec::with_capacity(info.len());
+
+    for stream in info {
+        cue_positions.push(
+            u32::try_from(data_size / frame_size)
+                .map_err(|_| PcmError::new(PcmErrorKind::FileSize))?,
+        );
+
+        let start_pos = source.position();
+        write_samples::<_, _, BYTE_DEPTH>(order, stream, source, &mut sink)?;
+        data_size += (source.position() - start_pos) as u64;
+
+        source
+            .advance_to_offset(start_pos, stream.size.get() as usize)
+            .map_err(PcmError::from_read(PcmErrorKind::ReadStream))?;
+    }
+
+    // chunks must start at even offsets
+    if data_size % 2 == 1 {
+        sink.write_all(&[0])
+            .map_err(PcmError::from_io(PcmErrorKind::EncodeStream))?;
+    }
+
+    let chunks_size = write_cue_chunks(info, &cue_positions, &mut sink)
+        .map_err(PcmError::from_io(PcmErrorKind::CreateCues))?;
+
+    let header_size = 4 + 8 + u64::from(fmt_chunk_size(channels.get().into())) + 8;
+    let riff_size = (header_size + data_size + data_size % 2 + chunks_size)
+        .pipe(u32::try_from)
+        .map_err(|_| PcmError::new(PcmErrorKind::FileSize))?;
+    let data_size = u32::try_from(data_size).map_err(|_| PcmError::new(PcmErrorKind::FileSize))?;
+
+    patch_sizes(file_start, channels.get().into(), riff_size, data_size, &mut sink)
+        .map_err(PcmError::from_io(PcmErrorKind::PatchHeader))?;
+
+    sink.flush()
+        .map(|()| sink)
+        .map_err(PcmError::from_io(PcmErrorKind::FinishStream))
+}
+
+// Writes a `cue ` chunk with a cue point at every position,
+// followed by a `LIST` chunk of labels for named streams.
+// Returns the total number of bytes written.
+fn write_cue_chunks<W: Write>(
+    info: &[StreamInfo],
+    positions: &[u32],
+    sink: &mut W,
+) -> Result<u64, IoError> {
+    let cue_count =
+        u32::try_from(positions.len()).map_err(|_| IoError::from(ErrorKind::InvalidInput))?;
+    let cue_size = u32::try_from(4 + 24 * positions.len())
+        .map_err(|_| IoError::from(ErrorKind::InvalidInput))?;
+
+    sink.write_all(b"cue ")?;
+    sink.write_all(&cue_size.to_le_bytes())?;
+    sink.write_all(&cue_count.to_le_bytes())?;
+
+    for (position, id) in positions.iter().zip(1u32..) {
+        sink.write_all(&id.to_le_bytes())?;
+        sink.write_all(&position.to_le_bytes())?;
+        sink.write_all(b"data")?;
+        sink.write_all(&0u32.to_le_bytes())?;
+        sink.write_all(&0u32.to_le_bytes())?;
+        sink.write_all(&position.to_le_bytes())?;
+    }
+
+    let mut labels = Vec::new();
+
+    for (stream, id) in info.iter().zip(1u32..) {
+        if let Some(name) = &stream.name {
+            let label_size = u32::try_from(4 + name.len() + 1)
+                .map_err(|_| IoError::from(ErrorKind::InvalidInput))?;
+
+            labels.write_all(b"labl")?;
+            labels.write_all(&label_size.to_le_bytes())?;
+            labels.write_all(&id.to_le_bytes())?;
+            labels.write_all(name.as_bytes())?;
+            labels.write_all(&[0])?;
+
+            // chunks must start at even offsets
+            if label_size % 2 == 1 {
+                labels.write_all(&[0])?;
+            }
+        }
+    }
+
+    let mut written = 8 + u64::from(cue_size);
+
+    if !labels.is_empty() {
+        let list_size =
+            u32::try_from(4 + labels.len()).map_err(|_| IoError::from(ErrorKind::InvalidInput))?;
+
+        sink.write_all(b"LIST")?;
+        sink.write_all(&list_size.to_le_bytes())?;
+        sink.write_all(b"adtl")?;
+        sink.write_all(&labels)?;
+
+        written += 8 + u64::from(list_size);
+    }
+
+    Ok(written)
+}
+
+// Overwrites the sizes of the `RIFF` and `data` chunks in a WAVE file header starting at `file_start`,
+// then returns to the end of the file.
+fn patch_sizes<W: Write + Seek>(
+    file_start: u64,
+    channels: u16,
+    riff_size: u32,
+    data_size: u32,
+    sink: &mut W,
+) -> Result<(), IoError> {
+    // the size of the `data` chunk follows "RIFF", "WAVE", the `fmt ` chunk, and "data"
+    let data_size_offset = 4 + 4 + 4 + 8 + u64::from(fmt_chunk_size(channels)) + 4;
+
+    let _ = sink.seek(SeekFrom::Start(file_start + 4))?;
+    sink.write_all(&riff_size.to_le_bytes())?;
+    let _ = sink.seek(SeekFrom::Start(file_start + data_size_offset))?;
+    sink.write_all(&data_size.to_le_bytes())?;
+    let _ = sink.seek(SeekFrom::End(0))?;
+    Ok(())
+}
+
+// Reads the entire stream into memory, then arranges it so that the data before the loop is followed
+// by the loop region repeated `repeats` times. Data after the loop region is discarded.
+// Loop positions are sample frame indices, so they are converted to byte offsets using the frame size.
+// The returned stream information describes the rearranged data.
+pub(super) fn read_looped<R: Read>(
+    info: &StreamInfo,
+    stream_loop: Loop,
+    frame_size: usize,
+    repeats: u32,
+    source: &mut Reader<R>,
+) -> Result<(StreamInfo, Vec<u8>), PcmError> {
+    let data = source
+        .take(info.size.get() as usize)
+        .map_err(PcmError::from_read(PcmErrorKind::ReadStream))?;
+
+    let to_offset = |sample: u32| (sample as usize).saturating_mul(frame_size).min(data.len());
+    let end = to_offset(stream_loop.end().get());
+    let start = to_offset(stream_loop.start()).min(end);
+    let (intro, region) = (&data[..start], &data[start..end]);
+
+    // The size is checked before allocating, since a large number of repeats could otherwise overflow
+    // or exhaust memory before the size is known to be invalid.
+    let size = region
+        .len()
+        .checked_mul(repeats as usize)
+        .and_then(|size| size.checked_add(intro.len()))
+        .and_then(|size| u32::try_from(size).ok())
+        .and_then(NonZeroU32::new)
+        .ok_or_else(|| PcmError::new(PcmErrorKind::LoopSize))?;
+
+    let mut looped = Vec::with_capacity(size.get() as usize);
+    looped.extend_from_slice(intro);
+    for _ in 0..repeats {
+        looped.extend_from_slice(region);
+    }
+
+    // like the size, the sample count describes the unrolled data rather than the original stream
+    let num_samples = (looped.len() / frame_size)
+        .pipe(u32::try_from)
+        .ok()
+        .and_then(NonZeroU32::new)
+        .ok_or_else(|| PcmError::new(PcmErrorKind::LoopSize))?;
+
+    let mut info = info.clone();
+    info.size = size;
+    info.num_samples = Some(num_samples);
+    info.stream_loop = None;
+
+    Ok((info, looped))
+}
+
+// Reads the entire stereo stream into memory, then swaps the left and right samples of every frame.
+pub(super) fn read_swapped<R: Read>(
+    info: &StreamInfo,
+    byte_depth: usize,
+    source: &mut Reader<R>,
+) -> Result<Vec<u8>, PcmError> {
+    let mut data = source
+        .take(info.size.get() as usize)
+        .map_err(PcmError::from_read(PcmErrorKind::ReadStream))?;
+
+    for frame in data.chunks_exact_mut(byte_depth * 2) {
+        let (left, right) = frame.split_at_mut(byte_depth);
+        left.swap_with_slice(right);
+    }
+
+    Ok(data)
+}
+
+// Reads the entire stream into memory. If every channel has the same samples, only the first channel is kept.
+// The returned stream information describes the returned data.
+pub(super) fn read_dual_mono<R: Read>(
+    info: &StreamInfo,
+    byte_depth: usize,
+    source: &mut Reader<R>,
+) -> Result<(StreamInfo, Vec<u8>), PcmError> {
+    let data = source
+        .take(info.size.get() as usize)
+        .map_err(PcmError::from_read(PcmErrorKind::ReadStream))?;
+
+    let frame_size = usize::from(info.channels.get()) * byte_depth;
+    let frames = data.chunks_exact(frame_size);
+    let is_dual_mono = frames.remainder().is_empty()
+        && frames.clone().all(|frame| {
+            let (first, rest) = frame.split_at(byte_depth);
+            rest.chunks_exact(byte_depth).all(|sample| sample == first)
+        });
+
+    let mono: Vec<u8> = frames.flat_map(|frame| &frame[..byte_depth]).copied().collect();
+
+    match u32::try_from(mono.len()).ok().and_then(NonZeroU32::new) {
+        Some(size) if is_dual_mono => {
+            let mut info = info.clone();
+            info.channels = NonZeroU8::MIN;
+            info.size = size;
+            Ok((info, mono))
+        }
+        _ => Ok((info.clone(), data)),
+    }
+}
+
+// Reads the sample frames from `first` (inclusive) to `last` (exclusive) into memory.
+// Positions are clamped to the end of the stream data.
+// The returned stream information describes the data within the range.
+pub(super) fn read_range<R: Read>(
+    info: &StreamInfo,
+    frame_size: usize,
+    first: u64,
+    last: u64,
+    source: &mut Reader<R>,
+) -> Result<(StreamInfo, Vec<u8>), PcmError> {
+    let stream_size = info.size.get() as usize;
+    let to_offset = |frame: u64| {
+        usize::try_from(frame)
+            .ok()
+            .and_then(|frame| frame.checked_mul(frame_size))
+            .map_or(stream_size, |offset| offset.min(stream_size))
+    };
+    let (start, end) = (to_offset(first), to_offset(last));
+
+    source
+        .skip(start)
+        .map_err(PcmError::from_read(PcmErrorKind::ReadStream))?;
+    let data = source
+        .take(end.saturating_sub(start))
+        .map_err(PcmError::from_read(PcmErrorKind::ReadStream))?;
+
+    let size = data
+        .len()
+        .pipe(u32::try_from)
+        .ok()
+        .and_then(|size| size.try_into().ok())
+        .ok_or_else(|| PcmError::new(PcmErrorKind::RangeSize))?;
+    let num_samples = (data.len() / frame_size)
+        .pipe(u32::try_from)
+        .ok()
+        .and_then(|samples| samples.try_into().ok())
+        .ok_or_else(|| PcmError::new(PcmErrorKind::RangeSize))?;
+
+    let mut info = info.clone();
+    info.size = size;
+    info.num_samples = Some(num_samples);
+    info.stream_loop = None;
+
+    Ok((info, data))
+}
+
+// Encodes interleaved 32-bit float samples that were decoded from a stream.
+pub(super) fn encode_float_samples<W: Write>(
+    info: &StreamInfo,
+    samples: &[f32],
+    mut sink: W,
+) -> Result<W, PcmError> {
+    let size = samples
+        .len()
+        .checked_mul(4)
+        .and_then(|size| u32::try_from(size).ok())
+        .ok_or_else(|| PcmError::new(PcmErrorKind::FileSize))?;
+
+    write_header(
+        size,
+        info.channels.get().into(),
+        info.sample_rate.get(),
+        Format::Float,
+        4,
+        &[],
+        &mut sink,
+    )?;
+
+    for sample in samples {
+        sink.write_all(&sample.to_le_bytes())
+            .map_err(PcmError::from_io(PcmErrorKind::EncodeSample))?;
+    }
+
     sink.flush()
         .map(|()| sink)
         .map_err(PcmError::from_io(PcmErrorKind::FinishStream))
 }
 
+// Encodes interleaved 16-bit integer samples that were decoded from a stream.
+pub(super) fn encode_i16_samples<W: Write>(
+    info: &StreamInfo,
+    samples: &[i16],
+    mut sink: W,
+) -> Result<W, PcmError> {
+    let size = samples
+        .len()
+        .checked_mul(2)
+        .and_then(|size| u32::try_from(size).ok())
+        .ok_or_else(|| PcmError::new(PcmErrorKind::FileSize))?;
+
+    write_header(
+        size,
+        info.channels.get().into(),
+        info.sample_rate.get(),
+        Format::Integer,
+        2,
+        &[],
+        &mut sink,
+    )?;
+
+    for sample in samples {
+        sink.write_all(&sample.to_le_bytes())
+            .map_err(PcmError::from_io(PcmErrorKind::EncodeSample))?;
+    }
+
+    sink.flush()
+        .map(|()| sink)
+        .map_err(PcmError::from_io(PcmErrorKind::FinishStream))
+}
+
+// Writes the optional metadata chunks of a stream, which are placed before the `fmt ` chunk.
+fn metadata_chunks(
+    info: &StreamInfo,
+    bext: Option<&BroadcastMetadata>,
+) -> Result<Vec<u8>, PcmError> {
+    let mut chunks = Vec::new();
+
+    if let Some(metadata) = bext {
+        bext::write_chunk(metadata, info.name.as_deref(), &mut chunks)
+            .map_err(PcmError::from_io(PcmErrorKind::CreateHeader))?;
+    }
+
+    if let Some(stream_loop) = info.stream_loop {
+        write_sampler_chunk(stream_loop, info.sample_rate, &mut chunks)
+            .map_err(PcmError::from_io(PcmErrorKind::CreateHeader))?;
+    }
+
+    Ok(chunks)
+}
+
+// Writes a `smpl` chunk containing the loop of a stream, which samplers and game engines use for seamless looping.
+// Loop positions are sample frame indices, so they are written without conversion.
+fn write_sampler_chunk<W: Write>(
+    stream_loop: Loop,
+    sample_rate: NonZeroU32,
+    sink: &mut W,
+) -> Result<(), IoError> {
+    // `smpl` chunk information taken from:
+    // [1]: https://www.recordingblogs.com/wiki/sample-chunk-of-a-wave-file
+
+    // 36 bytes of sampler information + 24 bytes for the only loop
+    const CHUNK_SIZE: u32 = 36 + 24;
+
+    let start = stream_loop.start();
+    // the end of a sampler loop is the last sample frame played, rather than the frame after it
+    let end = stream_loop.end().get() - 1;
+
+    sink.write_all(b"smpl")?;
+    sink.write_all(&CHUNK_SIZE.to_le_bytes())?;
+    // manufacturer and product
+    sink.write_all(&[0; 8])?;
+    // sample period, in nanoseconds
+    sink.write_all(&(1_000_000_000 / sample_rate.get()).to_le_bytes())?;
+    // MIDI unity note (middle C), pitch fraction, SMPTE format, and SMPTE offset
+    sink.write_all(&60u32.to_le_bytes())?;
+    sink.write_all(&[0; 12])?;
+    // number of loops and size of sampler-specific data
+    sink.write_all(&1u32.to_le_bytes())?;
+    sink.write_all(&0u32.to_le_bytes())?;
+    // cue point ID and loop type (forward)
+    sink.write_all(&[0; 8])?;
+    sink.write_all(&start.to_le_bytes())?;
+    sink.write_all(&end.to_le_bytes())?;
+    // fraction
+    sink.write_all(&0u32.to_le_bytes())?;
+    // play count, where 0 means the loop is repeated indefinitely
+    sink.write_all(&stream_loop.count().unwrap_or(0).to_le_bytes())
+}
+
+// Writes the header of a WAVE file whose `data` chunk contains `data_size` bytes.
+// The size of the `RIFF` chunk is derived from the chunks that follow it, so it is never smaller than the header itself.
 fn write_header<W: Write>(
-    file_size: u32,
+    data_size: u32,
     channels: u16,
     sample_rate: u32,
     format: Format,
     byte_depth: u16,
+    extra_chunks: &[u8],
     sink: &mut W,
-) -> Result<(), IoError> {
+) -> Result<(), PcmError> {
     // WAVE file header information taken from:
     // [1]: https://www-mmsp.ece.mcgill.ca/Documents/AudioFormats/WAVE/WAVE.html
     // [2]: http://soundfile.sapp.org/doc/WaveFormat/
@@ -73,23 +614,80 @@ fn write_header<W: Write>(
         Format::Integer => 1u16,
         Format::Float => 3u16,
     };
+    let fmt_size = fmt_chunk_size(channels);
     let bytes_per_second = sample_rate * u32::from(channels) * u32::from(byte_depth);
 
-    sink.write_all(b"RIFF")?;
-    sink.write_all(&(file_size - 8).to_le_bytes())?;
-    sink.write_all(b"WAVE")?;
-    sink.write_all(b"fmt ")?;
-    sink.write_all(&16u32.to_le_bytes())?;
-    sink.write_all(&format_id.to_le_bytes())?;
-    sink.write_all(&channels.to_le_bytes())?;
-    sink.write_all(&sample_rate.to_le_bytes())?;
-    sink.write_all(&bytes_per_second.to_le_bytes())?;
-    sink.write_all(&(channels * byte_depth).to_le_bytes())?;
-    sink.write_all(&(byte_depth * 8).to_le_bytes())?;
-    sink.write_all(b"data")?;
-    sink.write_all(&(file_size - 40).to_le_bytes())?;
+    // "WAVE" + `fmt ` chunk (8-byte chunk header + contents) + `data` chunk header
+    let riff_size = u32::try_from(extra_chunks.len())
+        .ok()
+        .and_then(|extra_size| (4 + 8 + fmt_size + 8).checked_add(extra_size))
+        .and_then(|size| size.checked_add(data_size))
+        .ok_or_else(|| PcmError::new(PcmErrorKind::FileSize))?;
 
-    Ok(())
+    let write = |sink: &mut W| -> Result<(), IoError> {
+        sink.write_all(b"RIFF")?;
+        sink.write_all(&riff_size.to_le_bytes())?;
+        sink.write_all(b"WAVE")?;
+        sink.write_all(extra_chunks)?;
+        sink.write_all(b"fmt ")?;
+        sink.write_all(&fmt_size.to_le_bytes())?;
+
+        if fmt_size == EXTENSIBLE_FMT_SIZE {
+            sink.write_all(&0xFFFEu16.to_le_bytes())?;
+        } else {
+            sink.write_all(&format_id.to_le_bytes())?;
+        }
+
+        sink.write_all(&channels.to_le_bytes())?;
+        sink.write_all(&sample_rate.to_le_bytes())?;
+        sink.write_all(&bytes_per_second.to_le_bytes())?;
+        sink.write_all(&(channels * byte_depth).to_le_bytes())?;
+        sink.write_all(&(byte_depth * 8).to_le_bytes())?;
+
+        if fmt_size == EXTENSIBLE_FMT_SIZE {
+            // size of the extension, valid bits per sample, and speaker positions of the channels
+            sink.write_all(&22u16.to_le_bytes())?;
+            sink.write_all(&(byte_depth * 8).to_le_bytes())?;
+            sink.write_all(&channel_mask(channels).to_le_bytes())?;
+            // the sub-format GUID starts with the format ID, followed by a fixed suffix
+            sink.write_all(&format_id.to_le_bytes())?;
+            sink.write_all(&[
+                0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+            ])?;
+        }
+
+        sink.write_all(b"data")?;
+        sink.write_all(&data_size.to_le_bytes())
+    };
+
+    write(sink).map_err(PcmError::from_io(PcmErrorKind::CreateHeader))
+}
+
+// The `fmt ` chunk of `WAVE_FORMAT_EXTENSIBLE` files has 24 more bytes than the basic chunk.
+const EXTENSIBLE_FMT_SIZE: u32 = 40;
+
+// Returns the size of the `fmt ` chunk's contents.
+// Files with more than 2 channels use `WAVE_FORMAT_EXTENSIBLE`, which describes the speaker layout of the channels.
+fn fmt_chunk_size(channels: u16) -> u32 {
+    if channels > 2 {
+        EXTENSIBLE_FMT_SIZE
+    } else {
+        16
+    }
+}
+
+// Returns the speaker positions of a `WAVE_FORMAT_EXTENSIBLE` file with `channels` channels.
+// Layouts other than quadraphonic, 5.1, and 7.1 are left unassigned, so players decide the speaker positions.
+fn channel_mask(channels: u16) -> u32 {
+    match channels {
+        // front left, front right, back left, back right
+        4 => 0x33,
+        // front left, front right, front center, low frequency, back left, back right
+        6 => 0x3F,
+        // 5.1 with side left and side right
+        8 => 0x63F,
+        _ => 0,
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -99,7 +697,7 @@ pub(super) enum Format {
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
-pub(super) enum Endianness {
+pub(crate) enum Endianness {
     Little,
     Big,
 }
@@ -110,7 +708,7 @@ pub(super) enum Endianness {
 #[derive(Debug)]
 pub struct PcmError {
     kind: PcmErrorKind,
-    source: PcmErrorSource,
+    source: Option<PcmErrorSource>,
 }
 
 /// A variant of a [`PcmError`].
@@ -127,6 +725,20 @@ pub enum PcmErrorKind {
     EncodeSample,
     /// Failed to flush the writer after encoding the entire stream.
     FinishStream,
+    /// Failed to read the stream data into memory.
+    ReadStream,
+    /// The size of the looped stream data was either 0 bytes or too large to be written.
+    LoopSize,
+    /// Streams could not be combined into a single file because their number of channels or sample rates differed.
+    MismatchedStreams,
+    /// Failed to write cue points and labels for combined streams.
+    CreateCues,
+    /// Failed to update the file header after writing combined streams.
+    PatchHeader,
+    /// The combined size of all streams was too large to be written in a single file.
+    FileSize,
+    /// The size of the stream data within the requested range was either 0 bytes or too large to be written.
+    RangeSize,
 }
 
 #[derive(Debug)]
@@ -136,17 +748,21 @@ enum PcmErrorSource {
 }
 
 impl PcmError {
+    fn new(kind: PcmErrorKind) -> Self {
+        Self { kind, source: None }
+    }
+
     fn from_io(kind: PcmErrorKind) -> impl FnOnce(IoError) -> Self {
         move |source| Self {
             kind,
-            source: PcmErrorSource::Io(source),
+            source: Some(PcmErrorSource::Io(source)),
         }
     }
 
     fn from_read(kind: PcmErrorKind) -> impl FnOnce(ReadError) -> Self {
         move |source| Self {
             kind,
-            source: PcmErrorSource::Read(source),
+            source: Some(PcmErrorSource::Read(source)),
         }
     }
 
@@ -166,8 +782,11 @@ impl Display for PcmError {
 impl Error for PcmError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match &self.source {
-            PcmErrorSource::Io(e) => Some(e),
-            PcmErrorSource::Read(e) => Some(e),
+            Some(source) => match source {
+                PcmErrorSource::Io(e) => Some(e),
+                PcmErrorSource::Read(e) => Some(e),
+            },
+            None => None,
         }
     }
 }
@@ -180,6 +799,300 @@ impl Display for PcmErrorKind {
             Self::DecodeSample => "failed to decode sample from PCM stream",
             Self::EncodeSample => "failed to encode sample",
             Self::FinishStream => "failed to finalize writing PCM stream data",
+            Self::ReadStream => "failed to read PCM stream data",
+            Self::LoopSize => "size of looped PCM stream data was invalid",
+            Self::MismatchedStreams => {
+                "PCM streams had different numbers of channels or sample rates"
+            }
+            Self::CreateCues => "failed to encode cue points",
+            Self::PatchHeader => "failed to update file header",
+            Self::FileSize => "combined size of PCM streams was too large",
+            Self::RangeSize => "size of PCM stream data in range was invalid",
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        encode, encode_combined, encode_float, read_dual_mono, read_looped, read_range, Endianness,
+        Format, PcmErrorKind,
+    };
+    use crate::header::{Loop, StreamInfo};
+    use crate::read::Reader;
+    use std::{io::Cursor, num::NonZeroU32};
+
+    const HEADER_SIZE: usize = 44;
+
+    fn decode_i24(bytes: &[u8]) -> i32 {
+        // sign-extend from the most significant byte, as players do when reading 24-bit WAVE samples
+        let sign = if bytes[2] & 0x80 == 0 { 0x00 } else { 0xFF };
+        i32::from_le_bytes([bytes[0], bytes[1], bytes[2], sign])
+    }
+
+    #[test]
+    fn big_endian_pcm24_keeps_sign() {
+        let samples: [i32; 16] = [
+            0x12_3456, -0x12_3456, 0x7F_FFFF, -0x80_0000, 1, -1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+
+        let data: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| {
+                let [_, b1, b2, b3] = sample.to_be_bytes();
+                [b1, b2, b3]
+            })
+            .collect();
+
+        let info = StreamInfo::new_test(44100, 1, 16, data.len().try_into().unwrap());
+        let mut reader = Reader::new(data.as_slice());
+        let output = encode::<_, _, 3>(
+            Format::Integer,
+            Endianness::Big,
+            &info,
+            None,
+            &mut reader,
+            Vec::new(),
+        )
+        .unwrap();
+
+        let decoded: Vec<i32> = output[HEADER_SIZE..].chunks_exact(3).map(decode_i24).collect();
+        assert_eq!(decoded, samples);
+    }
+
+    #[test]
+    fn write_sizes_of_small_stream() {
+        let data = [1; 16];
+
+        let info = StreamInfo::new_test(44100, 2, 4, 16);
+        let mut reader = Reader::new(data.as_slice());
+        let output = encode::<_, _, 2>(
+            Format::Integer,
+            Endianness::Little,
+            &info,
+            None,
+            &mut reader,
+            Vec::new(),
+        )
+        .unwrap();
+
+        assert_eq!(output.len(), HEADER_SIZE + 16);
+        assert_eq!(output[4..8], 52u32.to_le_bytes());
+        assert_eq!(output[40..44], 16u32.to_le_bytes());
+        assert_eq!(output[HEADER_SIZE..], data);
+    }
+
+    #[test]
+    fn write_sampler_loop() {
+        let data = [1; 16];
+
+        let mut info = StreamInfo::new_test(44100, 2, 4, 16);
+        info.stream_loop = Some(Loop::new_test(1, 3));
+        let mut reader = Reader::new(data.as_slice());
+        let output = encode::<_, _, 2>(
+            Format::Integer,
+            Endianness::Little,
+            &info,
+            None,
+            &mut reader,
+            Vec::new(),
+        )
+        .unwrap();
+
+        // the `smpl` chunk is 68 bytes, including its chunk header
+        assert_eq!(output.len(), HEADER_SIZE + 68 + 16);
+        assert_eq!(output[4..8], (52u32 + 68).to_le_bytes());
+        assert_eq!(output[12..16], *b"smpl");
+        assert_eq!(output[16..20], 60u32.to_le_bytes());
+        assert_eq!(output[48..52], 1u32.to_le_bytes());
+
+        // the loop covers sample frames 1..3, so it ends at (and includes) frame 2
+        assert_eq!(output[64..68], 1u32.to_le_bytes());
+        assert_eq!(output[68..72], 2u32.to_le_bytes());
+        assert_eq!(output[80..84], *b"fmt ");
+        assert_eq!(output[HEADER_SIZE + 68..], data);
+    }
+
+    #[test]
+    fn convert_integer_samples_to_float() {
+        let samples: [i16; 12] = [i16::MIN, 0, 16_384, i16::MAX, 0, 0, 0, 0, 0, 0, 0, 0];
+
+        let data: Vec<u8> = samples.iter().flat_map(|sample| sample.to_be_bytes()).collect();
+
+        let info = StreamInfo::new_test(44100, 1, 12, data.len().try_into().unwrap());
+        let mut reader = Reader::new(data.as_slice());
+        let output =
+            encode_float::<_, _, 2>(Endianness::Big, &info, None, &mut reader, Vec::new()).unwrap();
+
+        // format tag of IEEE float samples
+        assert_eq!(output[20..22], [3, 0]);
+
+        let decoded: Vec<f32> = output[HEADER_SIZE..]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(decoded[..4], [-1.0, 0.0, 0.5, 32_767.0 / 32_768.0]);
+    }
+
+    #[test]
+    fn read_frame_range() {
+        let data: Vec<u8> = (0..12).collect();
+        let info = StreamInfo::new_test(44100, 2, 6, 12);
+
+        let mut reader = Reader::new(data.as_slice());
+        let (range_info, range) = read_range(&info, 2, 1, 3, &mut reader).unwrap();
+        assert_eq!(range, [2, 3, 4, 5]);
+        assert_eq!(range_info.size.get(), 4);
+        assert_eq!(range_info.num_samples.unwrap().get(), 2);
+
+        let mut reader = Reader::new(data.as_slice());
+        let (_, range) = read_range(&info, 2, 4, u64::MAX, &mut reader).unwrap();
+        assert_eq!(range, [8, 9, 10, 11]);
+    }
+
+    #[test]
+    fn collapse_dual_mono() {
+        let info = StreamInfo::new_test(44100, 2, 3, 12);
+
+        let data = [1, 2, 1, 2, 3, 4, 3, 4, 5, 6, 5, 6];
+        let mut reader = Reader::new(data.as_slice());
+        let (mono_info, mono) = read_dual_mono(&info, 2, &mut reader).unwrap();
+        assert_eq!(mono, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(mono_info.channels.get(), 1);
+        assert_eq!(mono_info.size.get(), 6);
+
+        let data = [1, 2, 1, 2, 3, 4, 3, 5, 5, 6, 5, 6];
+        let mut reader = Reader::new(data.as_slice());
+        let (stereo_info, stereo) = read_dual_mono(&info, 2, &mut reader).unwrap();
+        assert_eq!(stereo, data);
+        assert_eq!(stereo_info, info);
+    }
+
+    #[test]
+    fn repeat_loop_region() {
+        let data: Vec<u8> = (0..10).collect();
+        let info = StreamInfo::new_test(44100, 1, 10, 10);
+        let mut reader = Reader::new(data.as_slice());
+
+        let (looped_info, looped) =
+            read_looped(&info, Loop::new_test(2, 5), 1, 3, &mut reader).unwrap();
+
+        assert_eq!(looped, [0, 1, 2, 3, 4, 2, 3, 4, 2, 3, 4]);
+        assert_eq!(looped_info.size.get(), 11);
+        assert_eq!(looped_info.num_samples.map(NonZeroU32::get), Some(11));
+        assert_eq!(reader.position(), 10);
+
+        // loop positions count frames rather than bytes
+        let mut reader = Reader::new(data.as_slice());
+        let (looped_info, looped) =
+            read_looped(&info, Loop::new_test(1, 3), 2, 2, &mut reader).unwrap();
+        assert_eq!(looped, [0, 1, 2, 3, 4, 5, 2, 3, 4, 5]);
+        assert_eq!(looped_info.num_samples.map(NonZeroU32::get), Some(5));
+    }
+
+    #[test]
+    fn reject_oversized_loop() {
+        let data: Vec<u8> = (0..10).collect();
+        let info = StreamInfo::new_test(44100, 1, 10, 10);
+        let mut reader = Reader::new(data.as_slice());
+
+        assert!(read_looped(&info, Loop::new_test(2, 5), 1, u32::MAX, &mut reader)
+            .is_err_and(|e| e.kind() == PcmErrorKind::LoopSize));
+    }
+
+    #[test]
+    fn combine_streams_with_cues() {
+        let data: Vec<u8> = (0..10).collect();
+        let info = [
+            StreamInfo::new_test(44100, 1, 3, 3),
+            StreamInfo::new_test(44100, 1, 7, 7),
+        ];
+        let mut reader = Reader::new(data.as_slice());
+
+        let output = encode_combined::<_, _, 1>(
+            Format::Integer,
+            Endianness::Little,
+            &info,
+            &mut reader,
+            Cursor::new(Vec::new()),
+        )
+        .unwrap()
+        .into_inner();
+
+        let riff_size = u32::from_le_bytes(output[4..8].try_into().unwrap());
+        let data_size = u32::from_le_bytes(output[40..44].try_into().unwrap());
+        assert_eq!(riff_size as usize, output.len() - 8);
+        assert_eq!(data_size, 10);
+        assert_eq!(&output[HEADER_SIZE..HEADER_SIZE + 10], data.as_slice());
+
+        let cues = &output[HEADER_SIZE + 10..];
+        assert_eq!(&cues[..4], b"cue ");
+        assert_eq!(u32::from_le_bytes(cues[8..12].try_into().unwrap()), 2);
+        // sample offset of the second cue point
+        assert_eq!(u32::from_le_bytes(cues[56..60].try_into().unwrap()), 3);
+    }
+
+    #[test]
+    fn write_extensible_header_for_surround() {
+        let data = [0; 24];
+        let info = StreamInfo::new_test(48000, 6, 2, 24);
+
+        let output = encode::<_, _, 2>(
+            Format::Integer,
+            Endianness::Little,
+            &info,
+            None,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+        )
+        .unwrap();
+
+        assert_eq!(output.len(), HEADER_SIZE + 24 + 24);
+        assert_eq!(output[4..8], (60u32 + 24).to_le_bytes());
+        assert_eq!(output[16..20], 40u32.to_le_bytes());
+        assert_eq!(output[20..22], 0xFFFEu16.to_le_bytes());
+        assert_eq!(output[36..38], 22u16.to_le_bytes());
+        assert_eq!(output[38..40], 16u16.to_le_bytes());
+        // 5.1 speaker layout
+        assert_eq!(output[40..44], 0x3Fu32.to_le_bytes());
+        // the sub-format GUID begins with the integer PCM format ID
+        assert_eq!(output[44..46], 1u16.to_le_bytes());
+        assert_eq!(output[60..64], *b"data");
+        assert_eq!(output[64..68], 24u32.to_le_bytes());
+
+        // the data size of combined streams is patched after the larger `fmt ` chunk
+        let info = [info.clone(), info];
+        let data = [0; 48];
+        let output = encode_combined::<_, _, 2>(
+            Format::Integer,
+            Endianness::Little,
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Cursor::new(Vec::new()),
+        )
+        .unwrap()
+        .into_inner();
+
+        let riff_size = u32::from_le_bytes(output[4..8].try_into().unwrap());
+        assert_eq!(riff_size as usize, output.len() - 8);
+        assert_eq!(output[64..68], 48u32.to_le_bytes());
+    }
+
+    #[test]
+    fn reject_mismatched_streams() {
+        let info = [
+            StreamInfo::new_test(44100, 1, 1, 1),
+            StreamInfo::new_test(48000, 1, 1, 1),
+        ];
+        let mut reader = Reader::new([0, 0].as_slice());
+
+        let result = encode_combined::<_, _, 1>(
+            Format::Integer,
+            Endianness::Little,
+            &info,
+            &mut reader,
+            Cursor::new(Vec::new()),
+        );
+        assert!(result.is_err());
+    }
+}
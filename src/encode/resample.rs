@@ -0,0 +1,69 @@
+use std::num::{NonZeroU32, NonZeroUsize};
+
+// Resamples interleaved samples from one sample rate to another using linear interpolation.
+// Linear interpolation is fast and has no dependencies, but it doesn't filter out frequencies above
+// the target Nyquist frequency, so downsampling can introduce some aliasing.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+pub(super) fn resample_linear(
+    samples: &[f32],
+    channels: NonZeroUsize,
+    from: NonZeroU32,
+    to: NonZeroU32,
+) -> Vec<f32> {
+    let channels = channels.get();
+    let in_frames = samples.len() / channels;
+
+    if from == to || in_frames == 0 {
+        return samples.to_vec();
+    }
+
+    let out_frames = (in_frames as u64 * u64::from(to.get())).div_ceil(u64::from(from.get()));
+    let step = f64::from(from.get()) / f64::from(to.get());
+
+    let mut resampled = Vec::with_capacity(out_frames as usize * channels);
+
+    for frame in 0..out_frames {
+        let position = frame as f64 * step;
+        // position is non-negative and less than in_frames
+        #[allow(clippy::cast_sign_loss)]
+        let index = position as usize;
+        let fraction = (position - index as f64) as f32;
+        let next = (index + 1).min(in_frames - 1);
+
+        for channel in 0..channels {
+            let current = samples[index * channels + channel];
+            let following = samples[next * channels + channel];
+            resampled.push(current + (following - current) * fraction);
+        }
+    }
+
+    resampled
+}
+
+#[cfg(test)]
+mod test {
+    use super::resample_linear;
+    use std::num::{NonZeroU32, NonZeroUsize};
+
+    #[test]
+    fn interpolate_between_frames() {
+        let channels = NonZeroUsize::new(2).unwrap();
+        let samples = [0.0, 1.0, 1.0, 0.0];
+
+        let upsampled = resample_linear(
+            &samples,
+            channels,
+            NonZeroU32::new(1).unwrap(),
+            NonZeroU32::new(2).unwrap(),
+        );
+        assert_eq!(upsampled, [0.0, 1.0, 0.5, 0.5, 1.0, 0.0, 1.0, 0.0]);
+
+        let downsampled = resample_linear(
+            &upsampled,
+            channels,
+            NonZeroU32::new(2).unwrap(),
+            NonZeroU32::new(1).unwrap(),
+        );
+        assert_eq!(downsampled, samples);
+    }
+}
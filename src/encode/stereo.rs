@@ -0,0 +1,121 @@
+/// The result of comparing the two channels of a stereo stream.
+///
+/// This type is returned from [`Stream::stereo_correlation`].
+/// It can help diagnose streams whose channels were stored incorrectly:
+/// - A strongly negative [correlation](Self::correlation) suggests that one channel has inverted polarity.
+/// - A non-zero [offset](Self::offset) suggests that one channel is shifted by a sample.
+///
+/// Channels that are only swapped (left and right reversed) can't be detected this way,
+/// since swapping channels doesn't change how similar they are.
+/// Use [`EncodeOptions::swap_stereo_channels`] to write such streams with their channels swapped back.
+///
+/// [`Stream::stereo_correlation`]: crate::Stream::stereo_correlation
+/// [`EncodeOptions::swap_stereo_channels`]: super::EncodeOptions::swap_stereo_channels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelCorrelation {
+    correlation: f32,
+    offset: i8,
+}
+
+impl ChannelCorrelation {
+    /// Returns the correlation coefficient between the left and right channels, in the range [-1.0, 1.0].
+    ///
+    /// Values near 1.0 mean the channels are very similar, values near 0.0 mean they are unrelated,
+    /// and values near -1.0 mean one channel is an inverted copy of the other.
+    /// Streams where either channel is silent have a correlation of 0.0.
+    #[must_use]
+    pub fn correlation(&self) -> f32 {
+        self.correlation
+    }
+
+    /// Returns the offset (in samples) of the right channel relative to the left channel that best aligns them.
+    ///
+    /// Offsets of -1, 0, and 1 are checked. A positive offset means the right channel lags behind the left channel.
+    #[must_use]
+    pub fn offset(&self) -> i8 {
+        self.offset
+    }
+}
+
+// Compares the channels of interleaved stereo samples.
+pub(super) fn correlate(samples: &[f32]) -> ChannelCorrelation {
+    let (left, right): (Vec<_>, Vec<_>) =
+        samples.chunks_exact(2).map(|frame| (frame[0], frame[1])).unzip();
+
+    let correlation = pearson(&left, &right);
+
+    if left.len() < 2 {
+        return ChannelCorrelation {
+            correlation,
+            offset: 0,
+        };
+    }
+
+    let (offset, _) = [(-1, &right[..], &left[1..]), (1, &left[..], &right[1..])]
+        .into_iter()
+        .map(|(offset, first, second)| (offset, pearson(&first[..second.len()], second)))
+        .fold((0, correlation), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        });
+
+    ChannelCorrelation {
+        correlation,
+        offset,
+    }
+}
+
+// Calculates the Pearson correlation coefficient between two sequences of the same length.
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+fn pearson(first: &[f32], second: &[f32]) -> f32 {
+    let len = first.len() as f64;
+    let mean = |values: &[f32]| values.iter().copied().map(f64::from).sum::<f64>() / len;
+    let (first_mean, second_mean) = (mean(first), mean(second));
+
+    let (mut covariance, mut first_variance, mut second_variance) = (0.0, 0.0, 0.0);
+
+    for (&a, &b) in first.iter().zip(second) {
+        let (a, b) = (f64::from(a) - first_mean, f64::from(b) - second_mean);
+        covariance += a * b;
+        first_variance += a * a;
+        second_variance += b * b;
+    }
+
+    let scale = (first_variance * second_variance).sqrt();
+
+    if scale == 0.0 {
+        0.0
+    } else {
+        (covariance / scale) as f32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::correlate;
+
+    #[test]
+    fn detect_inverted_and_offset_channels() {
+        let wave = [0.0, 0.5, 1.0, 0.25, -0.5, -1.0, 0.0, 0.75];
+
+        let same: Vec<_> = wave.iter().flat_map(|&sample| [sample, sample]).collect();
+        let result = correlate(&same);
+        assert!((result.correlation() - 1.0).abs() < 1e-6);
+        assert_eq!(result.offset(), 0);
+
+        let inverted: Vec<_> = wave.iter().flat_map(|&sample| [sample, -sample]).collect();
+        let result = correlate(&inverted);
+        assert!((result.correlation() + 1.0).abs() < 1e-6);
+
+        // the right channel lags one sample behind the left channel
+        let delayed: Vec<_> = wave
+            .iter()
+            .zip([0.0].iter().chain(&wave))
+            .flat_map(|(&left, &right)| [left, right])
+            .collect();
+        assert_eq!(correlate(&delayed).offset(), 1);
+    }
+}
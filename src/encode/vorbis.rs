@@ -1,42 +1,287 @@
+use super::options::{EncodeOptions, VorbisLayerMode};
 use super::vorbis_lookup::VORBIS_LOOKUP;
-use crate::header::StreamInfo;
+use crate::header::{Loop, StreamInfo};
 use crate::read::{ReadError, Reader};
 use lewton::{
     audio::{read_audio_packet_generic, PreviousWindowRight},
     header::{read_header_ident, read_header_setup, IdentHeader, SetupHeader},
 };
 use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
     io::{Error as IoError, Read, Write},
+    iter::zip,
+    num::{NonZeroU32, NonZeroU8},
 };
 use tap::Pipe;
-use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoder, VorbisEncoderBuilder};
+
+/// Returns the Vorbis setup header that is used to encode streams with the given setup header CRC32 checksum.
+///
+/// Sound banks don't store Vorbis setup headers; instead, each stream stores the CRC32 checksum of its setup header,
+/// which is looked up in a table of known setup headers. The returned bytes are the complete setup header packet.
+/// `None` is returned if the checksum is not in the table, in which case the stream can't be encoded.
+///
+/// This is useful for debugging streams that don't decode correctly.
+#[must_use]
+pub fn vorbis_setup_header(crc32: u32) -> Option<&'static [u8]> {
+    VORBIS_LOOKUP.get(&crc32).copied()
+}
 
 pub(super) fn encode<R: Read, W: Write>(
     info: &StreamInfo,
     source: &mut Reader<R>,
+    mut sink: W,
+    options: &EncodeOptions,
+    cache: &mut SetupHeaderCache,
+) -> Result<(W, u64), VorbisError> {
+    let layers = match (options.vorbis_layers, info.vorbis_layers) {
+        (VorbisLayerMode::Chained, Some(layers))
+            if layers.get() > 1 && info.channels.get() % layers.get() == 0 =>
+        {
+            layers.get()
+        }
+        _ => 1,
+    };
+
+    let mut frames = 0;
+
+    if layers == 1 {
+        let mut encoder = build_encoder(info.sample_rate, info.channels, info.stream_loop, sink)?;
+
+        decode(info, source, cache, &options.vorbis_setup_headers, |block| {
+            frames += block.first().map_or(0, Vec::len) as u64;
+            encoder
+                .encode_audio_block(block)
+                .map_err(VorbisError::from_vorbis(VorbisErrorKind::EncodeBlock))
+        })?;
+
+        return encoder
+            .finish()
+            .map(|sink| (sink, frames))
+            .map_err(VorbisError::from_vorbis(VorbisErrorKind::FinishStream));
+    }
+
+    // Each layer is encoded to its own in-memory logical stream.
+    // Logical streams are chained, so a layer can only be written once all previous layers are complete.
+    let layer_channels = info.channels.get() / layers;
+    let mut encoders = (0..layers)
+        .map(|_| {
+            build_encoder(
+                info.sample_rate,
+                layer_channels.try_into().expect("layer channel count is non-zero"),
+                info.stream_loop,
+                Vec::new(),
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    decode(info, source, cache, &options.vorbis_setup_headers, |block| {
+        frames += block.first().map_or(0, Vec::len) as u64;
+        for (encoder, layer) in zip(&mut encoders, block.chunks(layer_channels as usize)) {
+            encoder
+                .encode_audio_block(layer)
+                .map_err(VorbisError::from_vorbis(VorbisErrorKind::EncodeBlock))?;
+        }
+        Ok(())
+    })?;
+
+    for encoder in encoders {
+        let data = encoder
+            .finish()
+            .map_err(VorbisError::from_vorbis(VorbisErrorKind::FinishStream))?;
+
+        sink.write_all(&data)
+            .map_err(VorbisError::from_io(VorbisErrorKind::ChainLayers))?;
+    }
+
+    sink.flush()
+        .map(|()| (sink, frames))
+        .map_err(VorbisError::from_io(VorbisErrorKind::FinishStream))
+}
+
+// construct encoder that prioritizes audio quality
+fn build_encoder<W: Write>(
+    sample_rate: NonZeroU32,
+    channels: NonZeroU8,
+    stream_loop: Option<Loop>,
     sink: W,
-) -> Result<W, VorbisError> {
+) -> Result<VorbisEncoder<W>, VorbisError> {
+    let mut builder = VorbisEncoderBuilder::new(sample_rate, channels, sink)
+        .map_err(VorbisError::from_vorbis(VorbisErrorKind::CreateEncoder))?;
+
+    let _ = builder.bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+        target_quality: 1.0,
+    });
+
+    if let Some(stream_loop) = stream_loop {
+        let _ = builder
+            .comment_tags(loop_comments(stream_loop))
+            .map_err(VorbisError::from_vorbis(VorbisErrorKind::CreateEncoder))?;
+    }
+
+    builder
+        .build()
+        .map_err(VorbisError::from_vorbis(VorbisErrorKind::CreateEncoder))
+}
+
+// Returns the LOOPSTART and LOOPLENGTH comments read by game audio tools, in samples.
+// Loop positions are already sample indices, so they are used without conversion.
+fn loop_comments(stream_loop: Loop) -> [(&'static str, String); 2] {
+    [
+        ("LOOPSTART", stream_loop.start().to_string()),
+        ("LOOPLENGTH", stream_loop.len().to_string()),
+    ]
+}
+
+// Decodes the stream, passing each decoded block to `f`.
+// Blocks contain the samples of each channel separately.
+// The setup header is taken from `cache` (and added to `cache` if missing).
+// A setup header in `setup_headers` (from `EncodeOptions`) takes precedence, and is parsed without being added to `cache`.
+pub(super) fn decode<R, F>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    cache: &mut SetupHeaderCache,
+    setup_headers: &BTreeMap<u32, Box<[u8]>>,
+    f: F,
+) -> Result<(), VorbisError>
+where
+    R: Read,
+    F: FnMut(Vec<Vec<f32>>) -> Result<(), VorbisError>,
+{
     // The stream should have contained the CRC32 of a setup header in a header chunk.
-    // Otherwise, the stream cannot be encoded correctly.
+    // Otherwise, the stream cannot be decoded correctly.
     let crc32 = info
         .vorbis_crc32
         .ok_or_else(|| VorbisError::new(VorbisErrorKind::MissingCrc32))?;
 
     // construct headers needed for decoding packets from stream data
-    let (id_header, setup_header) =
-        init_headers(info.sample_rate.get(), info.channels.get(), crc32)?;
-
-    // construct encoder that prioritizes audio quality
-    let mut encoder = VorbisEncoderBuilder::new(info.sample_rate, info.channels, sink)
-        .map_err(VorbisError::from_vorbis(VorbisErrorKind::CreateEncoder))?
-        .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
-            target_quality: 1.0,
-        })
-        .build()
-        .map_err(VorbisError::from_vorbis(VorbisErrorKind::CreateEncoder))?;
+    let id_header = init_id_header(info.sample_rate.get(), info.channels.get())?;
+    let mut parsed = None;
+    let setup_header =
+        find_setup_header(crc32, info.channels.get(), cache, setup_headers, &mut parsed)?;
+
+    decode_packets(info, source, &id_header, setup_header, f)
+}
+
+// Returns the setup header with the given CRC32, like `decode`.
+// A setup header in `setup_headers` is parsed into `parsed`, so it isn't added to `cache`.
+fn find_setup_header<'a>(
+    crc32: u32,
+    channels: u8,
+    cache: &'a mut SetupHeaderCache,
+    setup_headers: &BTreeMap<u32, Box<[u8]>>,
+    parsed: &'a mut Option<SetupHeader>,
+) -> Result<&'a SetupHeader, VorbisError> {
+    match setup_headers.get(&crc32) {
+        Some(data) => Ok(parsed.insert(init_setup_header(data, channels)?)),
+        None => cache.get(crc32, channels),
+    }
+}
 
+// Returns whether the identification and setup headers needed to decode the stream can be reconstructed,
+// using the setup header with the given CRC32 (which can differ from the one in `info`).
+pub(super) fn can_init_headers(
+    info: &StreamInfo,
+    crc32: Option<u32>,
+    cache: &mut SetupHeaderCache,
+    setup_headers: &BTreeMap<u32, Box<[u8]>>,
+) -> bool {
+    crc32.is_some_and(|crc32| {
+        init_id_header(info.sample_rate.get(), info.channels.get()).is_ok()
+            && find_setup_header(crc32, info.channels.get(), cache, setup_headers, &mut None)
+                .is_ok()
+    })
+}
+
+// Decodes the entire stream into interleaved samples.
+pub(super) fn decode_interleaved<R: Read>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    cache: &mut SetupHeaderCache,
+    setup_headers: &BTreeMap<u32, Box<[u8]>>,
+) -> Result<Vec<f32>, VorbisError> {
+    let mut samples = Vec::new();
+
+    decode(info, source, cache, setup_headers, |block| {
+        let len = block.first().map_or(0, Vec::len);
+        for index in 0..len {
+            samples.extend(block.iter().map(|channel| channel[index]));
+        }
+        Ok(())
+    })?;
+
+    Ok(samples)
+}
+
+// Parsed Vorbis setup headers, keyed by CRC32 and channel count (which affects how setup headers are parsed).
+// Setup headers registered with `register` take precedence over the built-in lookup table.
+#[derive(Default)]
+pub(crate) struct SetupHeaderCache {
+    registered: HashMap<u32, Box<[u8]>>,
+    parsed: HashMap<(u32, u8), SetupHeader>,
+}
+
+impl SetupHeaderCache {
+    pub(crate) fn register(&mut self, crc32: u32, data: &[u8]) {
+        // registering the same data again keeps the setup headers that were already parsed from it
+        if self
+            .registered
+            .get(&crc32)
+            .is_some_and(|registered| **registered == *data)
+        {
+            return;
+        }
+
+        // setup headers parsed from the previous data are outdated
+        self.parsed.retain(|&(key, _), _| key != crc32);
+        drop(self.registered.insert(crc32, data.into()));
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.parsed.len()
+    }
+
+    fn get(&mut self, crc32: u32, channels: u8) -> Result<&SetupHeader, VorbisError> {
+        match self.parsed.entry((crc32, channels)) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let data = match self.registered.get(&crc32) {
+                    Some(data) => data,
+                    None => *VORBIS_LOOKUP
+                        .get(&crc32)
+                        .ok_or_else(|| VorbisError::new(VorbisErrorKind::Crc32Lookup { crc32 }))?,
+                };
+
+                Ok(entry.insert(init_setup_header(data, channels)?))
+            }
+        }
+    }
+}
+
+// `SetupHeader` doesn't implement `Debug`, so only the number of entries is shown
+impl std::fmt::Debug for SetupHeaderCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("SetupHeaderCache")
+            .field("registered", &self.registered.len())
+            .field("parsed", &self.parsed.len())
+            .finish()
+    }
+}
+
+// reads and decodes audio packets until the end of the stream, passing each decoded block to `f`
+fn decode_packets<R, F>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    id_header: &IdentHeader,
+    setup_header: &SetupHeader,
+    mut f: F,
+) -> Result<(), VorbisError>
+where
+    R: Read,
+    F: FnMut(Vec<Vec<f32>>) -> Result<(), VorbisError>,
+{
     let start_pos = source.position();
     let stream_size = info.size.get() as usize;
     let mut window = PreviousWindowRight::new();
@@ -51,23 +296,24 @@ pub(super) fn encode<R: Read, W: Write>(
             break;
         }
 
+        // A malformed packet size could point past the end of the stream data, so it is rejected before reading.
+        let remaining = stream_size.saturating_sub(source.position() - start_pos);
+        if packet_size as usize > remaining {
+            return Err(VorbisError::new(VorbisErrorKind::ReadPacket));
+        }
+
         let packet = source
             .take(packet_size as usize)
             .map_err(VorbisError::from_read(VorbisErrorKind::ReadPacket))?;
 
-        let block: Vec<_> =
-            read_audio_packet_generic(&id_header, &setup_header, &packet, &mut window)
-                .map_err(Into::into)
-                .map_err(VorbisError::from_lewton(VorbisErrorKind::DecodePacket))?;
+        let block = read_audio_packet_generic(id_header, setup_header, &packet, &mut window)
+            .map_err(Into::into)
+            .map_err(VorbisError::from_lewton(VorbisErrorKind::DecodePacket))?;
 
-        encoder
-            .encode_audio_block(block)
-            .map_err(VorbisError::from_vorbis(VorbisErrorKind::EncodeBlock))?;
+        f(block)?;
     }
 
-    encoder
-        .finish()
-        .map_err(VorbisError::from_vorbis(VorbisErrorKind::FinishStream))
+    Ok(())
 }
 
 // default block sizes for FMOD sound banks:
@@ -75,32 +321,19 @@ pub(super) fn encode<R: Read, W: Write>(
 const MIN_BLOCK_SIZE_EXP2: u8 = 8;
 const MAX_BLOCK_SIZE_EXP2: u8 = 11;
 
-fn init_headers(
-    sample_rate: u32,
-    channels: u8,
-    crc32: u32,
-) -> Result<(IdentHeader, SetupHeader), VorbisError> {
-    // construct identification header from scratch
-    let id_header = init_id_header_data(sample_rate, channels)
+// construct identification header from scratch
+fn init_id_header(sample_rate: u32, channels: u8) -> Result<IdentHeader, VorbisError> {
+    init_id_header_data(sample_rate, channels)
         .expect("writing to an in-memory buffer is infallible")
         .pipe_as_ref(read_header_ident)
         .map_err(Into::into)
-        .map_err(VorbisError::from_lewton(VorbisErrorKind::CreateHeaders))?;
-
-    // construct setup header from lookup table
-    let setup_header_data = *VORBIS_LOOKUP
-        .get(&crc32)
-        .ok_or_else(|| VorbisError::new(VorbisErrorKind::Crc32Lookup))?;
-
-    let setup_header = read_header_setup(
-        setup_header_data,
-        channels,
-        (MIN_BLOCK_SIZE_EXP2, MAX_BLOCK_SIZE_EXP2),
-    )
-    .map_err(Into::into)
-    .map_err(VorbisError::from_lewton(VorbisErrorKind::CreateHeaders))?;
+        .map_err(VorbisError::from_lewton(VorbisErrorKind::CreateHeaders))
+}
 
-    Ok((id_header, setup_header))
+fn init_setup_header(data: &[u8], channels: u8) -> Result<SetupHeader, VorbisError> {
+    read_header_setup(data, channels, (MIN_BLOCK_SIZE_EXP2, MAX_BLOCK_SIZE_EXP2))
+        .map_err(Into::into)
+        .map_err(VorbisError::from_lewton(VorbisErrorKind::CreateHeaders))
 }
 
 fn init_id_header_data(sample_rate: u32, channels: u8) -> Result<Vec<u8>, IoError> {
@@ -144,7 +377,10 @@ pub enum VorbisErrorKind {
     /// Failed to create the file headers needed for the Vorbis decoder.
     CreateHeaders,
     /// The stream's associated CRC32 checksum was found, but it did not match any existing entries in the lookup table.
-    Crc32Lookup,
+    Crc32Lookup {
+        /// The CRC32 checksum of the setup header that the stream needs.
+        crc32: u32,
+    },
     /// Failed to create the Vorbis encoder for writing audio samples.
     CreateEncoder,
     /// Failed to read an audio packet from the stream data.
@@ -153,6 +389,8 @@ pub enum VorbisErrorKind {
     DecodePacket,
     /// Failed to encode an audio sample to the writer.
     EncodeBlock,
+    /// Failed to write an encoded layer to the writer when chaining layers into separate logical streams.
+    ChainLayers,
     /// Failed to flush the writer after encoding the entire stream.
     FinishStream,
 }
@@ -162,6 +400,7 @@ enum VorbisErrorSource {
     Encode(vorbis_rs::VorbisError),
     Decode(lewton::VorbisError),
     Read(ReadError),
+    Io(IoError),
 }
 
 impl VorbisError {
@@ -190,6 +429,13 @@ impl VorbisError {
         }
     }
 
+    fn from_io(kind: VorbisErrorKind) -> impl FnOnce(IoError) -> Self {
+        move |source| Self {
+            kind,
+            source: Some(VorbisErrorSource::Io(source)),
+        }
+    }
+
     /// Returns the [`VorbisErrorKind`] associated with this error.
     #[must_use]
     pub fn kind(&self) -> VorbisErrorKind {
@@ -210,6 +456,7 @@ impl Error for VorbisError {
                 VorbisErrorSource::Encode(e) => Some(e),
                 VorbisErrorSource::Decode(e) => Some(e),
                 VorbisErrorSource::Read(e) => Some(e),
+                VorbisErrorSource::Io(e) => Some(e),
             },
             None => None,
         }
@@ -218,15 +465,144 @@ impl Error for VorbisError {
 
 impl Display for VorbisErrorKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        f.write_str(match self {
-            Self::MissingCrc32 => "file header did not contain CRC32 of Vorbis setup header",
-            Self::CreateHeaders => "failed to create dummy Vorbis headers",
-            Self::Crc32Lookup => "CRC32 of Vorbis setup header was not found in lookup table",
-            Self::CreateEncoder => "failed to create Vorbis stream encoder",
-            Self::ReadPacket => "failed to read audio packet from Vorbis stream",
-            Self::DecodePacket => "failed to decode audio packet from Vorbis stream",
-            Self::EncodeBlock => "failed to encode block of samples",
-            Self::FinishStream => "failed to finalize writing Vorbis stream data",
-        })
+        match self {
+            Self::MissingCrc32 => {
+                f.write_str("file header did not contain CRC32 of Vorbis setup header")
+            }
+            Self::CreateHeaders => f.write_str("failed to create dummy Vorbis headers"),
+            Self::Crc32Lookup { crc32 } => f.write_fmt(format_args!(
+                "CRC32 of Vorbis setup header ({crc32:#010X}) was not found in lookup table"
+            )),
+            Self::CreateEncoder => f.write_str("failed to create Vorbis stream encoder"),
+            Self::ReadPacket => f.write_str("failed to read audio packet from Vorbis stream"),
+            Self::DecodePacket => f.write_str("failed to decode audio packet from Vorbis stream"),
+            Self::EncodeBlock => f.write_str("failed to encode block of samples"),
+            Self::ChainLayers => f.write_str("failed to write chained Vorbis layer"),
+            Self::FinishStream => f.write_str("failed to finalize writing Vorbis stream data"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        decode_packets, encode, init_id_header, vorbis_setup_header, SetupHeaderCache,
+        VorbisErrorKind,
+    };
+    use crate::encode::{EncodeOptions, VorbisLayerMode};
+    use crate::header::{Loop, StreamInfo};
+    use crate::read::Reader;
+
+    #[test]
+    fn look_up_setup_header() {
+        assert!(vorbis_setup_header(0xA722_97FF)
+            .is_some_and(|header| header.starts_with(b"\x05vorbis")));
+        assert!(vorbis_setup_header(0).is_none());
+    }
+
+    #[test]
+    fn reject_packet_past_stream_end() {
+        let info = StreamInfo::new_test(44100, 2, 1, 4);
+        let id_header = init_id_header(44100, 2).unwrap();
+        let mut cache = SetupHeaderCache::default();
+        let setup_header = cache.get(0xA722_97FF, 2).unwrap();
+
+        // packet size of 16 bytes, but only 2 bytes of stream data remain
+        let data = [0x10, 0x00, 0x00, 0x00, 0xFF, 0xFF];
+        let mut reader = Reader::new(data.as_slice());
+
+        assert!(
+            decode_packets(&info, &mut reader, &id_header, setup_header, |_| Ok(()))
+                .is_err_and(|e| e.kind() == VorbisErrorKind::ReadPacket)
+        );
+        assert_eq!(reader.position(), 2);
+    }
+
+    #[test]
+    fn cache_setup_headers() {
+        let mut cache = SetupHeaderCache::default();
+
+        assert!(cache.get(0xA722_97FF, 2).is_ok());
+        assert!(cache.get(0xA722_97FF, 2).is_ok());
+        assert_eq!(cache.len(), 1);
+
+        assert!(cache
+            .get(0, 2)
+            .is_err_and(|e| e.kind() == VorbisErrorKind::Crc32Lookup { crc32: 0 }));
+        // the missing checksum is included in the error message, so it can be reported
+        assert!(cache
+            .get(0x1234_ABCD, 2)
+            .is_err_and(|e| e.to_string().contains("0x1234ABCD")));
+
+        // registered setup headers replace cached ones
+        cache.register(0xA722_97FF, &[0; 4]);
+        assert_eq!(cache.len(), 0);
+        assert!(cache
+            .get(0xA722_97FF, 2)
+            .is_err_and(|e| e.kind() == VorbisErrorKind::CreateHeaders));
+
+        cache.register(0, vorbis_setup_header(0xA722_97FF).unwrap());
+        assert!(cache.get(0, 2).is_ok());
+    }
+
+    #[test]
+    fn chain_vorbis_layers() {
+        // audio packets of zeros, which decode to silence
+        let data = [[8, 0, 0, 0, 0, 0, 0, 0, 0, 0]; 4].concat();
+        let mut info = StreamInfo::new_test(44100, 2, 16, 40);
+        info.vorbis_crc32 = Some(0xA722_97FF);
+        info.vorbis_layers = 2.try_into().ok();
+
+        let mut options = EncodeOptions::new();
+        let _ = options.vorbis_layers(VorbisLayerMode::Chained);
+        let (output, frames) = encode(
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &options,
+            &mut SetupHeaderCache::default(),
+        )
+        .unwrap();
+
+        // every layer starts a logical stream with its own serial number and identification header
+        let first_pages = output
+            .windows(6)
+            .enumerate()
+            .filter(|(_, w)| w.starts_with(b"OggS") && w[5] & 0x02 != 0)
+            .map(|(i, _)| &output[i..])
+            .collect::<Vec<_>>();
+        assert_eq!(first_pages.len(), 2);
+        assert_ne!(first_pages[0][14..18], first_pages[1][14..18]);
+
+        let id_headers = output
+            .windows(7)
+            .enumerate()
+            .filter(|(_, w)| w == b"\x01vorbis")
+            .map(|(i, _)| output[i + 11])
+            .collect::<Vec<_>>();
+        assert_eq!(id_headers, [1, 1]);
+        assert!(frames > 0);
+    }
+
+    #[test]
+    fn write_loop_comments() {
+        // audio packets of zeros, which decode to silence
+        let data = [[8, 0, 0, 0, 0, 0, 0, 0, 0, 0]; 4].concat();
+        let mut info = StreamInfo::new_test(44100, 2, 16, 40);
+        info.vorbis_crc32 = Some(0xA722_97FF);
+        info.stream_loop = Some(Loop::new_test(2, 10));
+
+        let (output, _) = encode(
+            &info,
+            &mut Reader::new(data.as_slice()),
+            Vec::new(),
+            &EncodeOptions::new(),
+            &mut SetupHeaderCache::default(),
+        )
+        .unwrap();
+
+        let contains = |comment: &[u8]| output.windows(comment.len()).any(|w| w == comment);
+        assert!(contains(b"LOOPSTART=2"));
+        assert!(contains(b"LOOPLENGTH=8"));
     }
 }
@@ -0,0 +1,235 @@
+use crate::header::StreamInfo;
+use crate::read::Reader;
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{copy, Error as IoError, Read, Write},
+    num::NonZeroU32,
+};
+
+// format tags of the WMA codecs that can be stored in xWMA files
+const WMAV2_FORMAT: u16 = 0x0161;
+const WMAPRO_FORMAT: u16 = 0x0162;
+
+// size of the `fmt ` chunk contents (a WAVEFORMATEX structure without extra data)
+const FMT_SIZE: u32 = 18;
+
+// Wraps the stream data in an xWMA file (a RIFF file that can be decoded by tools like xWMAEncode and FFmpeg).
+// The stream data is copied as-is, so no decoding takes place.
+pub(super) fn encode<R: Read, W: Write>(
+    info: &StreamInfo,
+    source: &mut Reader<R>,
+    mut sink: W,
+) -> Result<W, XwmaError> {
+    let config = info
+        .xwma_config
+        .as_deref()
+        .ok_or_else(|| XwmaError::new(XwmaErrorKind::MissingConfig))
+        .and_then(Config::parse)?;
+
+    write_header(info, &config, &mut sink)
+        .map_err(XwmaError::from_io(XwmaErrorKind::CreateHeader))?;
+
+    // There could be more data after the stream, so a limit is placed on the number of bytes read.
+    let _ = copy(&mut source.limit(info.size.get() as usize), &mut sink)
+        .map_err(XwmaError::from_io(XwmaErrorKind::EncodeStream))?;
+
+    sink.flush()
+        .map(|()| sink)
+        .map_err(XwmaError::from_io(XwmaErrorKind::FinishStream))
+}
+
+// The WAVEFORMATEX fields that FMOD stores (in big-endian order) at the start of the xWMA configuration chunk.
+// The rest of the chunk is not needed to write an xWMA file.
+struct Config {
+    format: u16,
+    block_align: u16,
+    bytes_per_second: u32,
+}
+
+impl Config {
+    fn parse(chunk: &[u8]) -> Result<Self, XwmaError> {
+        let Some(&[f0, f1, b0, b1, r0, r1, r2, r3]) = chunk.get(..8) else {
+            return Err(XwmaError::new(XwmaErrorKind::InvalidConfig));
+        };
+
+        let config = Self {
+            format: u16::from_be_bytes([f0, f1]),
+            block_align: u16::from_be_bytes([b0, b1]),
+            bytes_per_second: u32::from_be_bytes([r0, r1, r2, r3]),
+        };
+
+        match config.format {
+            WMAV2_FORMAT | WMAPRO_FORMAT if config.block_align != 0 => Ok(config),
+            _ => Err(XwmaError::new(XwmaErrorKind::InvalidConfig)),
+        }
+    }
+}
+
+fn write_header<W: Write>(info: &StreamInfo, config: &Config, sink: &mut W) -> Result<(), IoError> {
+    // xWMA file header information taken from:
+    // [1]: https://learn.microsoft.com/en-us/windows/win32/xaudio2/xaudio2-audio-data-formats
+    // [2]: https://github.com/vgmstream/vgmstream/blob/master/src/meta/fsb5.c
+
+    let channels = u16::from(info.channels.get());
+    let data_size = info.size.get();
+
+    // The decoded packet cumulative data size (dpds) table holds the number of bytes decoded
+    // after each packet. FMOD doesn't store this table in a usable form, so the decoded size
+    // of the stream is spread evenly across its packets.
+    let packets = data_size.div_ceil(u32::from(config.block_align));
+    let decoded_size =
+        u64::from(info.num_samples.map_or(0, NonZeroU32::get)) * u64::from(channels) * 2;
+
+    let dpds_size = packets
+        .checked_mul(4)
+        .ok_or_else(|| IoError::other("xWMA stream is too large to be written"))?;
+    let file_size = (4 + 8 + FMT_SIZE + 8 + 8)
+        .checked_add(dpds_size)
+        .and_then(|size| size.checked_add(data_size))
+        .ok_or_else(|| IoError::other("xWMA stream is too large to be written"))?;
+
+    sink.write_all(b"RIFF")?;
+    sink.write_all(&file_size.to_le_bytes())?;
+    sink.write_all(b"XWMA")?;
+
+    sink.write_all(b"fmt ")?;
+    sink.write_all(&FMT_SIZE.to_le_bytes())?;
+    sink.write_all(&config.format.to_le_bytes())?;
+    sink.write_all(&channels.to_le_bytes())?;
+    sink.write_all(&info.sample_rate.get().to_le_bytes())?;
+    sink.write_all(&config.bytes_per_second.to_le_bytes())?;
+    sink.write_all(&config.block_align.to_le_bytes())?;
+    // xWMA streams are always decoded to 16-bit samples
+    sink.write_all(&16u16.to_le_bytes())?;
+    // no extra format data
+    sink.write_all(&0u16.to_le_bytes())?;
+
+    sink.write_all(b"dpds")?;
+    sink.write_all(&dpds_size.to_le_bytes())?;
+    for packet in 1..=u64::from(packets) {
+        let cumulative_size = decoded_size * packet / u64::from(packets);
+        sink.write_all(&u32::try_from(cumulative_size).unwrap_or(u32::MAX).to_le_bytes())?;
+    }
+
+    sink.write_all(b"data")?;
+    sink.write_all(&data_size.to_le_bytes())?;
+
+    Ok(())
+}
+
+/// Represents an error that can occur when encoding an xWMA stream.
+///
+/// See [`XwmaErrorKind`] for the different kinds of errors that can occur.
+#[derive(Debug)]
+pub struct XwmaError {
+    kind: XwmaErrorKind,
+    source: Option<IoError>,
+}
+
+/// A variant of an [`XwmaError`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum XwmaErrorKind {
+    /// The stream header did not contain an xWMA configuration.
+    MissingConfig,
+    /// The xWMA configuration of the stream was invalid.
+    InvalidConfig,
+    /// Failed to write the file header due to an underlying I/O error.
+    CreateHeader,
+    /// Failed to copy the stream data from reader to writer.
+    EncodeStream,
+    /// Failed to flush the writer after encoding the entire stream.
+    FinishStream,
+}
+
+impl XwmaError {
+    fn new(kind: XwmaErrorKind) -> Self {
+        Self { kind, source: None }
+    }
+
+    fn from_io(kind: XwmaErrorKind) -> impl FnOnce(IoError) -> Self {
+        move |source| Self {
+            kind,
+            source: Some(source),
+        }
+    }
+
+    /// Returns the [`XwmaErrorKind`] associated with this error.
+    #[must_use]
+    pub fn kind(&self) -> XwmaErrorKind {
+        self.kind
+    }
+}
+
+impl Display for XwmaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        self.kind.fmt(f)
+    }
+}
+
+impl Error for XwmaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.source {
+            Some(e) => Some(e),
+            None => None,
+        }
+    }
+}
+
+impl Display for XwmaErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(match self {
+            Self::MissingConfig => "xWMA configuration was not found in stream header",
+            Self::InvalidConfig => "xWMA configuration was invalid",
+            Self::CreateHeader => "failed to encode file header",
+            Self::EncodeStream => "failed to copy xWMA stream data",
+            Self::FinishStream => "failed to finalize writing xWMA stream data",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{encode, XwmaErrorKind};
+    use crate::header::StreamInfo;
+    use crate::read::Reader;
+
+    #[test]
+    fn wrap_stream_data() {
+        let mut info = StreamInfo::new_test(44100, 2, 1000, 10);
+        // WMAv2, 4-byte packets, 6000 bytes per second
+        info.xwma_config = Some(Box::new([0x01, 0x61, 0, 4, 0, 0, 0x17, 0x70, 0xAB, 0xCD]));
+        let data = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+        let file = encode(&info, &mut Reader::new(data.as_slice()), Vec::new()).unwrap();
+
+        assert_eq!(file.len(), 76);
+        assert_eq!(&file[..4], b"RIFF");
+        assert_eq!(&file[4..8], 68u32.to_le_bytes());
+        assert_eq!(&file[8..12], b"XWMA");
+        assert_eq!(&file[20..22], 0x0161u16.to_le_bytes());
+        assert_eq!(&file[28..32], 6000u32.to_le_bytes());
+        assert_eq!(&file[32..34], 4u16.to_le_bytes());
+        // 3 packets share 4000 bytes of decoded samples
+        assert_eq!(&file[38..42], b"dpds");
+        assert_eq!(&file[42..46], 12u32.to_le_bytes());
+        assert_eq!(&file[46..50], 1333u32.to_le_bytes());
+        assert_eq!(&file[54..58], 4000u32.to_le_bytes());
+        assert_eq!(&file[58..62], b"data");
+        assert_eq!(&file[66..], &data[..10]);
+    }
+
+    #[test]
+    fn reject_invalid_config() {
+        let mut info = StreamInfo::new_test(44100, 2, 1000, 10);
+        let source = [0; 10];
+
+        let result = encode(&info, &mut Reader::new(source.as_slice()), Vec::new());
+        assert!(result.is_err_and(|e| e.kind() == XwmaErrorKind::MissingConfig));
+
+        info.xwma_config = Some(Box::new([0x01, 0x60, 0, 4, 0, 0, 0x17, 0x70]));
+        let result = encode(&info, &mut Reader::new(source.as_slice()), Vec::new());
+        assert!(result.is_err_and(|e| e.kind() == XwmaErrorKind::InvalidConfig));
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     error::Error,
     ffi::FromBytesUntilNulError,
     fmt::{Display, Formatter, Result as FmtResult},
+    io::Error as IoError,
     str::Utf8Error,
 };
 
@@ -17,7 +18,9 @@ pub(crate) struct HeaderError {
 pub(crate) enum HeaderErrorKind {
     Magic,
     Version,
-    UnknownVersion { version: u32 },
+    UnknownVersion {
+        version: u32,
+    },
     StreamCount,
     ZeroStreams,
     StreamHeadersSize,
@@ -25,13 +28,44 @@ pub(crate) enum HeaderErrorKind {
     TotalStreamSize,
     ZeroTotalStreamSize,
     AudioFormat,
-    UnknownAudioFormat { flag: u32 },
+    UnknownAudioFormat {
+        flag: u32,
+    },
     EncodingFlags,
     Metadata,
     StreamHeader,
-    ZeroStreamSize { index: u32 },
-    WrongHeaderSize { expected: usize, actual: usize },
+    ZeroStreamSize {
+        index: u32,
+    },
+    StreamDataOverlap {
+        index: u32,
+    },
+    WrongStreamHeadersSize {
+        expected: usize,
+        actual: usize,
+    },
     NameTable,
+    WrongNameTableSize {
+        expected: usize,
+        actual: usize,
+    },
+    StreamData {
+        index: u32,
+    },
+    FirstStreamOffset {
+        offset: u32,
+    },
+    DataSectionOffset {
+        expected: usize,
+        actual: usize,
+    },
+    MisalignedStreamData {
+        offset: usize,
+    },
+    NextBank,
+    Rewind,
+    #[cfg(feature = "tokio")]
+    FileHeader,
 }
 
 #[derive(Debug)]
@@ -39,6 +73,7 @@ pub(crate) enum HeaderErrorSource {
     Read(ReadError),
     Stream(StreamError),
     NameTable(NameError),
+    Io(IoError),
 }
 
 impl HeaderError {
@@ -53,11 +88,24 @@ impl HeaderError {
         }
     }
 
+    pub(crate) fn new_with_io(kind: HeaderErrorKind, source: IoError) -> Self {
+        Self {
+            kind,
+            source: Some(HeaderErrorSource::Io(source)),
+        }
+    }
+
     pub(crate) fn factory(kind: HeaderErrorKind) -> impl FnOnce(ReadError) -> Self {
         move |source| Self::new_with_source(kind, source)
     }
 }
 
+impl HeaderError {
+    pub(crate) fn is_eof(&self) -> bool {
+        matches!(&self.source, Some(HeaderErrorSource::Read(e)) if e.is_eof())
+    }
+}
+
 #[cfg(test)]
 impl HeaderError {
     pub(crate) fn kind(&self) -> HeaderErrorKind {
@@ -106,10 +154,30 @@ impl Display for HeaderError {
             Metadata => f.write_str("failed to read (unused) metadata bytes"),
             StreamHeader => f.write_str("failed to parse stream header"),
             ZeroStreamSize { index } => f.write_fmt(format_args!("size of data of stream at index {index} was 0 bytes")),
-            WrongHeaderSize { expected, actual } => {
-                f.write_fmt(format_args!("total size of base header and stream headers ({actual} bytes) was different from expected ({expected} bytes)"))
+            StreamDataOverlap { index } => {
+                f.write_fmt(format_args!("data of stream at index {index} started past the next stream or the end of the stream data section"))
+            }
+            WrongStreamHeadersSize { expected, actual } => {
+                f.write_fmt(format_args!("stream headers ended at offset {actual} instead of offset {expected} given by the stream headers size"))
+            }
+            NameTable => f.write_str("failed to read stream names"),
+            WrongNameTableSize { expected, actual } => {
+                f.write_fmt(format_args!("name table ended at offset {actual} instead of offset {expected} given by the name table size"))
+            }
+            StreamData { index } => f.write_fmt(format_args!("failed to read data of stream at index {index}")),
+            FirstStreamOffset { offset } => {
+                f.write_fmt(format_args!("data of first stream started at offset {offset} instead of the start of the stream data section"))
+            }
+            DataSectionOffset { expected, actual } => {
+                f.write_fmt(format_args!("stream data section started at offset {actual} instead of expected offset {expected}"))
+            }
+            MisalignedStreamData { offset } => {
+                f.write_fmt(format_args!("stream data section started at an offset not aligned to 32 bytes ({offset})"))
             }
-            NameTable => f.write_str("failed to read stream names")
+            NextBank => f.write_str("failed to seek to next sound bank"),
+            Rewind => f.write_str("failed to seek back to start of stream data"),
+            #[cfg(feature = "tokio")]
+            FileHeader => f.write_str("failed to read file header"),
         }
     }
 }
@@ -121,6 +189,7 @@ impl Error for HeaderError {
                 HeaderErrorSource::Read(e) => Some(e),
                 HeaderErrorSource::Stream(e) => Some(e),
                 HeaderErrorSource::NameTable(e) => Some(e),
+                HeaderErrorSource::Io(e) => Some(e),
             },
             None => None,
         }
@@ -230,9 +299,14 @@ pub(crate) enum ChunkErrorKind {
     ZeroSampleRate,
     LoopStart,
     LoopEnd,
+    LoopCount,
     ZeroLengthLoop,
     DspCoefficients,
     VorbisCrc32,
+    PeakVolume,
+    Atrac9Config,
+    XwmaConfig,
+    OpusDataSize,
     VorbisLayerCount,
     TooManyVorbisLayers { layers: u32 },
     ZeroVorbisLayers,
@@ -284,9 +358,14 @@ impl Display for ChunkError {
             ZeroSampleRate => f.write_str("sample rate was 0"),
             LoopStart => f.write_str("failed to read starting position of loop in stream"),
             LoopEnd => f.write_str("failed to read ending position of loop in stream"),
+            LoopCount => f.write_str("failed to read number of loop repeats in stream"),
             ZeroLengthLoop => f.write_str("length of loop in stream was 0"),
             DspCoefficients => f.write_str("failed to read DSP coefficients of stream"),
             VorbisCrc32 => f.write_str("failed to read CRC32 of Vorbis setup header"),
+            PeakVolume => f.write_str("failed to read peak volume of stream"),
+            Atrac9Config => f.write_str("failed to read ATRAC9 configuration of stream"),
+            XwmaConfig => f.write_str("failed to read xWMA configuration of stream"),
+            OpusDataSize => f.write_str("failed to read Opus data size of stream"),
             VorbisLayerCount => {
                 f.write_str("failed to read number of layers per channel in Vorbis stream")
             }
@@ -11,19 +11,90 @@ use std::{
     io::Read,
     iter::zip,
     num::{NonZeroU32, NonZeroU8},
-    ops::Mul,
+    ops::{Mul, Range},
+    time::Duration,
 };
 use tap::Pipe;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct Header {
+    pub(crate) version: u32,
     pub(crate) format: AudioFormat,
     pub(crate) flags: u32,
     pub(crate) stream_info: Box<[StreamInfo]>,
+    pub(crate) data_offset: usize,
+    // the offset of every stream's data from the start of the sound bank
+    pub(crate) stream_offsets: Box<[usize]>,
+    pub(crate) bank_size: usize,
+    pub(crate) skipped_names: Option<NameTable>,
+    pub(crate) raw: Option<Box<[u8]>>,
+}
+
+/// The location of stream names that were skipped while parsing the file header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct NameTable {
+    start: usize,
+    offsets: Box<[u32]>,
+}
+
+/// Options that change how the file header is parsed.
+/// Every option defaults to the strictest behavior.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct ParseOptions {
+    pub(crate) unknown_format_as_raw: bool,
+    pub(crate) strict_alignment: bool,
+    pub(crate) fallback_dsp_coeffs: Option<[i16; 16]>,
+    pub(crate) parse_chunks: ChunkParseMode,
+    pub(crate) skip_invalid_chunks: bool,
+    pub(crate) skip_names: bool,
+    pub(crate) lossy_names: bool,
+    pub(crate) allow_zero_samples: bool,
+    pub(crate) capture_raw_header: bool,
+}
+
+/// Represents which metadata is parsed from stream header chunks.
+///
+/// Chunks needed to encode stream data (e.g. channel count, sample rate, DSP coefficients, and Vorbis setup information)
+/// are always parsed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ChunkParseMode {
+    /// Only chunks needed to encode stream data are parsed. Other chunks (e.g. loop information) are skipped.
+    Minimal,
+    /// All known chunks are parsed.
+    #[default]
+    All,
 }
 
 impl Header {
     pub(crate) fn parse<R: Read>(reader: &mut Reader<R>) -> Result<Self, HeaderError> {
+        Self::parse_with(reader, &ParseOptions::default())
+    }
+
+    pub(crate) fn parse_with<R: Read>(
+        reader: &mut Reader<R>,
+        options: &ParseOptions,
+    ) -> Result<Self, HeaderError> {
+        let header = Self::parse_until_data(reader, options)?;
+
+        reader
+            .advance_to(header.data_offset)
+            .map_err(HeaderError::factory(HeaderErrorKind::StreamData { index: 0 }))?;
+
+        Ok(header)
+    }
+
+    // Parses the file header without reading past it, leaving any padding before the first stream unread.
+    // This is used when the padding is read separately (e.g. from an async reader).
+    pub(crate) fn parse_until_data<R: Read>(
+        reader: &mut Reader<R>,
+        options: &ParseOptions,
+    ) -> Result<Self, HeaderError> {
+        if options.capture_raw_header {
+            reader.start_capture();
+        }
+
         // check for file signature
         match reader.take_const() {
             Ok(data) if data == FSB5_MAGIC => Ok(()),
@@ -32,10 +103,10 @@ impl Header {
         }?;
 
         // determines how encoding flags are read
-        let version = reader
+        let raw_version = reader
             .le_u32()
-            .map_err(HeaderError::factory(HeaderErrorKind::Version))?
-            .try_into()?;
+            .map_err(HeaderError::factory(HeaderErrorKind::Version))?;
+        let version = raw_version.try_into()?;
 
         let num_streams = reader
             .le_u32()
@@ -60,7 +131,7 @@ impl Header {
         let format = reader
             .le_u32()
             .map_err(HeaderError::factory(HeaderErrorKind::AudioFormat))
-            .and_then(AudioFormat::parse)?;
+            .and_then(|flag| AudioFormat::parse(flag, options))?;
 
         // read encoding flags
         let (flags, base_header_size) = match version {
@@ -83,17 +154,25 @@ impl Header {
             .advance_to(base_header_size)
             .map_err(HeaderError::factory(HeaderErrorKind::Metadata))?;
 
-        let mut stream_info = parse_stream_headers(reader, num_streams, total_stream_size)?;
+        let (mut stream_info, first_stream_offset) =
+            parse_stream_headers(reader, format, num_streams, total_stream_size, options)?;
 
-        let header_size = base_header_size + stream_headers_size as usize;
+        let header_size = base_header_size.saturating_add(stream_headers_size as usize);
 
-        // make sure base header + stream headers have been read
-        reader.advance_to(header_size).map_err(HeaderError::factory(
-            HeaderErrorKind::WrongHeaderSize {
+        // make sure base header + stream headers have been read, and nothing past them
+        if reader.position() > header_size {
+            return Err(HeaderError::new(HeaderErrorKind::WrongStreamHeadersSize {
                 expected: header_size,
                 actual: reader.position(),
-            },
-        ))?;
+            }));
+        }
+
+        reader
+            .advance_to_offset(base_header_size, stream_headers_size as usize)
+            .map_err(HeaderError::factory(HeaderErrorKind::WrongStreamHeadersSize {
+                expected: header_size,
+                actual: reader.position(),
+            }))?;
 
         // Read stream names, if present.
         // The name table has two parts: name offsets, then names (stored as null-terminated strings).
@@ -101,29 +180,107 @@ impl Header {
         // for example, if the first name offset is 0 and the second name offset is 12,
         // then the first name's length (including the null terminator) is 12 - 0 = 12.
         // The final name offset is subtracted from the name table size to get the final name's length.
-        if name_table_size != 0 {
-            let mut name_offsets = Vec::with_capacity(num_streams.get() as usize + 1);
+        let skipped_names = if name_table_size == 0 {
+            None
+        } else {
+            parse_name_table(reader, num_streams, name_table_size, &mut stream_info, options)?
+        };
 
-            for index in 0..num_streams.get() {
-                let offset = reader
-                    .le_u32()
-                    .map_err(NameError::read_factory(index, NameErrorKind::NameOffset))?;
+        let data_section_start = header_size.saturating_add(name_table_size as usize);
 
-                name_offsets.push(offset);
-            }
-            name_offsets.push(name_table_size);
+        // Padding before the first stream is read when advancing to the stream data below, so it isn't captured.
+        let raw = reader.finish_capture().map(|mut raw| {
+            raw.truncate(data_section_start);
+            raw.into_boxed_slice()
+        });
+
+        if options.strict_alignment {
+            check_data_alignment(reader, data_section_start)?;
+        }
+
+        // Some sound banks pad the end of the name table or the start of the data section.
+        // Stream data is read from the first stream's data offset so the padding isn't read as stream data.
+        let data_offset = data_section_start.saturating_add(first_stream_offset as usize);
 
-            read_stream_names(reader, &name_offsets, &mut stream_info)?;
+        if reader.position() > data_offset {
+            return Err(HeaderError::new(HeaderErrorKind::DataSectionOffset {
+                expected: data_offset,
+                actual: reader.position(),
+            }));
         }
 
         Ok(Self {
+            version: raw_version,
             format,
             flags,
+            stream_offsets: stream_offsets(&stream_info, data_offset),
             stream_info: stream_info.into_boxed_slice(),
+            data_offset,
+            bank_size: header_size
+                .saturating_add(name_table_size as usize)
+                .saturating_add(total_stream_size.get() as usize),
+            skipped_names,
+            raw,
         })
     }
 }
 
+// Calculates the offset of every stream's data from the start of the sound bank.
+// Streams are stored back-to-back, so each stream starts where the previous stream ends.
+fn stream_offsets(stream_info: &[StreamInfo], data_offset: usize) -> Box<[usize]> {
+    stream_info
+        .iter()
+        .scan(data_offset, |offset, info| {
+            let start = *offset;
+            *offset = offset.saturating_add(info.size.get() as usize);
+            Some(start)
+        })
+        .collect()
+}
+
+// Returns the location of stream names if they were skipped instead of being read.
+fn parse_name_table<R: Read>(
+    reader: &mut Reader<R>,
+    num_streams: NonZeroU32,
+    name_table_size: u32,
+    stream_info: &mut [StreamInfo],
+    options: &ParseOptions,
+) -> Result<Option<NameTable>, HeaderError> {
+    let table_end = reader.position().saturating_add(name_table_size as usize);
+    let mut name_offsets = Vec::with_capacity(preallocated_streams(num_streams) + 1);
+
+    for index in 0..num_streams.get() {
+        let offset = reader
+            .le_u32()
+            .map_err(NameError::read_factory(index, NameErrorKind::NameOffset))?;
+
+        name_offsets.push(offset);
+    }
+    name_offsets.push(name_table_size);
+
+    let skipped_names = if options.skip_names {
+        let start = reader.position();
+        skip_stream_names(reader, &name_offsets)?;
+        Some(NameTable {
+            start,
+            offsets: name_offsets.into_boxed_slice(),
+        })
+    } else {
+        read_stream_names(reader, &name_offsets, options.lossy_names, stream_info)?;
+        None
+    };
+
+    // name offsets determine how many bytes are read, so they can point past the end of the name table
+    if reader.position() > table_end {
+        return Err(HeaderError::new(HeaderErrorKind::WrongNameTableSize {
+            expected: table_end,
+            actual: reader.position(),
+        }));
+    }
+
+    Ok(skipped_names)
+}
+
 const FSB5_MAGIC: [u8; 4] = *b"FSB5";
 
 enum Version {
@@ -145,6 +302,7 @@ impl TryFrom<u32> for Version {
 
 /// Represents known audio formats of streams within a sound bank.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum AudioFormat {
     /// PCM with 8-bit integer samples.
@@ -157,7 +315,7 @@ pub enum AudioFormat {
     Pcm32,
     /// PCM with 32-bit float (IEEE 754) samples.
     PcmFloat,
-    /// GC ADPCM, used in games for the GameCube, Wii and Wii U.
+    /// GC ADPCM, used in games for the `GameCube`, Wii and Wii U.
     GcAdpcm,
     /// IMA ADPCM, developed by the
     /// [Interactive Multimedia Association](https://en.wikipedia.org/wiki/Interactive_Multimedia_Association).
@@ -176,7 +334,7 @@ pub enum AudioFormat {
     /// CELT, developed by the [Xiph.Org Foundation](https://en.wikipedia.org/wiki/Xiph.Org_Foundation).
     /// The CELT format is obsolete, and its functionality has been merged into Opus.
     Celt,
-    /// ATRAC9, used in PlayStation games and debuting with the PS Vita.
+    /// ATRAC9, used in `PlayStation` games and debuting with the PS Vita.
     /// ATRAC9 is part of the ATRAC family of audio formats.
     Atrac9,
     /// xWMA, used in games for Windows and Xbox systems.
@@ -189,36 +347,137 @@ pub enum AudioFormat {
     /// Opus, developed by the [Xiph.Org Foundation](https://en.wikipedia.org/wiki/Xiph.Org_Foundation).
     /// Opus is intended to replace older Xiph.Org formats such as Vorbis.
     Opus,
+    /// A format not recognized by this library, along with its raw format flag.
+    ///
+    /// This is only produced when parsing with [`BankBuilder::treat_unknown_format_as_raw`].
+    /// Streams of this format can be extracted as raw bytes, but not encoded.
+    ///
+    /// [`BankBuilder::treat_unknown_format_as_raw`]: crate::BankBuilder::treat_unknown_format_as_raw
+    Unknown(u32),
 }
 
 impl AudioFormat {
-    fn parse(value: u32) -> Result<Self, HeaderError> {
+    fn parse(value: u32, options: &ParseOptions) -> Result<Self, HeaderError> {
+        match Self::from_raw(value) {
+            Some(format) => Ok(format),
+            None if options.unknown_format_as_raw => Ok(Self::Unknown(value)),
+            None => Err(HeaderError::new(HeaderErrorKind::UnknownAudioFormat { flag: value })),
+        }
+    }
+
+    /// Returns the audio format with the given format flag, as stored in sound bank headers and FMOD project files.
+    /// `None` is returned if the flag doesn't refer to a known format.
+    ///
+    /// This is the inverse of [`AudioFormat::as_raw`].
+    #[must_use]
+    pub fn from_raw(value: u32) -> Option<Self> {
         match value {
-            1 => Ok(Self::Pcm8),
-            2 => Ok(Self::Pcm16),
-            3 => Ok(Self::Pcm24),
-            4 => Ok(Self::Pcm32),
-            5 => Ok(Self::PcmFloat),
-            6 => Ok(Self::GcAdpcm),
-            7 => Ok(Self::ImaAdpcm),
-            8 => Ok(Self::Vag),
-            9 => Ok(Self::HeVag),
-            10 => Ok(Self::Xma),
-            11 => Ok(Self::Mpeg),
-            12 => Ok(Self::Celt),
-            13 => Ok(Self::Atrac9),
-            14 => Ok(Self::Xwma),
-            15 => Ok(Self::Vorbis),
-            16 => Ok(Self::FAdpcm),
-            17 => Ok(Self::Opus),
-            flag => Err(HeaderError::new(HeaderErrorKind::UnknownAudioFormat { flag })),
+            1 => Some(Self::Pcm8),
+            2 => Some(Self::Pcm16),
+            3 => Some(Self::Pcm24),
+            4 => Some(Self::Pcm32),
+            5 => Some(Self::PcmFloat),
+            6 => Some(Self::GcAdpcm),
+            7 => Some(Self::ImaAdpcm),
+            8 => Some(Self::Vag),
+            9 => Some(Self::HeVag),
+            10 => Some(Self::Xma),
+            11 => Some(Self::Mpeg),
+            12 => Some(Self::Celt),
+            13 => Some(Self::Atrac9),
+            14 => Some(Self::Xwma),
+            15 => Some(Self::Vorbis),
+            16 => Some(Self::FAdpcm),
+            17 => Some(Self::Opus),
+            _ => None,
+        }
+    }
+
+    /// Returns the format flag of this audio format, as stored in sound bank headers and FMOD project files.
+    /// For [`AudioFormat::Unknown`], the stored flag is returned.
+    #[must_use]
+    pub fn as_raw(self) -> u32 {
+        match self {
+            Self::Pcm8 => 1,
+            Self::Pcm16 => 2,
+            Self::Pcm24 => 3,
+            Self::Pcm32 => 4,
+            Self::PcmFloat => 5,
+            Self::GcAdpcm => 6,
+            Self::ImaAdpcm => 7,
+            Self::Vag => 8,
+            Self::HeVag => 9,
+            Self::Xma => 10,
+            Self::Mpeg => 11,
+            Self::Celt => 12,
+            Self::Atrac9 => 13,
+            Self::Xwma => 14,
+            Self::Vorbis => 15,
+            Self::FAdpcm => 16,
+            Self::Opus => 17,
+            Self::Unknown(flag) => flag,
+        }
+    }
+
+    /// Returns the MIME type of data produced when encoding streams of this format.
+    ///
+    /// PCM and ADPCM streams are encoded as WAVE files (`audio/wav`), Vorbis streams are encoded as Ogg files (`audio/ogg`),
+    /// Opus streams are wrapped in Ogg Opus files (`audio/ogg`), and MPEG streams are copied as MP3 files (`audio/mpeg`).
+    /// For other formats, `application/octet-stream` is returned,
+    /// which matches the raw stream data written by `write_raw()`, the `.at9` files written for ATRAC9 streams,
+    /// and the `.xwma` files written for xWMA streams.
+    #[must_use]
+    pub fn output_mime_type(&self) -> &'static str {
+        match self {
+            Self::Pcm8
+            | Self::Pcm16
+            | Self::Pcm24
+            | Self::Pcm32
+            | Self::PcmFloat
+            | Self::GcAdpcm
+            | Self::ImaAdpcm
+            | Self::FAdpcm => "audio/wav",
+            Self::Vorbis | Self::Opus => "audio/ogg",
+            Self::Mpeg => "audio/mpeg",
+            _ => "application/octet-stream",
+        }
+    }
+
+    /// Returns the file extension (without a leading dot) for files containing streams of this format.
+    ///
+    /// For formats that can be encoded, this matches the encoder output:
+    /// PCM and ADPCM (GC, IMA, and FADPCM) streams are encoded as WAVE files (`wav`),
+    /// Vorbis streams as Ogg files (`ogg`), ATRAC9 streams as `at9` files, MPEG streams as MP3 files (`mp3`),
+    /// xWMA streams as `xwma` files, and Opus streams as Ogg Opus files (`opus`).
+    /// For other formats, the extension describes the stream data written by `write_raw()`
+    /// (e.g. `xma` for XMA), and `bin` is returned for [`AudioFormat::Unknown`].
+    #[must_use]
+    pub fn recommended_extension(self) -> &'static str {
+        match self {
+            Self::Pcm8
+            | Self::Pcm16
+            | Self::Pcm24
+            | Self::Pcm32
+            | Self::PcmFloat
+            | Self::GcAdpcm
+            | Self::ImaAdpcm
+            | Self::FAdpcm => "wav",
+            Self::Vorbis => "ogg",
+            Self::Atrac9 => "at9",
+            Self::Mpeg => "mp3",
+            Self::Vag | Self::HeVag => "vag",
+            Self::Xma => "xma",
+            Self::Celt => "celt",
+            Self::Xwma => "xwma",
+            Self::Opus => "opus",
+            Self::Unknown(_) => "bin",
         }
     }
 }
 
 impl Display for AudioFormat {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        f.write_str(match self {
+        let name = match self {
             Self::Pcm8 => "PCM (8-bit, integer)",
             Self::Pcm16 => "PCM (16-bit, integer)",
             Self::Pcm24 => "PCM (24-bit, integer)",
@@ -236,50 +495,83 @@ impl Display for AudioFormat {
             Self::Vorbis => "Vorbis",
             Self::FAdpcm => "FADPCM",
             Self::Opus => "Opus",
-        })
+            Self::Unknown(flag) => {
+                return f.write_fmt(format_args!("unknown format (0x{flag:08x})"));
+            }
+        };
+
+        f.write_str(name)
     }
 }
 
+// The stream count is read from the file header before anything else can be validated,
+// so a malformed header could otherwise trigger a huge up-front allocation.
+const MAX_PREALLOCATED_STREAMS: usize = 4096;
+
+fn preallocated_streams(num_streams: NonZeroU32) -> usize {
+    (num_streams.get() as usize).min(MAX_PREALLOCATED_STREAMS)
+}
+
 fn parse_stream_headers<R: Read>(
     reader: &mut Reader<R>,
+    format: AudioFormat,
     num_streams: NonZeroU32,
     total_stream_size: NonZeroU32,
-) -> Result<Vec<StreamInfo>, HeaderError> {
-    let num_streams_usize = num_streams.get() as usize;
+    options: &ParseOptions,
+) -> Result<(Vec<StreamInfo>, u32), HeaderError> {
+    let capacity = preallocated_streams(num_streams);
 
-    let mut stream_headers = Vec::with_capacity(num_streams_usize);
-    let mut stream_offsets = Vec::with_capacity(num_streams_usize + 1);
+    let mut stream_headers = Vec::with_capacity(capacity);
+    let mut stream_offsets = Vec::with_capacity(capacity + 1);
 
     for index in 0..num_streams.get() {
         // Stream headers contain information such as sample rate (Hz) and number of channels.
         // They can also contain metadata chunks useful for decoding and encoding stream data.
         // Sometimes, flags for header fields are set to 0 while the actual values are stored in chunks.
         let mut stream_header = match reader.le_u64() {
-            Ok(n) => RawStreamHeader::from(n).parse(index),
+            Ok(n) => RawStreamHeader::from(n).parse(index, options.allow_zero_samples),
             Err(e) => Err(StreamError::new_with_source(index, StreamErrorKind::StreamInfo, e)),
         }?;
 
         if stream_header.has_chunks {
-            parse_stream_chunks(reader, &mut stream_header)
+            parse_stream_chunks(reader, &mut stream_header, options)
                 .map_err(|e| e.into_stream_err(index))?;
         }
 
+        // Some GC ADPCM sound banks omit DSP coefficients, relying on a coefficient set known ahead of time.
+        if let (AudioFormat::GcAdpcm, Some(coeffs), None) =
+            (format, options.fallback_dsp_coeffs, &stream_header.dsp_coeffs)
+        {
+            stream_header.dsp_coeffs =
+                Some(vec![coeffs; stream_header.channels.get().into()].into_boxed_slice());
+        }
+
         stream_offsets.push(stream_header.data_offset);
         stream_headers.push(stream_header);
     }
     stream_offsets.push(total_stream_size.get());
 
+    // Streams are read one after another from the start of the data section,
+    // so data written before the first stream is padding that has to be skipped.
+    let first_offset = stream_offsets[0];
+
+    if options.strict_alignment && first_offset != 0 {
+        return Err(HeaderError::new(HeaderErrorKind::FirstStreamOffset {
+            offset: first_offset,
+        }));
+    }
+
     // Only stream offsets are stored in stream headers, so they are processed to get stream lengths.
     // Stream lengths are calculated the same way as name lengths in the name table.
+    // Offsets that decrease would make stream data overlap, so they are rejected.
+
+    let mut stream_info = Vec::with_capacity(capacity);
 
-    let mut stream_info = Vec::with_capacity(num_streams_usize);
+    for ((window, header), index) in zip(stream_offsets.windows(2), stream_headers).zip(0..) {
+        let size = window[1]
+            .checked_sub(window[0])
+            .ok_or_else(|| HeaderError::new(HeaderErrorKind::StreamDataOverlap { index }))?;
 
-    for ((size, header), index) in zip(
-        stream_offsets.windows(2).map(|window| window[1] - window[0]),
-        stream_headers,
-    )
-    .zip(0..)
-    {
         stream_info.push(
             header.with_stream_size(
                 size.try_into()
@@ -288,7 +580,7 @@ fn parse_stream_headers<R: Read>(
         );
     }
 
-    Ok(stream_info)
+    Ok((stream_info, first_offset))
 }
 
 #[bitsize(64)]
@@ -308,14 +600,25 @@ struct StreamHeader {
     sample_rate: NonZeroU32,
     channels: NonZeroU8,
     data_offset: u32,
-    num_samples: NonZeroU32,
+    num_samples: Option<NonZeroU32>,
     stream_loop: Option<Loop>,
-    dsp_coeffs: Option<Box<[i16]>>,
+    dsp_coeffs: Option<Box<[[i16; 16]]>>,
     vorbis_crc32: Option<u32>,
+    vorbis_layers: Option<NonZeroU8>,
+    peak_volume: Option<u32>,
+    atrac9_config: Option<Box<[u8]>>,
+    xwma_config: Option<Box<[u8]>>,
+    opus_data_size: Option<u32>,
+    chunk_flags: Vec<u8>,
+    chunk_error: bool,
 }
 
 impl RawStreamHeader {
-    fn parse(self, stream_index: u32) -> Result<StreamHeader, StreamError> {
+    fn parse(
+        self,
+        stream_index: u32,
+        allow_zero_samples: bool,
+    ) -> Result<StreamHeader, StreamError> {
         let sample_rate = match self.sample_rate().value() {
             0 => Ok(4000),
             1 => Ok(8000),
@@ -346,11 +649,12 @@ impl RawStreamHeader {
         .try_into()
         .unwrap();
 
-        let num_samples = self
-            .num_samples()
-            .value()
-            .try_into()
-            .map_err(|_| StreamError::new(stream_index, StreamErrorKind::ZeroSamples))?;
+        // Some sound banks contain streams without samples, which are used as markers.
+        let num_samples = NonZeroU32::new(self.num_samples().value());
+
+        if num_samples.is_none() && !allow_zero_samples {
+            return Err(StreamError::new(stream_index, StreamErrorKind::ZeroSamples));
+        }
 
         // Some information (e.g. playback loops) are read from stream header chunks,
         // which happens after parsing the stream header, so their values are set to None for now.
@@ -363,6 +667,13 @@ impl RawStreamHeader {
             stream_loop: None,
             dsp_coeffs: None,
             vorbis_crc32: None,
+            vorbis_layers: None,
+            peak_volume: None,
+            atrac9_config: None,
+            xwma_config: None,
+            opus_data_size: None,
+            chunk_flags: Vec::new(),
+            chunk_error: false,
         })
     }
 }
@@ -370,114 +681,187 @@ impl RawStreamHeader {
 fn parse_stream_chunks<R: Read>(
     reader: &mut Reader<R>,
     stream: &mut StreamHeader,
+    options: &ParseOptions,
 ) -> Result<(), ChunkError> {
-    use crate::header::Loop;
-    use StreamChunkKind::*;
-
     for index in 0.. {
-        let chunk = match reader.le_u32() {
-            Ok(n) => RawStreamChunk::from(n).parse(index),
-            Err(e) => Err(ChunkError::new_with_source(index, ChunkErrorKind::Flag, e)),
-        }?;
+        let raw_chunk = reader
+            .le_u32()
+            .map(RawStreamChunk::from)
+            .map_err(ChunkError::factory(index, ChunkErrorKind::Flag))?;
 
+        let more_chunks = raw_chunk.more_chunks();
+        let size = raw_chunk.size().value();
         let start_position = reader.position();
+        stream.chunk_flags.push(raw_chunk.kind().value());
+
+        match raw_chunk.parse(index).and_then(|chunk| {
+            parse_chunk_payload(reader, stream, &chunk, index, options.parse_chunks)
+        }) {
+            Ok(()) => {}
+            // The chunk size is known even when the chunk is invalid, so the rest of the chunk can be skipped.
+            Err(_) if options.skip_invalid_chunks => stream.chunk_error = true,
+            Err(e) => return Err(e),
+        }
 
-        match chunk.kind {
-            Channels => {
-                stream.channels = reader
-                    .u8()
-                    .map_err(ChunkError::factory(index, ChunkErrorKind::ChannelCount))?
-                    .try_into()
-                    .map_err(|_| ChunkError::new(index, ChunkErrorKind::ZeroChannels))?;
-            }
-            SampleRate => {
-                stream.sample_rate = reader
-                    .le_u32()
-                    .map_err(ChunkError::factory(index, ChunkErrorKind::SampleRate))?
-                    .try_into()
-                    .map_err(|_| ChunkError::new(index, ChunkErrorKind::ZeroSampleRate))?;
-            }
-            Loop => {
-                let start = reader
-                    .le_u32()
-                    .map_err(ChunkError::factory(index, ChunkErrorKind::LoopStart))?;
+        // make sure the entire chunk has been read before continuing
+        reader
+            .advance_to_offset(start_position, size as usize)
+            .map_err(ChunkError::factory(
+                index,
+                ChunkErrorKind::WrongChunkSize {
+                    expected: size,
+                    actual: reader.position() - start_position,
+                },
+            ))?;
 
-                let end = reader
-                    .le_u32()
-                    .map_err(ChunkError::factory(index, ChunkErrorKind::LoopEnd))?;
+        if !more_chunks {
+            break;
+        }
+    }
+
+    Ok(())
+}
 
-                stream.stream_loop = Some(Loop::parse(index, start, end)?);
+#[allow(clippy::too_many_lines)]
+fn parse_chunk_payload<R: Read>(
+    reader: &mut Reader<R>,
+    stream: &mut StreamHeader,
+    chunk: &StreamChunk,
+    index: u32,
+    mode: ChunkParseMode,
+) -> Result<(), ChunkError> {
+    use crate::header::Loop;
+    use StreamChunkKind::*;
+
+    match chunk.kind {
+        Channels => {
+            stream.channels = reader
+                .u8()
+                .map_err(ChunkError::factory(index, ChunkErrorKind::ChannelCount))?
+                .try_into()
+                .map_err(|_| ChunkError::new(index, ChunkErrorKind::ZeroChannels))?;
+        }
+        SampleRate => {
+            stream.sample_rate = reader
+                .le_u32()
+                .map_err(ChunkError::factory(index, ChunkErrorKind::SampleRate))?
+                .try_into()
+                .map_err(|_| ChunkError::new(index, ChunkErrorKind::ZeroSampleRate))?;
+        }
+        // skipped by the caller, along with chunks that aren't parsed at all
+        Loop | PeakVolume if mode == ChunkParseMode::Minimal => {}
+        Loop => {
+            let start = reader
+                .le_u32()
+                .map_err(ChunkError::factory(index, ChunkErrorKind::LoopStart))?;
+
+            let end = reader
+                .le_u32()
+                .map_err(ChunkError::factory(index, ChunkErrorKind::LoopEnd))?;
+
+            let mut stream_loop = Loop::parse(index, start, end)?;
+
+            // some loop chunks also store the number of times the loop is repeated
+            if chunk.size >= 12 {
+                stream_loop.count = reader
+                    .le_u32()
+                    .map_err(ChunkError::factory(index, ChunkErrorKind::LoopCount))?
+                    .pipe(Some);
             }
-            DspCoefficients => {
-                // used for decoding and encoding GC ADPCM streams
 
-                let channels = stream.channels.get();
+            stream.stream_loop = Some(stream_loop);
+        }
+        DspCoefficients => {
+            // used for decoding and encoding GC ADPCM streams
 
-                let mut dsp_coeffs = Vec::with_capacity(channels as usize);
+            let channels = stream.channels.get();
 
-                for _ in 0..channels {
-                    let mut coeff = 0;
+            let mut dsp_coeffs = Vec::with_capacity(channels as usize);
 
-                    for _ in 0..16 {
-                        coeff += reader
-                            .be_i16()
-                            .map_err(ChunkError::factory(index, ChunkErrorKind::DspCoefficients))?;
-                    }
+            for _ in 0..channels {
+                let mut coeffs = [0; 16];
 
-                    reader
-                        .skip(14)
+                for coeff in &mut coeffs {
+                    *coeff = reader
+                        .be_i16()
                         .map_err(ChunkError::factory(index, ChunkErrorKind::DspCoefficients))?;
-
-                    dsp_coeffs.push(coeff);
                 }
 
-                stream.dsp_coeffs = Some(dsp_coeffs.into_boxed_slice());
-            }
-            VorbisSeekTable => {
-                // Vorbis is a variable bitrate codec, so seek tables are used to seek to specific times.
-                // This chunk starts with the CRC32 checksum of a Vorbis setup header.
-                // When encoding this stream, the checksum is used to recover the original setup header.
-                // The seek table is discarded because it isn't useful for stream decoding or encoding.
+                reader
+                    .skip(14)
+                    .map_err(ChunkError::factory(index, ChunkErrorKind::DspCoefficients))?;
 
-                stream.vorbis_crc32 = reader
-                    .le_u32()
-                    .map_err(ChunkError::factory(index, ChunkErrorKind::VorbisCrc32))?
-                    .pipe(Some);
+                dsp_coeffs.push(coeffs);
             }
-            VorbisIntraLayers => {
-                // Some Vorbis stream data is stored as multiple "layers" per channel.
-                // For decoding and encoding purposes, layers simply mean that more channels are present.
 
-                let layers = reader
-                    .le_u32()
-                    .map_err(ChunkError::factory(index, ChunkErrorKind::VorbisLayerCount))?;
-
-                stream.channels = layers
-                    .pipe(u8::try_from)
-                    .map_err(|_| {
-                        ChunkError::new(index, ChunkErrorKind::TooManyVorbisLayers { layers })
-                    })?
-                    .mul(stream.channels.get())
-                    .try_into()
-                    .map_err(|_| ChunkError::new(index, ChunkErrorKind::ZeroVorbisLayers))?;
-            }
-            _ => {}
+            stream.dsp_coeffs = Some(dsp_coeffs.into_boxed_slice());
         }
-
-        // make sure the entire chunk has been read before continuing
-        reader
-            .advance_to(start_position + chunk.size as usize)
-            .map_err(ChunkError::factory(
-                index,
-                ChunkErrorKind::WrongChunkSize {
-                    expected: chunk.size,
-                    actual: reader.position() - start_position,
-                },
-            ))?;
-
-        if !chunk.more_chunks {
-            break;
+        VorbisSeekTable => {
+            // Vorbis is a variable bitrate codec, so seek tables are used to seek to specific times.
+            // This chunk starts with the CRC32 checksum of a Vorbis setup header.
+            // When encoding this stream, the checksum is used to recover the original setup header.
+            // The seek table is discarded because it isn't useful for stream decoding or encoding.
+
+            stream.vorbis_crc32 = reader
+                .le_u32()
+                .map_err(ChunkError::factory(index, ChunkErrorKind::VorbisCrc32))?
+                .pipe(Some);
+        }
+        PeakVolume => {
+            // the peak amplitude of the stream, as a linear value where 1.0 is full scale
+            stream.peak_volume = reader
+                .le_u32()
+                .map_err(ChunkError::factory(index, ChunkErrorKind::PeakVolume))?
+                .pipe(Some);
         }
+        Atrac9Config => {
+            // The configuration of the ATRAC9 encoder, which is needed to wrap the stream data in an ATRAC9 file.
+            // The chunk is stored as-is, because its contents are only interpreted when encoding.
+            stream.atrac9_config = reader
+                .take_boxed(chunk.size as usize)
+                .map_err(ChunkError::factory(index, ChunkErrorKind::Atrac9Config))?
+                .pipe(Some);
+        }
+        XwmaConfig => {
+            // The format of the xWMA stream data, which is needed to wrap the stream data in an xWMA file.
+            // Like the ATRAC9 configuration, the chunk is stored as-is.
+            stream.xwma_config = reader
+                .take_boxed(chunk.size as usize)
+                .map_err(ChunkError::factory(index, ChunkErrorKind::XwmaConfig))?
+                .pipe(Some);
+        }
+        OpusDataSize => {
+            // The size of the Opus stream data, not counting the frame header in front of every packet.
+            // Packet boundaries are read from the frame headers instead, so this size isn't needed for encoding.
+            stream.opus_data_size = reader
+                .le_u32()
+                .map_err(ChunkError::factory(index, ChunkErrorKind::OpusDataSize))?
+                .pipe(Some);
+        }
+        VorbisIntraLayers => {
+            // Some Vorbis stream data is stored as multiple "layers" per channel.
+            // For decoding and encoding purposes, layers simply mean that more channels are present.
+
+            let layers = reader
+                .le_u32()
+                .map_err(ChunkError::factory(index, ChunkErrorKind::VorbisLayerCount))?;
+
+            let layers: NonZeroU8 = layers
+                .pipe(u8::try_from)
+                .map_err(|_| {
+                    ChunkError::new(index, ChunkErrorKind::TooManyVorbisLayers { layers })
+                })?
+                .try_into()
+                .map_err(|_| ChunkError::new(index, ChunkErrorKind::ZeroVorbisLayers))?;
+
+            stream.channels = layers
+                .get()
+                .mul(stream.channels.get())
+                .try_into()
+                .map_err(|_| ChunkError::new(index, ChunkErrorKind::ZeroVorbisLayers))?;
+            stream.vorbis_layers = Some(layers);
+        }
+        _ => {}
     }
 
     Ok(())
@@ -492,7 +876,6 @@ struct RawStreamChunk {
 }
 
 struct StreamChunk {
-    more_chunks: bool,
     size: u32,
     kind: StreamChunkKind,
 }
@@ -533,7 +916,6 @@ impl RawStreamChunk {
         }?;
 
         Ok(StreamChunk {
-            more_chunks: self.more_chunks(),
             size: self.size().value(),
             kind,
         })
@@ -541,29 +923,69 @@ impl RawStreamChunk {
 }
 
 /// Loop information associated with a stream.
+///
+/// Loop positions are sample indices, counted in sample frames (one sample per channel) from the start of the stream.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "LoopFields"))]
 pub struct Loop {
     start: u32,
     len: NonZeroU32,
+    count: Option<u32>,
+}
+
+// The fields of a deserialized loop, which are checked so that the end of the loop fits in a u32.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LoopFields {
+    start: u32,
+    len: NonZeroU32,
+    count: Option<u32>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<LoopFields> for Loop {
+    type Error = &'static str;
+
+    fn try_from(fields: LoopFields) -> Result<Self, Self::Error> {
+        if fields.start.checked_add(fields.len.get()).is_none() {
+            return Err("loop end was past the maximum stream size");
+        }
+
+        Ok(Self {
+            start: fields.start,
+            len: fields.len,
+            count: fields.count,
+        })
+    }
 }
 
 impl Loop {
+    // Loop chunks store the index of the last sample in the loop, so the loop ends at the sample after it.
     fn parse(index: u32, start: u32, end: u32) -> Result<Self, ChunkError> {
-        let len = NonZeroU32::new(end - start)
+        let len = end
+            .checked_add(1)
+            .and_then(|end| end.checked_sub(start))
+            .and_then(NonZeroU32::new)
             .ok_or_else(|| ChunkError::new(index, ChunkErrorKind::ZeroLengthLoop))?;
 
-        Ok(Self { start, len })
+        Ok(Self {
+            start,
+            len,
+            count: None,
+        })
     }
 
     /// Returns the starting position of the loop.
-    /// This value refers to the offset, in bytes, from the start of the stream data.
+    /// This value is the index of the first sample frame in the loop.
     #[must_use]
     pub fn start(&self) -> u32 {
         self.start
     }
 
     /// Returns the ending position of the loop.
-    /// This value refers to the offset, in bytes, from the start of the stream data.
+    /// This value is the index of the sample frame after the last one in the loop,
+    /// which is 1 more than the inclusive end stored in the sound bank.
     #[allow(clippy::missing_panics_doc)]
     #[must_use]
     pub fn end(&self) -> NonZeroU32 {
@@ -572,21 +994,58 @@ impl Loop {
             .expect("the sum of u32 and NonZeroU32 must be NonZeroU32")
     }
 
-    /// Returns the length of the loop, in bytes.
+    /// Returns the length of the loop, in sample frames.
     #[must_use]
     pub fn len(&self) -> NonZeroU32 {
         self.len
     }
+
+    /// Returns the loop as a range of sample frame indices.
+    ///
+    /// This is equivalent to `loop.start()..loop.end().get()`, and is useful for indexing into decoded samples.
+    #[must_use]
+    pub fn as_range(&self) -> Range<u32> {
+        self.start..self.end().get()
+    }
+
+    /// Returns the number of times the loop is repeated, if it is stored in the sound bank.
+    /// A value of 0 means that the loop is repeated indefinitely.
+    #[must_use]
+    pub fn count(&self) -> Option<u32> {
+        self.count
+    }
+}
+
+#[cfg(test)]
+impl Loop {
+    // `end` is exclusive, unlike the end stored in loop chunks
+    pub(crate) fn new_test(start: u32, end: u32) -> Self {
+        Self {
+            start,
+            len: NonZeroU32::new(end - start).unwrap(),
+            count: None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct StreamInfo {
     pub(crate) sample_rate: NonZeroU32,
     pub(crate) channels: NonZeroU8,
-    pub(crate) num_samples: NonZeroU32,
+    pub(crate) num_samples: Option<NonZeroU32>,
     pub(crate) stream_loop: Option<Loop>,
-    pub(crate) _dsp_coeffs: Option<Box<[i16]>>,
+    pub(crate) dsp_coeffs: Option<Box<[[i16; 16]]>>,
     pub(crate) vorbis_crc32: Option<u32>,
+    pub(crate) vorbis_layers: Option<NonZeroU8>,
+    // stored as the bits of an f32, so that stream information can be compared for equality
+    pub(crate) peak_volume: Option<u32>,
+    pub(crate) atrac9_config: Option<Box<[u8]>>,
+    pub(crate) xwma_config: Option<Box<[u8]>>,
+    // the size of the Opus packets in the stream data, in bytes
+    pub(crate) opus_data_size: Option<u32>,
+    // the type flag of every chunk in the stream header, including chunks that aren't parsed
+    pub(crate) chunk_flags: Box<[u8]>,
+    pub(crate) chunk_error: bool,
     pub(crate) size: NonZeroU32,
     pub(crate) name: Option<Box<str>>,
 }
@@ -599,38 +1058,158 @@ impl StreamHeader {
             channels: self.channels,
             num_samples: self.num_samples,
             stream_loop: self.stream_loop,
-            _dsp_coeffs: self.dsp_coeffs,
+            dsp_coeffs: self.dsp_coeffs,
             vorbis_crc32: self.vorbis_crc32,
+            vorbis_layers: self.vorbis_layers,
+            peak_volume: self.peak_volume,
+            atrac9_config: self.atrac9_config,
+            xwma_config: self.xwma_config,
+            opus_data_size: self.opus_data_size,
+            chunk_flags: self.chunk_flags.into_boxed_slice(),
+            chunk_error: self.chunk_error,
             size,
             name: None,
         }
     }
 }
 
+impl StreamInfo {
+    // Returns the duration of the stream based on its sample count.
+    // Streams without samples have a duration of 0.
+    pub(crate) fn duration(&self) -> Duration {
+        let samples = self.num_samples.map_or(0, NonZeroU32::get);
+        Duration::from_secs_f64(f64::from(samples) / f64::from(self.sample_rate.get()))
+    }
+}
+
+#[cfg(test)]
+impl StreamInfo {
+    pub(crate) fn new_test(sample_rate: u32, channels: u8, num_samples: u32, size: u32) -> Self {
+        Self {
+            sample_rate: NonZeroU32::new(sample_rate).unwrap(),
+            channels: NonZeroU8::new(channels).unwrap(),
+            num_samples: NonZeroU32::new(num_samples),
+            stream_loop: None,
+            dsp_coeffs: None,
+            vorbis_crc32: None,
+            vorbis_layers: None,
+            peak_volume: None,
+            atrac9_config: None,
+            xwma_config: None,
+            opus_data_size: None,
+            chunk_flags: Box::default(),
+            chunk_error: false,
+            size: NonZeroU32::new(size).unwrap(),
+            name: None,
+        }
+    }
+}
+
+// Stream data offsets are stored as multiples of 32 relative to the start of the data section,
+// so every stream is 32-byte aligned as long as the data section itself is.
+fn check_data_alignment<R: Read>(reader: &Reader<R>, expected: usize) -> Result<(), HeaderError> {
+    let actual = reader.position();
+
+    if actual != expected {
+        return Err(HeaderError::new(HeaderErrorKind::DataSectionOffset {
+            expected,
+            actual,
+        }));
+    }
+
+    if actual % 32 != 0 {
+        return Err(HeaderError::new(HeaderErrorKind::MisalignedStreamData {
+            offset: actual,
+        }));
+    }
+
+    Ok(())
+}
+
+// Reads the name of every stream.
+// If `lossy` is set, invalid UTF-8 sequences are replaced with U+FFFD instead of failing to parse.
 fn read_stream_names<R: Read>(
     reader: &mut Reader<R>,
     name_offsets: &[u32],
+    lossy: bool,
     stream_info: &mut [StreamInfo],
 ) -> Result<(), NameError> {
     for (name_len, index) in name_offsets.windows(2).map(|window| window[1] - window[0]).zip(0..) {
-        stream_info[index as usize].name = reader
+        let bytes = reader
             .take(name_len as usize)
-            .map_err(NameError::read_factory(index, NameErrorKind::Name))?
-            .pipe_as_ref(CStr::from_bytes_until_nul)
-            .map_err(NameError::cstr_factory(index))?
-            .to_str()
-            .map_err(NameError::utf8_factory(index))?
-            .pipe(Some)
-            .map(Into::into);
+            .map_err(NameError::read_factory(index, NameErrorKind::Name))?;
+        let name = CStr::from_bytes_until_nul(&bytes).map_err(NameError::cstr_factory(index))?;
+
+        let name = if lossy {
+            name.to_string_lossy()
+        } else {
+            name.to_str().map_err(NameError::utf8_factory(index))?.into()
+        };
+
+        stream_info[index as usize].name = Some(name.into());
     }
 
     Ok(())
 }
 
+fn skip_stream_names<R: Read>(
+    reader: &mut Reader<R>,
+    name_offsets: &[u32],
+) -> Result<(), NameError> {
+    for (name_len, index) in name_offsets.windows(2).map(|window| window[1] - window[0]).zip(0..) {
+        reader
+            .skip(name_len as usize)
+            .map_err(NameError::read_factory(index, NameErrorKind::Name))?;
+    }
+
+    Ok(())
+}
+
+// Parses the file header of a sound bank stored in memory, returning stream names that borrow from `data`.
+// Names are validated the same way as in `read_stream_names`, but without allocating each name.
+pub(crate) fn borrow_stream_names(data: &[u8]) -> Result<Box<[Option<&str>]>, HeaderError> {
+    let mut reader = Reader::new(data);
+    let header = Header::parse_with(
+        &mut reader,
+        &ParseOptions {
+            skip_names: true,
+            ..Default::default()
+        },
+    )?;
+
+    let Some(table) = header.skipped_names else {
+        return Ok(vec![None; header.stream_info.len()].into_boxed_slice());
+    };
+
+    let mut position = table.start;
+
+    table
+        .offsets
+        .windows(2)
+        .map(|window| (window[1] - window[0]) as usize)
+        .zip(0..)
+        .map(|(name_len, index)| {
+            // these bytes were already read while parsing the file header
+            let name = &data[position..position + name_len];
+            position += name_len;
+
+            CStr::from_bytes_until_nul(name)
+                .map_err(NameError::cstr_factory(index))?
+                .to_str()
+                .map_err(NameError::utf8_factory(index))?
+                .pipe(Some)
+                .pipe(Ok)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::error::{ChunkErrorKind::*, HeaderErrorKind::*, StreamErrorKind::*};
-    use super::{Header, RawStreamChunk, RawStreamHeader, StreamHeader, FSB5_MAGIC};
+    use super::{
+        borrow_stream_names, ChunkParseMode, Header, ParseOptions, RawStreamChunk, RawStreamHeader,
+        StreamHeader, FSB5_MAGIC,
+    };
     use crate::read::Reader;
     use std::num::{NonZeroU32, NonZeroU8};
 
@@ -738,6 +1317,520 @@ mod test {
         );
     }
 
+    #[test]
+    fn convert_raw_audio_format() {
+        for flag in 1..=17 {
+            let format = super::AudioFormat::from_raw(flag).unwrap();
+            assert_eq!(format.as_raw(), flag);
+        }
+
+        assert_eq!(super::AudioFormat::from_raw(15), Some(super::AudioFormat::Vorbis));
+        assert_eq!(super::AudioFormat::from_raw(0), None);
+        assert_eq!(super::AudioFormat::from_raw(18), None);
+        assert_eq!(super::AudioFormat::Unknown(0x20).as_raw(), 0x20);
+    }
+
+    #[test]
+    fn recommend_extensions() {
+        use super::AudioFormat;
+
+        assert_eq!(AudioFormat::Pcm24.recommended_extension(), "wav");
+        assert_eq!(AudioFormat::GcAdpcm.recommended_extension(), "wav");
+        assert_eq!(AudioFormat::Vorbis.recommended_extension(), "ogg");
+        assert_eq!(AudioFormat::Mpeg.recommended_extension(), "mp3");
+        assert_eq!(AudioFormat::Opus.recommended_extension(), "opus");
+        assert_eq!(AudioFormat::Unknown(0x20).recommended_extension(), "bin");
+
+        assert_eq!(AudioFormat::FAdpcm.output_mime_type(), "audio/wav");
+        assert_eq!(AudioFormat::Mpeg.output_mime_type(), "audio/mpeg");
+        assert_eq!(AudioFormat::Opus.output_mime_type(), "audio/ogg");
+        assert_eq!(AudioFormat::Xma.output_mime_type(), "application/octet-stream");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_loop() {
+        use serde::{de::value::MapDeserializer, Deserialize};
+
+        let deserialize = |start: u32, len: u32| {
+            let fields = [("start", start), ("len", len)];
+            super::Loop::deserialize(MapDeserializer::<_, serde::de::value::Error>::new(
+                fields.into_iter(),
+            ))
+        };
+
+        assert_eq!(deserialize(2, 8).unwrap(), super::Loop::new_test(2, 10));
+        assert!(deserialize(2, 0).is_err());
+        assert!(deserialize(u32::MAX, 1).is_err());
+    }
+
+    #[test]
+    fn read_unknown_audio_format_as_raw() {
+        let options = ParseOptions {
+            unknown_format_as_raw: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            super::AudioFormat::parse(0x20, &options).unwrap(),
+            super::AudioFormat::Unknown(0x20)
+        );
+
+        let data = b"FSB5\x01\x00\x00\x000000000000000000\x20\x00\x00\x00";
+        let mut reader = Reader::new(data.as_slice());
+        assert!(Header::parse_with(&mut reader, &options).is_err_and(|e| e.kind() == EncodingFlags));
+    }
+
+    #[test]
+    fn check_stream_data_alignment() {
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34);
+
+        let options = ParseOptions {
+            strict_alignment: true,
+            ..Default::default()
+        };
+
+        let misaligned_data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf
+        };
+        let mut reader = Reader::new(misaligned_data.as_slice());
+        assert!(Header::parse(&mut reader).is_ok());
+        reader = Reader::new(misaligned_data.as_slice());
+        assert!(Header::parse_with(&mut reader, &options)
+            .is_err_and(|e| e.kind() == MisalignedStreamData { offset: 68 }));
+
+        let aligned_data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf[12] = 0x24;
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&[0; 28]);
+            buf
+        };
+        reader = Reader::new(aligned_data.as_slice());
+        assert!(Header::parse_with(&mut reader, &options).is_ok());
+
+        let offset_data = {
+            let mut buf = aligned_data.clone();
+            buf[20] = 0x40;
+            buf[60] |= 0x80;
+            buf.extend_from_slice(&[0; 64]);
+            buf
+        };
+        reader = Reader::new(offset_data.as_slice());
+        assert!(Header::parse(&mut reader).is_ok_and(|header| header.data_offset == 128));
+        assert_eq!(reader.position(), 128);
+        reader = Reader::new(offset_data.as_slice());
+        assert!(Header::parse_with(&mut reader, &options)
+            .is_err_and(|e| e.kind() == FirstStreamOffset { offset: 32 }));
+    }
+
+    #[test]
+    fn reject_overlapping_stream_data() {
+        // 2 streams
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x02\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x00\x40\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34);
+
+        // the first stream starts after the second stream
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&(STREAM_HEADER | (1 << 7)).to_le_bytes());
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&[0; 64]);
+            buf
+        };
+        let mut reader = Reader::new(data.as_slice());
+        assert!(
+            Header::parse(&mut reader).is_err_and(|e| e.kind() == StreamDataOverlap { index: 0 })
+        );
+
+        // the second stream starts past the end of the stream data section
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&(STREAM_HEADER | (3 << 7)).to_le_bytes());
+            buf.extend_from_slice(&[0; 128]);
+            buf
+        };
+        reader = Reader::new(data.as_slice());
+        assert!(
+            Header::parse(&mut reader).is_err_and(|e| e.kind() == StreamDataOverlap { index: 1 })
+        );
+    }
+
+    #[test]
+    fn check_stream_headers_size() {
+        // stream headers size of 4 bytes
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x04\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&[0; 32]);
+            buf
+        };
+        let mut reader = Reader::new(data.as_slice());
+        assert!(Header::parse(&mut reader).is_err_and(|e| e.kind()
+            == WrongStreamHeadersSize {
+                expected: 64,
+                actual: 68
+            }));
+    }
+
+    #[test]
+    fn check_name_table_size() {
+        // name table of 8 bytes
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x08\x00\x00\x00\x08\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34);
+
+        // the name offset doesn't account for the size of the name offsets, so the name is read past the table
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(b"\x00\x00\x00\x00abc\x00defg");
+            buf.extend_from_slice(&[0; 32]);
+            buf
+        };
+        let mut reader = Reader::new(data.as_slice());
+        assert!(Header::parse(&mut reader).is_err_and(|e| e.kind()
+            == WrongNameTableSize {
+                expected: 76,
+                actual: 80
+            }));
+    }
+
+    #[test]
+    fn borrow_names_from_slice() {
+        // 2 streams, name table of 16 bytes
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x02\x00\x00\x00\x10\x00\x00\x00\x10\x00\x00\x00\x40\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            // data offset 32
+            buf.extend_from_slice(&(STREAM_HEADER | (1 << 7)).to_le_bytes());
+            buf.extend_from_slice(b"\x08\x00\x00\x00\x0c\x00\x00\x00ab\x00\x00cd\x00\x00");
+            buf.extend_from_slice(&[0; 64]);
+            buf
+        };
+
+        let names = borrow_stream_names(&data).unwrap();
+        assert_eq!(*names, [Some("ab"), Some("cd")]);
+        assert_eq!(names[0].unwrap().as_ptr(), data[84..].as_ptr());
+
+        let mut reader = Reader::new(data.as_slice());
+        let header = Header::parse(&mut reader).unwrap();
+        assert_eq!(header.stream_info[1].name.as_deref(), Some("cd"));
+    }
+
+    #[test]
+    fn read_lossy_names() {
+        // 1 stream, name table of 8 bytes
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x08\x00\x00\x00\x08\x00\x00\x00\x40\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 32
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34) | (1 << 7);
+
+        // a stray Shift-JIS byte
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(b"\x04\x00\x00\x00a\x82b\x00");
+            buf.extend_from_slice(&[0; 64]);
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        assert!(Header::parse(&mut reader).is_err_and(|e| e.kind() == NameTable));
+
+        let options = ParseOptions {
+            lossy_names: true,
+            ..Default::default()
+        };
+        let mut reader = Reader::new(data.as_slice());
+        let header = Header::parse_with(&mut reader, &options).unwrap();
+        assert_eq!(header.stream_info[0].name.as_deref(), Some("a\u{FFFD}b"));
+    }
+
+    #[test]
+    fn capture_raw_header() {
+        // 1 stream, name table of 8 bytes
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x08\x00\x00\x00\x08\x00\x00\x00\x40\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 32
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34) | (1 << 7);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(b"\x04\x00\x00\x00ab\x00\x00");
+            buf.extend_from_slice(&[0; 64]);
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        assert!(Header::parse(&mut reader).is_ok_and(|header| header.raw.is_none()));
+
+        let options = ParseOptions {
+            capture_raw_header: true,
+            ..Default::default()
+        };
+        reader = Reader::new(data.as_slice());
+        let header = Header::parse_with(&mut reader, &options).unwrap();
+        // padding between the name table and the first stream is not included
+        assert_eq!(header.raw.as_deref(), Some(&data[..76]));
+        assert_eq!(reader.position(), 108);
+    }
+
+    #[test]
+    fn apply_fallback_dsp_coefficients() {
+        // GC ADPCM format, 1 stream
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x06\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 2 channels, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 5) | (1 << 34);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        let header = Header::parse(&mut reader).unwrap();
+        assert_eq!(header.stream_info[0].dsp_coeffs, None);
+
+        let coeffs = [
+            1, -2, 3, -4, 5, -6, 7, -8, 9, -10, 11, -12, 13, -14, 15, -16,
+        ];
+        let options = ParseOptions {
+            fallback_dsp_coeffs: Some(coeffs),
+            ..Default::default()
+        };
+
+        reader = Reader::new(data.as_slice());
+        let header = Header::parse_with(&mut reader, &options).unwrap();
+        assert_eq!(
+            header.stream_info[0].dsp_coeffs.as_deref(),
+            Some([coeffs, coeffs].as_slice())
+        );
+    }
+
+    #[test]
+    fn skip_loop_chunk_in_minimal_mode() {
+        // 1 stream, stream headers are 20 bytes long
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x14\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // has chunks, 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = 1 | (8 << 1) | (1 << 34);
+        // 8-byte loop chunk, no more chunks
+        const LOOP_CHUNK: u32 = (8 << 1) | (3 << 25);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&LOOP_CHUNK.to_le_bytes());
+            buf.extend_from_slice(&2u32.to_le_bytes());
+            buf.extend_from_slice(&10u32.to_le_bytes());
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        let header = Header::parse(&mut reader).unwrap();
+        assert_eq!(header.stream_info[0].stream_loop, Some(super::Loop::new_test(2, 11)));
+        assert_eq!(header.stream_info[0].stream_loop.unwrap().count(), None);
+
+        let options = ParseOptions {
+            parse_chunks: ChunkParseMode::Minimal,
+            ..Default::default()
+        };
+
+        reader = Reader::new(data.as_slice());
+        let header = Header::parse_with(&mut reader, &options).unwrap();
+        assert_eq!(header.stream_info[0].stream_loop, None);
+        assert_eq!(reader.position(), data.len());
+    }
+
+    #[test]
+    fn reject_undersized_chunk() {
+        // 1 stream, stream headers are 20 bytes long
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x14\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // has chunks, 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = 1 | (8 << 1) | (1 << 34);
+        // loop chunk declared as 4 bytes, even though the loop start and end take 8 bytes
+        const LOOP_CHUNK: u32 = (4 << 1) | (3 << 25);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&LOOP_CHUNK.to_le_bytes());
+            buf.extend_from_slice(&2u32.to_le_bytes());
+            buf.extend_from_slice(&10u32.to_le_bytes());
+            buf.extend_from_slice(&[0; 32]);
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        assert!(
+            Header::parse(&mut reader).is_err_and(|e| e.is_chunk_err_kind(WrongChunkSize {
+                expected: 4,
+                actual: 8
+            }))
+        );
+    }
+
+    #[test]
+    fn skip_invalid_chunk() {
+        // 1 stream, stream headers are 24 bytes long
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x18\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // has chunks, 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = 1 | (8 << 1) | (1 << 34);
+        // 4-byte chunk of unknown type, followed by more chunks
+        const INVALID_CHUNK: u32 = 1 | (4 << 1) | (5 << 25);
+        // 4-byte sample rate chunk, no more chunks
+        const SAMPLE_RATE_CHUNK: u32 = (4 << 1) | (2 << 25);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&INVALID_CHUNK.to_le_bytes());
+            buf.extend_from_slice(&[0; 4]);
+            buf.extend_from_slice(&SAMPLE_RATE_CHUNK.to_le_bytes());
+            buf.extend_from_slice(&12345u32.to_le_bytes());
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        assert!(
+            Header::parse(&mut reader).is_err_and(|e| e.is_chunk_err_kind(UnknownType { flag: 5 }))
+        );
+
+        let options = ParseOptions {
+            skip_invalid_chunks: true,
+            ..Default::default()
+        };
+
+        reader = Reader::new(data.as_slice());
+        let header = Header::parse_with(&mut reader, &options).unwrap();
+        assert!(header.stream_info[0].chunk_error);
+        assert_eq!(header.stream_info[0].sample_rate.get(), 12345);
+    }
+
+    #[test]
+    fn read_loop_count() {
+        // 1 stream, stream headers are 24 bytes long
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x18\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // has chunks, 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = 1 | (8 << 1) | (1 << 34);
+        // 12-byte loop chunk, no more chunks
+        const LOOP_CHUNK: u32 = (12 << 1) | (3 << 25);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&LOOP_CHUNK.to_le_bytes());
+            buf.extend_from_slice(&2u32.to_le_bytes());
+            buf.extend_from_slice(&10u32.to_le_bytes());
+            buf.extend_from_slice(&3u32.to_le_bytes());
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        let header = Header::parse(&mut reader).unwrap();
+        let stream_loop = header.stream_info[0].stream_loop.unwrap();
+        // the stored end is the last sample in the loop
+        assert_eq!((stream_loop.start(), stream_loop.end().get()), (2, 11));
+        assert_eq!(stream_loop.as_range(), 2..11);
+        assert_eq!(stream_loop.len().get(), 9);
+        assert_eq!(stream_loop.count(), Some(3));
+    }
+
+    #[test]
+    fn read_peak_volume() {
+        // 1 stream, stream headers are 16 bytes long
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // has chunks, 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = 1 | (8 << 1) | (1 << 34);
+        // 4-byte peak volume chunk, no more chunks
+        const PEAK_VOLUME_CHUNK: u32 = (4 << 1) | (13 << 25);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&PEAK_VOLUME_CHUNK.to_le_bytes());
+            buf.extend_from_slice(&0.5f32.to_le_bytes());
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        let header = Header::parse(&mut reader).unwrap();
+        assert_eq!(header.stream_info[0].peak_volume.map(f32::from_bits), Some(0.5));
+
+        let options = ParseOptions {
+            parse_chunks: ChunkParseMode::Minimal,
+            ..Default::default()
+        };
+        let mut reader = Reader::new(data.as_slice());
+        let header = Header::parse_with(&mut reader, &options).unwrap();
+        assert_eq!(header.stream_info[0].peak_volume, None);
+        // skipped chunks are still recorded
+        assert_eq!(&*header.stream_info[0].chunk_flags, [13]);
+    }
+
+    #[test]
+    fn read_atrac9_config() {
+        // 1 stream, stream headers are 24 bytes long
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x18\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // has chunks, 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = 1 | (8 << 1) | (1 << 34);
+        // 12-byte ATRAC9 configuration chunk, no more chunks
+        const ATRAC9_CONFIG_CHUNK: u32 = (12 << 1) | (9 << 25);
+        const CONFIG: [u8; 12] = [0, 0, 0, 0, 0xFE, 0x74, 0x1F, 0xF0, 0, 0, 0, 0];
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&ATRAC9_CONFIG_CHUNK.to_le_bytes());
+            buf.extend_from_slice(&CONFIG);
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        let header = Header::parse(&mut reader).unwrap();
+        assert_eq!(
+            header.stream_info[0].atrac9_config.as_deref(),
+            Some(CONFIG.as_slice())
+        );
+    }
+
+    #[test]
+    fn read_opus_data_size() {
+        // 1 stream, stream headers are 16 bytes long
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x11\x00\x00\x0000000000000000000000000000000000";
+        // has chunks, 48000 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = 1 | (9 << 1) | (1 << 34);
+        // 4-byte Opus data size chunk, no more chunks
+        const OPUS_DATA_SIZE_CHUNK: u32 = (4 << 1) | (15 << 25);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&OPUS_DATA_SIZE_CHUNK.to_le_bytes());
+            buf.extend_from_slice(&28u32.to_le_bytes());
+            buf
+        };
+
+        let mut reader = Reader::new(data.as_slice());
+        let header = Header::parse(&mut reader).unwrap();
+        assert_eq!(header.format, super::AudioFormat::Opus);
+        assert_eq!(header.stream_info[0].opus_data_size, Some(28));
+    }
+
     #[test]
     fn read_encoding_flags() {
         let mut reader;
@@ -833,15 +1926,17 @@ mod test {
         let data = 0b011010000101100111100000001011_111001101101001101000100110_11_1110_0;
         let mode = RawStreamHeader::from(data);
         assert!(mode
-            .parse(0)
+            .parse(0, false)
             .is_err_and(|e| e.kind() == UnknownSampleRate { flag: 0b1110 }));
 
         let data = 0b000000000000000000000000000000_111001101101001101000100110_11_0000_0;
         let mode = RawStreamHeader::from(data);
-        assert!(mode.parse(0).is_err_and(|e| e.kind() == ZeroSamples));
+        assert!(mode.parse(0, false).is_err_and(|e| e.kind() == ZeroSamples));
+        let mode = RawStreamHeader::from(data).parse(0, true).unwrap();
+        assert_eq!(mode.num_samples, None);
 
         let data = 0b000000000000000000000000000001_000000000000000000000000001_01_1000_0;
-        let mode = RawStreamHeader::from(data).parse(0).unwrap();
+        let mode = RawStreamHeader::from(data).parse(0, false).unwrap();
         assert_eq!(
             mode,
             StreamHeader {
@@ -849,10 +1944,17 @@ mod test {
                 sample_rate: NonZeroU32::new(44100).unwrap(),
                 channels: NonZeroU8::new(2).unwrap(),
                 data_offset: 32,
-                num_samples: NonZeroU32::new(1).unwrap(),
+                num_samples: NonZeroU32::new(1),
                 stream_loop: None,
                 dsp_coeffs: None,
                 vorbis_crc32: None,
+                vorbis_layers: None,
+                peak_volume: None,
+                atrac9_config: None,
+                xwma_config: None,
+                opus_data_size: None,
+                chunk_flags: Vec::new(),
+                chunk_error: false,
             }
         );
     }
@@ -29,6 +29,9 @@
 //!
 //!     // iterate over streams
 //!     for (index, stream) in bank.into_iter().enumerate() {
+//!         // stop if the stream data could not be read
+//!         let stream = stream?;
+//!
 //!         // check stream name
 //!         let file_name = if let Some(name) = stream.name() {
 //!             format!("{name}.ogg")
@@ -45,22 +48,60 @@
 //! }
 //! ```
 //!
+//! Iterating with `into_iter()` reads the data of every stream into memory before it is encoded.
+//! For large streams (e.g. long Vorbis music tracks), use [`Bank::read_streams`] instead:
+//! each [`LazyStream`] is encoded while its data is read from the sound bank (e.g. one Vorbis packet at a time).
+//! Some formats and encode options still hold a whole stream in memory; see [`LazyStream`] for details.
+//!
 //! ## Supported formats
 //!
 //! `fsbex` supports encoding stream data for the following formats:
 //! - PCM (8, 16, 24, 32-bit integer)
 //! - PCM (32-bit float)
 //! - Vorbis
+//! - GC ADPCM (decoded to 16-bit PCM)
+//! - IMA ADPCM (decoded to 16-bit PCM)
+//! - FADPCM (decoded to 16-bit PCM)
+//! - ATRAC9 (wrapped in an `.at9` file without decoding)
+//! - MPEG (copied to an `.mp3` file without decoding)
+//! - xWMA (wrapped in an `.xwma` file without decoding)
+//! - Opus (wrapped in an Ogg Opus (`.opus`) file without decoding)
+//!
+//! ## Optional features
+//!
+//! - `loudness`: measures the integrated loudness of streams with `Stream::measure_lufs`
+//! - `metrics`: records per-stream encoding time and output size with `Bank::extract_with_metrics`
+//! - `rayon`: reads streams in parallel with `Bank::par_read_streams`
+//! - `resample`: resamples streams to a fixed sample rate during encoding with `EncodeOptions::resample_to`
+//! - `serde`: implements `Serialize` and `Deserialize` for `AudioFormat`, `Loop`, `BankManifest`, and `StreamManifest`
+//! - `tokio`: parses sound banks from asynchronous readers with `AsyncBank`
 
+#[cfg(feature = "tokio")]
+mod async_bank;
 mod bank;
+mod batch;
 pub mod encode;
 mod header;
+mod manifest;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod name;
+mod probe;
 mod read;
 mod stream;
+pub mod wav;
 
-pub use bank::{Bank, DecodeError, LazyStreamError};
-pub use header::{AudioFormat, Loop};
-pub use stream::{LazyStream, Stream, StreamIntoIter};
+#[cfg(feature = "tokio")]
+pub use async_bank::AsyncBank;
+pub use bank::{Bank, BankBuilder, DecodeError, LazyStreamError};
+pub use batch::BatchExtractor;
+pub use header::{AudioFormat, ChunkParseMode, Loop};
+pub use manifest::{BankManifest, StreamManifest};
+#[cfg(feature = "metrics")]
+pub use metrics::StreamMetric;
+pub use name::sanitize_name;
+pub use probe::ProbeResult;
+pub use stream::{LazyStream, PcmReader, Stream, StreamIntoIter, StreamIter};
 
 // Decoding and encoding involves casting values from u32 to usize.
 // To ensure correct conversions, only compilation targets where usize is at least 32 bits are allowed.
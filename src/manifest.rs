@@ -0,0 +1,165 @@
+use crate::header::{AudioFormat, Header, Loop, StreamInfo};
+use std::num::{NonZeroU32, NonZeroU8};
+
+/// A description of a sound bank and its streams, without any stream data.
+///
+/// This type is returned from [`Bank::manifest`].
+/// It owns all of its data, so it can be kept around after the sound bank is consumed.
+/// With the `serde` feature, it can be serialized to formats like JSON to catalog sound banks.
+///
+/// [`Bank::manifest`]: crate::Bank::manifest
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BankManifest {
+    format: AudioFormat,
+    version: u32,
+    flags: u32,
+    streams: Box<[StreamManifest]>,
+}
+
+impl BankManifest {
+    pub(crate) fn new(header: &Header) -> Self {
+        Self {
+            format: header.format,
+            version: header.version,
+            flags: header.flags,
+            streams: header
+                .stream_info
+                .iter()
+                .zip(0..)
+                .map(|(info, index)| StreamManifest::new(index, info))
+                .collect(),
+        }
+    }
+
+    /// Returns the audio format of streams in the sound bank.
+    #[must_use]
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    /// Returns the minor version of the FSB5 file format used by the sound bank.
+    #[must_use]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns the encoding flags stored in the sound bank's file header.
+    #[must_use]
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Returns descriptions of all streams in the sound bank, in order.
+    #[must_use]
+    pub fn streams(&self) -> &[StreamManifest] {
+        &self.streams
+    }
+}
+
+/// A description of a single stream within a [`BankManifest`].
+///
+/// Comment chunks are not parsed from stream headers, so stream comments are not included.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamManifest {
+    index: u32,
+    sample_rate: NonZeroU32,
+    channels: NonZeroU8,
+    sample_count: Option<NonZeroU32>,
+    loop_info: Option<Loop>,
+    // stored as the bits of an f32, so that manifests can be compared for equality
+    #[cfg_attr(feature = "serde", serde(with = "peak_volume_bits"))]
+    peak_volume: Option<u32>,
+    size: NonZeroU32,
+    name: Option<Box<str>>,
+}
+
+impl StreamManifest {
+    fn new(index: u32, info: &StreamInfo) -> Self {
+        Self {
+            index,
+            sample_rate: info.sample_rate,
+            channels: info.channels,
+            sample_count: info.num_samples,
+            loop_info: info.stream_loop,
+            peak_volume: info.peak_volume,
+            size: info.size,
+            name: info.name.clone(),
+        }
+    }
+
+    /// Returns the index of the stream within the sound bank.
+    #[must_use]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the sample rate (Hz) of the stream.
+    #[must_use]
+    pub fn sample_rate(&self) -> NonZeroU32 {
+        self.sample_rate
+    }
+
+    /// Returns the number of channels in the stream.
+    #[must_use]
+    pub fn channels(&self) -> NonZeroU8 {
+        self.channels
+    }
+
+    /// Returns the number of samples in the stream.
+    /// `None` is returned for streams without samples, which are only parsed with [`BankBuilder::allow_zero_samples`].
+    ///
+    /// [`BankBuilder::allow_zero_samples`]: crate::BankBuilder::allow_zero_samples
+    #[must_use]
+    pub fn sample_count(&self) -> Option<NonZeroU32> {
+        self.sample_count
+    }
+
+    /// Returns loop information, if it exists.
+    #[must_use]
+    pub fn loop_info(&self) -> Option<Loop> {
+        self.loop_info
+    }
+
+    /// Returns the peak amplitude of the stream stored in the sound bank, if it exists.
+    ///
+    /// The value is linear, where 1.0 is full scale.
+    #[must_use]
+    pub fn peak_volume(&self) -> Option<f32> {
+        self.peak_volume.map(f32::from_bits)
+    }
+
+    /// Returns the size of the stream data, in bytes.
+    #[must_use]
+    pub fn size(&self) -> NonZeroU32 {
+        self.size
+    }
+
+    /// Returns the name of the stream, if it exists.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+// Peak volumes are serialized as floats rather than as their bits.
+#[cfg(feature = "serde")]
+mod peak_volume_bits {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    // `serde(with)` passes fields by reference
+    #[allow(clippy::ref_option, clippy::trivially_copy_pass_by_ref)]
+    pub(super) fn serialize<S: Serializer>(
+        bits: &Option<u32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        bits.map(f32::from_bits).serialize(serializer)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u32>, D::Error> {
+        Option::<f32>::deserialize(deserializer).map(|value| value.map(f32::to_bits))
+    }
+}
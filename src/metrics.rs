@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+/// Timing and size information recorded while encoding a single stream.
+///
+/// This type is returned from [`Bank::extract_with_metrics`].
+///
+/// [`Bank::extract_with_metrics`]: crate::Bank::extract_with_metrics
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StreamMetric {
+    index: u32,
+    duration: Duration,
+    bytes: u64,
+}
+
+impl StreamMetric {
+    pub(crate) fn new(index: u32, duration: Duration, bytes: u64) -> Self {
+        Self {
+            index,
+            duration,
+            bytes,
+        }
+    }
+
+    /// Returns the index of the stream within the sound bank.
+    #[must_use]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the time spent reading, decoding, and encoding the stream.
+    /// This includes time spent writing to the writer.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the number of bytes written to the writer.
+    #[must_use]
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+
+/// Converts a stream name into a string that can safely be used as a file name.
+///
+/// Stream names are arbitrary strings, so they can contain path separators or characters that are illegal in file names.
+/// Using them directly as file names can create unintended subdirectories or fail entirely.
+/// This function replaces characters that are illegal on Windows or Unix file systems (including control characters) with `_`,
+/// removes trailing dots and spaces (which Windows ignores), and escapes names reserved by Windows (e.g. `CON`).
+///
+/// Exactly these changes are made, in order:
+///
+/// | Input | Output |
+/// | --- | --- |
+/// | `<`, `>`, `:`, `"`, `/`, `\`, `\|`, `?`, `*` | `_` |
+/// | control characters (e.g. `\t`, `\n`, `\0`) | `_` |
+/// | trailing dots and spaces | removed |
+/// | empty result (e.g. `..`) | `_` |
+/// | `CON`, `PRN`, `AUX`, `NUL`, `COM1`-`COM9`, `LPT1`-`LPT9` (any case, with or without an extension) | `_` appended |
+///
+/// Since `/`, `\`, and trailing dots are replaced or removed, the result can't be `.`, `..`, or contain a path separator,
+/// so it always names a file within a directory.
+///
+/// # Examples
+///
+/// ```
+/// use fsbex::sanitize_name;
+///
+/// assert_eq!(sanitize_name("music/level_1"), "music_level_1");
+/// assert_eq!(sanitize_name("CON"), "CON_");
+/// ```
+#[must_use]
+pub fn sanitize_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+
+    if sanitized.is_empty() {
+        return String::from("_");
+    }
+
+    if is_reserved_name(&sanitized) {
+        sanitized.push('_');
+    }
+
+    sanitized
+}
+
+// Sanitizes a stream name, borrowing it if it is already safe to use as a file name.
+// Streams without a name are named after their index instead.
+pub(crate) fn safe_file_stem(name: Option<&str>, index: u32) -> Cow<'_, str> {
+    match name {
+        Some(name) => match sanitize_name(name) {
+            sanitized if sanitized == name => Cow::Borrowed(name),
+            sanitized => Cow::Owned(sanitized),
+        },
+        None => Cow::Owned(unnamed_file_stem(index)),
+    }
+}
+
+// The file name (without an extension) used for a stream that has no name.
+pub(crate) fn unnamed_file_stem(index: u32) -> String {
+    format!("stream_{index}")
+}
+
+// Windows reserves these device names, even when followed by an extension (e.g. `NUL.txt`).
+fn is_reserved_name(name: &str) -> bool {
+    const RESERVED_NAMES: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        return true;
+    }
+
+    match stem.as_bytes() {
+        [a, b, c, b'1'..=b'9'] => {
+            let prefix = [*a, *b, *c];
+            prefix.eq_ignore_ascii_case(b"COM") || prefix.eq_ignore_ascii_case(b"LPT")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{safe_file_stem, sanitize_name};
+    use std::borrow::Cow;
+
+    #[test]
+    fn replace_illegal_characters() {
+        assert_eq!(sanitize_name("foo/bar"), "foo_bar");
+        assert_eq!(sanitize_name("a<b>c:d\"e\\f|g?h*i"), "a_b_c_d_e_f_g_h_i");
+        assert_eq!(sanitize_name("tab\there"), "tab_here");
+        assert_eq!(sanitize_name("music_01"), "music_01");
+    }
+
+    #[test]
+    fn handle_special_names() {
+        assert_eq!(sanitize_name(""), "_");
+        assert_eq!(sanitize_name(".."), "_");
+        assert_eq!(sanitize_name("name. "), "name");
+        assert_eq!(sanitize_name("con"), "con_");
+        assert_eq!(sanitize_name("nul.txt"), "nul.txt_");
+        assert_eq!(sanitize_name("COM1"), "COM1_");
+        assert_eq!(sanitize_name("COM10"), "COM10");
+    }
+
+    #[test]
+    fn borrow_safe_names() {
+        assert!(matches!(
+            safe_file_stem(Some("music_01"), 0),
+            Cow::Borrowed("music_01")
+        ));
+        assert!(
+            matches!(safe_file_stem(Some("../music"), 0), Cow::Owned(name) if name == ".._music")
+        );
+        assert!(matches!(safe_file_stem(None, 3), Cow::Owned(name) if name == "stream_3"));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::header::AudioFormat;
+
+/// A summary of whether the first stream of a sound bank looks decodable.
+///
+/// This type is returned from [`Bank::probe_first_stream`].
+///
+/// [`Bank::probe_first_stream`]: crate::Bank::probe_first_stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ProbeResult {
+    format: AudioFormat,
+    encodable: bool,
+    bytes_read: usize,
+    setup_header_found: Option<bool>,
+}
+
+impl ProbeResult {
+    pub(crate) fn new(
+        format: AudioFormat,
+        encodable: bool,
+        bytes_read: usize,
+        setup_header_found: Option<bool>,
+    ) -> Self {
+        Self {
+            format,
+            encodable,
+            bytes_read,
+            setup_header_found,
+        }
+    }
+
+    /// Returns the audio format of the stream.
+    #[must_use]
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    /// Returns the number of bytes of stream data that were read.
+    #[must_use]
+    pub fn bytes_read(&self) -> usize {
+        self.bytes_read
+    }
+
+    /// Returns whether the Vorbis headers needed to decode the stream could be reconstructed,
+    /// which requires the CRC32 of its setup header to be found in the lookup table or among user-supplied setup headers
+    /// (see [`Bank::probe_first_stream_with`]).
+    ///
+    /// `None` is returned for streams in formats other than Vorbis.
+    ///
+    /// [`Bank::probe_first_stream_with`]: crate::Bank::probe_first_stream_with
+    #[must_use]
+    pub fn setup_header_found(&self) -> Option<bool> {
+        self.setup_header_found
+    }
+
+    /// Returns whether the stream is likely to be encoded successfully.
+    ///
+    /// This is `true` if the stream is in a format that can be encoded, its data could be read,
+    /// and (for Vorbis streams) its headers could be reconstructed.
+    /// Stream data is not fully decoded, so encoding can still fail because of corrupted data.
+    #[must_use]
+    pub fn is_decodable(&self) -> bool {
+        self.encodable && self.bytes_read > 0 && self.setup_header_found != Some(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::encode::{vorbis_setup_header, EncodeOptions};
+    use crate::{AudioFormat, Bank, BatchExtractor};
+    use std::io::Cursor;
+
+    #[test]
+    fn probe_pcm_stream() {
+        const HEADER_BASE: [u8; 60] = *b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x01\x00\x00\x0000000000000000000000000000000000";
+        // 44100 Hz, 1 channel, 1 sample, data offset 0
+        const STREAM_HEADER: u64 = (8 << 1) | (1 << 34);
+
+        let data = {
+            let mut buf = Vec::from(HEADER_BASE);
+            buf.extend_from_slice(&STREAM_HEADER.to_le_bytes());
+            buf.extend_from_slice(&[1; 32]);
+            buf
+        };
+        let mut bank = Bank::new(Cursor::new(data)).unwrap();
+
+        let probe = bank.probe_first_stream().unwrap();
+        assert_eq!(probe.format(), AudioFormat::Pcm8);
+        assert_eq!(probe.bytes_read(), 32);
+        assert_eq!(probe.setup_header_found(), None);
+        assert!(probe.is_decodable());
+
+        // the stream can still be read after probing
+        let stream = bank.into_iter().next().unwrap().unwrap();
+        assert_eq!(stream.write_raw(Vec::new()).unwrap(), [1; 32]);
+    }
+
+    #[test]
+    fn probe_vorbis_stream_with_setup_headers() {
+        // 1 stream of 2 channels at 44100 Hz, with a chunk holding a setup header CRC32 that isn't in the lookup table
+        let mut data =
+            b"FSB5\x01\x00\x00\x00\x01\x00\x00\x00\x10\x00\x00\x00\x00\x00\x00\x00".to_vec();
+        data.extend_from_slice(&10u32.to_le_bytes());
+        data.extend_from_slice(&15u32.to_le_bytes());
+        data.extend_from_slice(&[b'0'; 32]);
+        data.extend_from_slice(&(1u64 | (8 << 1) | (1 << 5) | (1000 << 34)).to_le_bytes());
+        data.extend_from_slice(&((4u32 << 1) | (11 << 25)).to_le_bytes());
+        data.extend_from_slice(&0x1234_ABCDu32.to_le_bytes());
+        data.extend_from_slice(&[8, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let mut bank = Bank::new(Cursor::new(data)).unwrap();
+
+        let probe = bank.probe_first_stream().unwrap();
+        assert_eq!(probe.setup_header_found(), Some(false));
+        assert!(!probe.is_decodable());
+
+        let setup_header = vorbis_setup_header(0xA722_97FF).unwrap();
+        let mut options = EncodeOptions::new();
+        let _ = options.vorbis_setup_header(0x1234_ABCD, setup_header);
+        let probe = bank.probe_first_stream_with(&options).unwrap();
+        assert_eq!(probe.setup_header_found(), Some(true));
+        assert!(probe.is_decodable());
+
+        let mut extractor = BatchExtractor::new();
+        let _ = extractor.register_setup_header(0x1234_ABCD, setup_header);
+        let probe = extractor.probe_first_stream(&mut bank).unwrap();
+        assert_eq!(probe.setup_header_found(), Some(true));
+    }
+}
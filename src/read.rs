@@ -2,14 +2,17 @@ use std::{
     cmp::min,
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
-    io::{BufRead, Error as IoError, ErrorKind, Read},
+    io::{BufRead, Error as IoError, ErrorKind, Read, Seek, SeekFrom},
     num::NonZeroUsize,
 };
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct Reader<R: Read> {
     inner: R,
     position: usize,
+    capture: Option<Vec<u8>>,
 }
 
 impl<R: Read> Reader<R> {
@@ -17,56 +20,45 @@ impl<R: Read> Reader<R> {
         Self {
             inner: reader,
             position: 0,
+            capture: None,
         }
     }
 
-    fn read_to_array<const LEN: usize>(&mut self, buf: &mut [u8; LEN]) -> ReadResult<()> {
-        match self.inner.read(buf) {
-            Ok(n) => {
-                self.position += n;
-
-                if n == LEN {
-                    Ok(())
-                } else {
-                    Err(self.to_error(ReadErrorKind::Incomplete(Needed::Size(
-                        NonZeroUsize::new(LEN - n).expect("n is guaranteed to not equal LEN"),
-                    ))))
+    // Readers can return fewer bytes than requested without reaching the end of their data,
+    // so reading continues until the buffer is full or the reader returns no more bytes.
+    // As a result, incomplete reads are only reported once the reader reaches EOF.
+    // The reader is always read from at least once, so its errors are reported even for an empty buffer.
+    fn read_to_slice(&mut self, buf: &mut [u8]) -> ReadResult<()> {
+        let mut filled = 0;
+
+        loop {
+            match self.inner.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    if let Some(capture) = &mut self.capture {
+                        capture.extend_from_slice(&buf[filled..filled + n]);
+                    }
+                    self.position += n;
+                    filled += n;
+
+                    if filled == buf.len() {
+                        break;
+                    }
                 }
+                Err(e) => match e.kind() {
+                    // this I/O error is non-fatal, so reading is retried
+                    ErrorKind::Interrupted => {}
+                    ErrorKind::UnexpectedEof => {
+                        return Err(self.to_error(ReadErrorKind::Incomplete(Needed::Unknown)))
+                    }
+                    _ => return Err(self.to_error_with_source(ReadErrorKind::Failure, e)),
+                },
             }
-            Err(e) => match e.kind() {
-                // this I/O error is non-fatal, so reading is retried
-                ErrorKind::Interrupted => self.read_to_array(buf),
-                ErrorKind::UnexpectedEof => {
-                    Err(self.to_error(ReadErrorKind::Incomplete(Needed::Unknown)))
-                }
-                _ => Err(self.to_error_with_source(ReadErrorKind::Failure, e)),
-            },
         }
-    }
 
-    fn read_to_slice(&mut self, buf: &mut [u8]) -> ReadResult<()> {
-        match self.inner.read(buf) {
-            Ok(n) => {
-                self.position += n;
-                let buf_len = buf.len();
-
-                if n == buf_len {
-                    Ok(())
-                } else {
-                    Err(self.to_error(ReadErrorKind::Incomplete(Needed::Size(
-                        NonZeroUsize::new(buf_len - n)
-                            .expect("n is guaranteed to not equal buf_len"),
-                    ))))
-                }
-            }
-            Err(e) => match e.kind() {
-                // this I/O error is non-fatal, so reading is retried
-                ErrorKind::Interrupted => self.read_to_slice(buf),
-                ErrorKind::UnexpectedEof => {
-                    Err(self.to_error(ReadErrorKind::Incomplete(Needed::Unknown)))
-                }
-                _ => Err(self.to_error_with_source(ReadErrorKind::Failure, e)),
-            },
+        match NonZeroUsize::new(buf.len() - filled) {
+            None => Ok(()),
+            Some(needed) => Err(self.to_error(ReadErrorKind::Incomplete(Needed::Size(needed)))),
         }
     }
 
@@ -74,9 +66,23 @@ impl<R: Read> Reader<R> {
         self.position
     }
 
+    // Starts recording a copy of every byte that is read (or skipped) until `finish_capture` is called.
+    // Bytes read through a `CappedReader` are not recorded.
+    pub(crate) fn start_capture(&mut self) {
+        self.capture = Some(Vec::new());
+    }
+
+    pub(crate) fn finish_capture(&mut self) -> Option<Vec<u8>> {
+        self.capture.take()
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.inner
+    }
+
     pub(crate) fn take_const<const LEN: usize>(&mut self) -> ReadResult<[u8; LEN]> {
         let mut buf = [0; LEN];
-        Self::read_to_array(self, &mut buf)?;
+        Self::read_to_slice(self, &mut buf)?;
         Ok(buf)
     }
 
@@ -86,13 +92,50 @@ impl<R: Read> Reader<R> {
         Ok(buf)
     }
 
+    pub(crate) fn take_boxed(&mut self, len: usize) -> ReadResult<Box<[u8]>> {
+        let mut buf = vec![0; len].into_boxed_slice();
+        Self::read_to_slice(self, &mut buf)?;
+        Ok(buf)
+    }
+
+    // If the reader runs out of data, `position` still counts the bytes that were consumed before the error,
+    // so it can be used to report (or resume from) where reading stopped.
+    // Skipped bytes are read into a small buffer in pieces, so skipping large regions doesn't allocate.
+    // Buffered readers aren't skipped through `BufRead::consume`, since callers are only bounded by `Read`.
     pub(crate) fn skip(&mut self, amount: usize) -> ReadResult<()> {
-        let mut buf = vec![0; amount];
-        Self::read_to_slice(self, buf.as_mut_slice())
+        let mut buf = [0; SKIP_BUFFER_SIZE];
+        let mut remaining = amount;
+
+        while remaining > 0 {
+            let len = remaining.min(buf.len());
+            remaining -= len;
+
+            // the number of bytes needed counts every byte left to skip, not just the bytes in this piece
+            Self::read_to_slice(self, &mut buf[..len])
+                .map_err(|e| e.with_more_needed(remaining))?;
+        }
+
+        Ok(())
     }
 
+    // Sizes read from the file header can be smaller than the data that was already read,
+    // so a position behind the current position is reported as an error instead of underflowing.
     pub(crate) fn advance_to(&mut self, position: usize) -> ReadResult<()> {
-        self.skip(position - self.position)
+        let amount = position
+            .checked_sub(self.position)
+            .ok_or_else(|| self.to_error(ReadErrorKind::Backwards))?;
+
+        self.skip(amount)
+    }
+
+    // Stream and chunk sizes are read from the file header, so adding them to a position can overflow,
+    // especially on 32-bit targets. This returns an error instead of wrapping around.
+    pub(crate) fn advance_to_offset(&mut self, start: usize, offset: usize) -> ReadResult<()> {
+        let position = start
+            .checked_add(offset)
+            .ok_or_else(|| self.to_error(ReadErrorKind::Overflow))?;
+
+        self.advance_to(position)
     }
 
     // `std::io::Take` isn't used here because constructing it requires taking ownership of the reader
@@ -105,42 +148,150 @@ impl<R: Read> Reader<R> {
 
     pub(crate) fn u8(&mut self) -> ReadResult<u8> {
         let mut buf = [0; 1];
-        Self::read_to_array(self, &mut buf)?;
+        Self::read_to_slice(self, &mut buf)?;
         Ok(buf[0])
     }
 
     pub(crate) fn le_u16(&mut self) -> ReadResult<u16> {
         let mut buf = [0; 2];
-        Self::read_to_array(self, &mut buf)?;
+        Self::read_to_slice(self, &mut buf)?;
         Ok(u16::from_le_bytes(buf))
     }
 
     pub(crate) fn le_u32(&mut self) -> ReadResult<u32> {
         let mut buf = [0; 4];
-        Self::read_to_array(self, &mut buf)?;
+        Self::read_to_slice(self, &mut buf)?;
         Ok(u32::from_le_bytes(buf))
     }
 
     pub(crate) fn le_u64(&mut self) -> ReadResult<u64> {
         let mut buf = [0; 8];
-        Self::read_to_array(self, &mut buf)?;
+        Self::read_to_slice(self, &mut buf)?;
         Ok(u64::from_le_bytes(buf))
     }
 
     pub(crate) fn be_i16(&mut self) -> ReadResult<i16> {
         let mut buf = [0; 2];
-        Self::read_to_array(self, &mut buf)?;
+        Self::read_to_slice(self, &mut buf)?;
         Ok(i16::from_be_bytes(buf))
     }
 }
 
+impl<R: Read + Seek> Reader<R> {
+    // Seeks to a position in either direction.
+    // Positions are relative to where the reader started, so the inner reader is seeked relative to its current position.
+    pub(crate) fn seek_to(&mut self, position: usize) -> Result<(), IoError> {
+        let offset = i64::try_from(position)
+            .ok()
+            .zip(i64::try_from(self.position).ok())
+            .and_then(|(target, current)| target.checked_sub(current))
+            .ok_or_else(|| IoError::from(ErrorKind::InvalidInput))?;
+
+        let _ = self.inner.seek(SeekFrom::Current(offset))?;
+        self.position = position;
+        Ok(())
+    }
+}
+
+// An asynchronous counterpart to `Reader`, which only supports the operations needed to read file headers and stream data.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub(crate) struct AsyncReader<R> {
+    inner: R,
+    position: usize,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin> AsyncReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self {
+            inner: reader,
+            position: 0,
+        }
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+
+    fn to_error(&self, kind: ReadErrorKind) -> ReadError {
+        ReadError {
+            position: self.position,
+            kind,
+            source: None,
+        }
+    }
+
+    // Reads until `len` bytes are read or the reader reaches EOF, so fewer than `len` bytes can be returned.
+    // The buffer grows as data is read, so a large `len` from a malformed header doesn't cause a huge allocation.
+    pub(crate) async fn take_up_to(&mut self, len: usize) -> ReadResult<Vec<u8>> {
+        let mut buf = Vec::new();
+        let limit = u64::try_from(len).map_err(|_| self.to_error(ReadErrorKind::Overflow))?;
+
+        let result = (&mut self.inner).take(limit).read_to_end(&mut buf).await;
+        self.position += buf.len();
+
+        match result {
+            Ok(_) => Ok(buf),
+            Err(e) => Err(ReadError {
+                position: self.position,
+                kind: ReadErrorKind::Failure,
+                source: Some(e),
+            }),
+        }
+    }
+
+    pub(crate) async fn take(&mut self, len: usize) -> ReadResult<Vec<u8>> {
+        let buf = self.take_up_to(len).await?;
+
+        match NonZeroUsize::new(len - buf.len()) {
+            None => Ok(buf),
+            Some(needed) => Err(self.to_error(ReadErrorKind::Incomplete(Needed::Size(needed)))),
+        }
+    }
+
+    pub(crate) async fn advance_to(&mut self, position: usize) -> ReadResult<()> {
+        let amount = position
+            .checked_sub(self.position)
+            .ok_or_else(|| self.to_error(ReadErrorKind::Backwards))?;
+
+        // skipped bytes are read in small pieces rather than all at once
+        let mut remaining = amount;
+        let mut buf = [0; 4096];
+
+        while remaining > 0 {
+            let len = remaining.min(buf.len());
+            let n = match self.inner.read(&mut buf[..len]).await {
+                Ok(n) => n,
+                Err(e) => {
+                    return Err(ReadError {
+                        position: self.position,
+                        kind: ReadErrorKind::Failure,
+                        source: Some(e),
+                    })
+                }
+            };
+
+            if n == 0 {
+                let needed = NonZeroUsize::new(remaining).expect("remaining bytes are non-zero");
+                return Err(self.to_error(ReadErrorKind::Incomplete(Needed::Size(needed))));
+            }
+
+            self.position += n;
+            remaining -= n;
+        }
+
+        Ok(())
+    }
+}
+
 // essentially `std::io::Take` but with a mutable reference to a reader instead of owning it
 pub(crate) struct CappedReader<'reader, R: Read> {
     reader: &'reader mut Reader<R>,
     limit: usize,
 }
 
-impl<'reader, R: Read> Read for CappedReader<'reader, R> {
+impl<R: Read> Read for CappedReader<'_, R> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
         if self.limit == 0 {
             return Ok(0);
@@ -154,7 +305,7 @@ impl<'reader, R: Read> Read for CappedReader<'reader, R> {
     }
 }
 
-impl<'reader, R: BufRead> BufRead for CappedReader<'reader, R> {
+impl<R: BufRead> BufRead for CappedReader<'_, R> {
     fn fill_buf(&mut self) -> Result<&[u8], IoError> {
         if self.limit == 0 {
             return Ok(&[]);
@@ -165,15 +316,19 @@ impl<'reader, R: BufRead> BufRead for CappedReader<'reader, R> {
         Ok(&buf[..cap])
     }
 
+    // Bytes consumed from the inner buffer count towards the position, just like bytes returned by `read`.
     fn consume(&mut self, amt: usize) {
         let amt = min(amt, self.limit);
         self.limit -= amt;
+        self.reader.position += amt;
         self.reader.inner.consume(amt);
     }
 }
 
 type ReadResult<T> = Result<T, ReadError>;
 
+const SKIP_BUFFER_SIZE: usize = 4096;
+
 #[derive(Debug)]
 pub(crate) struct ReadError {
     position: usize,
@@ -186,6 +341,8 @@ pub(crate) struct ReadError {
 pub(crate) enum ReadErrorKind {
     Failure,
     Incomplete(Needed),
+    Overflow,
+    Backwards,
 }
 
 #[derive(Debug)]
@@ -213,6 +370,21 @@ impl<R: Read> Reader<R> {
     }
 }
 
+impl ReadError {
+    fn with_more_needed(mut self, amount: usize) -> Self {
+        if let ReadErrorKind::Incomplete(Needed::Size(needed)) = &mut self.kind {
+            *needed = needed.saturating_add(amount);
+        }
+        self
+    }
+
+    // Incomplete reads are only reported at EOF, so they indicate that the data ended early
+    // rather than an I/O failure.
+    pub(crate) fn is_eof(&self) -> bool {
+        matches!(self.kind, ReadErrorKind::Incomplete(_))
+    }
+}
+
 #[cfg(test)]
 impl ReadError {
     fn is_kind(&self, kind: ReadErrorKind) -> bool {
@@ -230,6 +402,12 @@ impl Display for ReadError {
                 }
                 Needed::Unknown => f.write_str("incomplete data"),
             },
+            ReadErrorKind::Overflow => {
+                f.write_str("target position was too large to be represented")
+            }
+            ReadErrorKind::Backwards => {
+                f.write_str("target position was behind the current position")
+            }
         }?;
 
         f.write_fmt(format_args!(" - byte position {}", self.position))
@@ -249,7 +427,7 @@ impl Error for ReadError {
 mod test {
     use super::{Needed, ReadErrorKind, ReadResult, Reader};
     use std::{
-        io::{Error as IoError, ErrorKind, Read, Result as IoResult},
+        io::{BufRead, Cursor, Error as IoError, ErrorKind, Read, Result as IoResult},
         num::NonZeroUsize,
     };
 
@@ -268,6 +446,20 @@ mod test {
                 .is_kind(ReadErrorKind::Incomplete(Needed::Size(NonZeroUsize::new(1).unwrap())))));
     }
 
+    #[test]
+    fn take_boxed_bytes() {
+        let data = b"abc123";
+        let mut reader = Reader::new(data.as_slice());
+
+        assert_eq!(&*reader.take_boxed(2).unwrap(), b"ab");
+        assert_eq!(&*reader.take_boxed(4).unwrap(), b"c123");
+        assert!(reader.take_boxed(0).unwrap().is_empty());
+        assert!(reader
+            .take_boxed(1)
+            .is_err_and(|e| e
+                .is_kind(ReadErrorKind::Incomplete(Needed::Size(NonZeroUsize::new(1).unwrap())))));
+    }
+
     #[test]
     fn skip_bytes() {
         let data = b"abc123";
@@ -283,6 +475,25 @@ mod test {
                 .is_kind(ReadErrorKind::Incomplete(Needed::Size(NonZeroUsize::new(1).unwrap())))));
     }
 
+    #[test]
+    fn skip_large_region() {
+        const SIZE: usize = 8 * 1024 * 1024;
+
+        let mut reader = Reader::new(std::io::repeat(1).take(SIZE as u64 + 1));
+        assert!(reader.skip(SIZE).is_ok());
+        assert_eq!(reader.position(), SIZE);
+        assert_eq!(reader.take_const().unwrap(), [1]);
+
+        // the error reports every byte that couldn't be skipped, even when more than one piece is missing
+        let mut reader = Reader::new(std::io::repeat(1).take(SIZE as u64));
+        assert!(reader
+            .skip(SIZE + 10_000)
+            .is_err_and(|e| e.is_kind(ReadErrorKind::Incomplete(Needed::Size(
+                NonZeroUsize::new(10_000).unwrap()
+            )))));
+        assert_eq!(reader.position(), SIZE);
+    }
+
     #[test]
     fn advance_to_position() {
         let data = b"abc123";
@@ -297,12 +508,42 @@ mod test {
         assert!(reader.advance_to(6).is_ok());
         assert_eq!(reader.position(), 6);
 
+        assert!(reader
+            .advance_to(2)
+            .is_err_and(|e| e.is_kind(ReadErrorKind::Backwards)));
+        assert_eq!(reader.position(), 6);
+
         assert!(reader
             .advance_to(10)
             .is_err_and(|e| e
                 .is_kind(ReadErrorKind::Incomplete(Needed::Size(NonZeroUsize::new(4).unwrap())))));
     }
 
+    #[test]
+    fn advance_to_offset_position() {
+        let data = b"abc123";
+        let mut reader = Reader::new(data.as_slice());
+
+        assert!(reader.advance_to_offset(0, 2).is_ok());
+        assert_eq!(reader.position(), 2);
+
+        assert!(reader.advance_to_offset(2, 4).is_ok());
+        assert_eq!(reader.position(), 6);
+    }
+
+    #[test]
+    fn handle_position_overflow() {
+        let data = b"abc123";
+        let mut reader = Reader::new(data.as_slice());
+
+        // simulates a stream of size near u32::MAX starting near the end of a 32-bit address space
+        let start = usize::MAX - u32::MAX as usize + 1;
+        assert!(reader
+            .advance_to_offset(start, u32::MAX as usize)
+            .is_err_and(|e| e.is_kind(ReadErrorKind::Overflow)));
+        assert_eq!(reader.position(), 0);
+    }
+
     #[test]
     fn parse_single_number() {
         let data = b"\x00\x00\x00\x00\x00\x00";
@@ -347,7 +588,7 @@ mod test {
     impl<R: Read> Reader<R> {
         fn unit(&mut self) -> ReadResult<()> {
             let mut buf = [0; 0];
-            Self::read_to_array(self, &mut buf)
+            Self::read_to_slice(self, &mut buf)
         }
     }
 
@@ -388,6 +629,50 @@ mod test {
         assert!(reader.unit().is_ok());
     }
 
+    #[test]
+    fn retry_interrupted_io_until_eof() {
+        let mut reader = Reader::new(InterruptReader(0));
+
+        assert!(reader.u8().is_err_and(|e| e.is_eof()));
+    }
+
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl Read for ByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+            let len = buf.len().min(self.0.len()).min(1);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn handle_short_reads() {
+        let mut reader = Reader::new(ByteReader(b"\x11\x00\x00\x00\x22"));
+
+        assert_eq!(reader.le_u32().unwrap(), 17);
+        assert!(reader.le_u32().is_err_and(|e| e.is_eof()
+            && e.is_kind(ReadErrorKind::Incomplete(Needed::Size(NonZeroUsize::new(3).unwrap())))));
+        assert_eq!(reader.position(), 5);
+    }
+
+    #[test]
+    fn track_position_after_failed_skip() {
+        let mut reader = Reader::new(ByteReader(b"abcde"));
+
+        assert!(reader.skip(2).is_ok());
+        assert!(reader.skip(6).is_err_and(|e| e.is_eof()
+            && e.is_kind(ReadErrorKind::Incomplete(Needed::Size(NonZeroUsize::new(3).unwrap())))));
+        assert_eq!(reader.position(), 5);
+
+        assert!(reader
+            .advance_to(10)
+            .is_err_and(|e| e
+                .is_kind(ReadErrorKind::Incomplete(Needed::Size(NonZeroUsize::new(5).unwrap())))));
+        assert_eq!(reader.position(), 5);
+    }
+
     struct EofReader;
 
     impl Read for EofReader {
@@ -440,6 +725,33 @@ mod test {
         assert!(reader.unit().is_err_and(|e| e.is_kind(ReadErrorKind::Failure)));
     }
 
+    #[test]
+    fn seek_in_either_direction() {
+        let mut source = Cursor::new(b"__abc123");
+        source.set_position(2);
+        let mut reader = Reader::new(source);
+
+        assert!(reader.seek_to(3).is_ok());
+        assert_eq!(reader.take(3).unwrap(), b"123");
+        assert!(reader.seek_to(0).is_ok());
+        assert_eq!(reader.position(), 0);
+        assert_eq!(reader.take(3).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn capture_read_bytes() {
+        let mut reader = Reader::new(ByteReader(b"\x01abcdef"));
+
+        assert_eq!(reader.u8().unwrap(), 1);
+        reader.start_capture();
+        assert!(reader.skip(2).is_ok());
+        assert_eq!(reader.take_const().unwrap(), *b"cd");
+        assert!(reader.skip(3).is_err());
+
+        assert_eq!(reader.finish_capture().unwrap(), b"abcdef");
+        assert!(reader.finish_capture().is_none());
+    }
+
     #[test]
     fn capped_reader_works() {
         let data = b"abcd1234";
@@ -455,4 +767,21 @@ mod test {
             .read_exact(&mut [0])
             .is_err_and(|e| e.kind() == ErrorKind::UnexpectedEof));
     }
+
+    #[test]
+    fn capped_reader_counts_buffered_bytes() {
+        let data = b"abcd1234";
+        let mut reader = Reader::new(data.as_slice());
+
+        let mut capped = reader.limit(6);
+        assert_eq!(capped.fill_buf().unwrap(), b"abcd12");
+        capped.consume(4);
+        assert_eq!(capped.fill_buf().unwrap(), b"12");
+        // consuming more than the limit only consumes up to the limit
+        capped.consume(10);
+        assert!(capped.fill_buf().unwrap().is_empty());
+
+        assert_eq!(reader.position(), 6);
+        assert_eq!(reader.take_const().unwrap(), *b"34");
+    }
 }
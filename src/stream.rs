@@ -1,9 +1,23 @@
-use crate::encode::{encode, EncodeError};
+use crate::bank::DecodeError;
+#[cfg(feature = "loudness")]
+use crate::encode::measure_loudness;
+use crate::encode::{
+    decode_chunked, encode, encode_buffered, encode_cached, encode_counted, encode_looped,
+    encode_time_range, is_dual_mono, measure_peaks, pcm_endianness, stereo_correlation, write_raw,
+    ChannelCorrelation, EncodeError, EncodeOptions, Endianness, SetupHeaderCache,
+};
+use crate::header::error::{HeaderError, HeaderErrorKind};
 use crate::header::{AudioFormat, Loop, StreamInfo};
+use crate::name::safe_file_stem;
 use crate::read::Reader;
 use std::{
-    io::{Read, Write},
-    num::{NonZeroU32, NonZeroU8},
+    borrow::Cow,
+    fs::File,
+    io::{Cursor, Error as IoError, ErrorKind, Read, Result as IoResult, Seek, Write},
+    mem::replace,
+    num::{NonZeroU32, NonZeroU8, NonZeroUsize},
+    path::Path,
+    time::Duration,
 };
 
 /// An audio stream of data that has not been read yet.
@@ -13,7 +27,16 @@ use std::{
 /// Unlike [`Stream`], encoding can fail when reading/decoding stream data.
 /// However, encoding for both [`LazyStream`] and [`Stream`] can fail due to I/O errors.
 ///
+/// Stream data is read as it is encoded, so this is the recommended way to encode large streams.
+/// For example, Vorbis streams are decoded and re-encoded one packet at a time when they are written as Ogg files.
+/// This doesn't hold for every format and option:
+/// ADPCM streams are decoded entirely before being written,
+/// as are streams encoded with options that need every sample at once
+/// (writing Vorbis streams as WAVE files with [`EncodeOptions::vorbis_output`], resampling, swapping stereo channels, or downmixing).
+/// Vorbis layers written as chained Ogg streams are encoded into memory until every layer is complete.
+///
 /// [`Bank::read_streams`]: crate::Bank::read_streams
+/// [`EncodeOptions::vorbis_output`]: crate::encode::EncodeOptions::vorbis_output
 #[derive(Debug, PartialEq, Eq)]
 pub struct LazyStream<'bank, R: Read> {
     index: u32,
@@ -54,6 +77,15 @@ impl<'bank, R: Read> LazyStream<'bank, R> {
         self.format
     }
 
+    /// Returns whether the samples of this stream are encoded from big-endian data, if the stream is in a PCM format.
+    ///
+    /// This reflects the decision made by the PCM encoder, based on flags in the sound bank's file header.
+    /// For other formats, `None` is returned.
+    #[must_use]
+    pub fn pcm_is_big_endian(&self) -> Option<bool> {
+        pcm_endianness(self.format, self.flags).map(|order| order == Endianness::Big)
+    }
+
     /// Returns the sample rate (Hz) of the stream.
     #[must_use]
     pub fn sample_rate(&self) -> NonZeroU32 {
@@ -67,23 +99,110 @@ impl<'bank, R: Read> LazyStream<'bank, R> {
     }
 
     /// Returns the number of samples in the stream.
+    ///
+    /// This is the same as [`decoded_sample_len`](Self::decoded_sample_len).
+    /// `None` is returned for streams without samples, which are only parsed with [`BankBuilder::allow_zero_samples`].
+    ///
+    /// [`BankBuilder::allow_zero_samples`]: crate::BankBuilder::allow_zero_samples
     #[must_use]
-    pub fn sample_count(&self) -> NonZeroU32 {
+    pub fn sample_count(&self) -> Option<NonZeroU32> {
         self.info.num_samples
     }
 
+    /// Returns the duration of the stream, computed from its sample count and sample rate without decoding.
+    ///
+    /// This may differ slightly from the duration of the encoded output for formats whose encoders pad the stream
+    /// (e.g. to a whole number of frames). Streams without samples have a duration of 0.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.info.duration()
+    }
+
     /// Returns loop information, if it exists.
     #[must_use]
     pub fn loop_info(&self) -> Option<Loop> {
         self.info.stream_loop
     }
 
+    /// Returns the peak amplitude of the stream stored in the sound bank, if it exists.
+    ///
+    /// The value is linear, where 1.0 is full scale. It is read from metadata, so it can be missing or stale;
+    /// see [`Stream::measure_peaks`] for measuring the peak amplitude from the stream data instead.
+    #[must_use]
+    pub fn peak_volume(&self) -> Option<f32> {
+        self.info.peak_volume.map(f32::from_bits)
+    }
+
+    /// Returns the CRC32 checksum of the Vorbis setup header that the stream needs, if it exists.
+    ///
+    /// Only Vorbis streams contain this checksum. It identifies the setup header in the lookup table
+    /// used for encoding (see [`vorbis_setup_header`](crate::encode::vorbis_setup_header)),
+    /// so it is useful for reporting streams that fail with [`VorbisErrorKind::Crc32Lookup`].
+    ///
+    /// [`VorbisErrorKind::Crc32Lookup`]: crate::encode::VorbisErrorKind::Crc32Lookup
+    #[must_use]
+    pub fn vorbis_setup_crc32(&self) -> Option<u32> {
+        self.info.vorbis_crc32
+    }
+
+    /// Returns the raw ATRAC9 configuration of the stream, if it exists.
+    ///
+    /// Only ATRAC9 streams contain this configuration. The bytes are stored as-is from the stream header,
+    /// so they can be passed to external decoders (e.g. vgmstream or at9tool) alongside the data from `write_raw()`.
+    #[must_use]
+    pub fn atrac9_config(&self) -> Option<&[u8]> {
+        self.info.atrac9_config.as_deref()
+    }
+
+    /// Returns the raw xWMA configuration of the stream, if it exists.
+    ///
+    /// Only xWMA streams contain this configuration. Its first 8 bytes hold the WMA format tag, block alignment,
+    /// and average bytes per second (in big-endian order); the remaining bytes are stored as-is from the stream header.
+    #[must_use]
+    pub fn xwma_config(&self) -> Option<&[u8]> {
+        self.info.xwma_config.as_deref()
+    }
+
+    /// Returns the total size of the Opus packets in the stream, in bytes, if the size exists.
+    ///
+    /// Only Opus streams contain this size. In the data from `write_raw()`, every packet is preceded by a 4-byte frame header,
+    /// which isn't counted in this size.
+    #[must_use]
+    pub fn opus_data_size(&self) -> Option<u32> {
+        self.info.opus_data_size
+    }
+
     /// Returns the size of the stream, in bytes.
+    ///
+    /// This is the same as [`compressed_byte_len`](Self::compressed_byte_len).
     #[must_use]
     pub fn size(&self) -> NonZeroU32 {
         self.info.size
     }
 
+    /// Returns the length of the stream data stored in the sound bank, in bytes.
+    ///
+    /// This is the size of the data before decoding, so it depends on the audio format:
+    /// for PCM formats, it is proportional to the number of samples,
+    /// while for compressed formats (e.g. Vorbis or ADPCM), it is usually much smaller than the decoded audio.
+    /// See [`decoded_sample_len`](Self::decoded_sample_len) for the length of the decoded audio.
+    #[must_use]
+    pub fn compressed_byte_len(&self) -> NonZeroU32 {
+        self.info.size
+    }
+
+    /// Returns the length of the decoded audio, in samples per channel.
+    ///
+    /// Unlike [`compressed_byte_len`](Self::compressed_byte_len), this does not depend on the audio format.
+    /// Dividing it by the [sample rate](Self::sample_rate) gives the duration of the stream in seconds.
+    /// `None` is returned for streams without samples, which are only parsed with [`BankBuilder::allow_zero_samples`].
+    ///
+    /// [`BankBuilder::allow_zero_samples`]: crate::BankBuilder::allow_zero_samples
+    #[must_use]
+    pub fn decoded_sample_len(&self) -> Option<NonZeroU32> {
+        self.info.num_samples
+    }
+
     /// Returns the name of the stream, if it exists.
     #[must_use]
     pub fn name(&self) -> Option<&str> {
@@ -93,13 +212,213 @@ impl<'bank, R: Read> LazyStream<'bank, R> {
         }
     }
 
+    /// Returns the name of the stream in a form that can safely be used as a file name (without an extension).
+    ///
+    /// The name is converted with [`sanitize_name`], and is only copied if any characters had to be changed.
+    /// If the stream has no name, `stream_{index}` is returned (e.g. `stream_0`), like the file names used by [`Bank::extract_to_dir`].
+    ///
+    /// [`sanitize_name`]: crate::sanitize_name
+    /// [`Bank::extract_to_dir`]: crate::Bank::extract_to_dir
+    #[must_use]
+    pub fn safe_file_stem(&self) -> Cow<'_, str> {
+        safe_file_stem(self.name(), self.index)
+    }
+
     /// Encodes the stream data by writing audio samples to a writer.
     ///
     /// # Errors
     /// This function returns an error if the stream data could not be successfully written.
     /// See [`EncodeError`] for more information.
     pub fn write<W: Write>(self, sink: W) -> Result<W, EncodeError> {
-        encode(self.format, self.flags, self.info, self.reader, sink)
+        self.write_with(sink, &EncodeOptions::default())
+    }
+
+    /// Encodes the stream data by writing audio samples to a writer, using the given options.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn write_with<W: Write>(self, sink: W, options: &EncodeOptions) -> Result<W, EncodeError> {
+        encode(self.format, self.flags, self.info, self.reader, sink, options)
+    }
+
+    // Encodes the stream data like `write_with`, taking parsed Vorbis setup headers from `cache`.
+    pub(crate) fn write_cached<W: Write>(
+        self,
+        sink: W,
+        options: &EncodeOptions,
+        cache: &mut SetupHeaderCache,
+    ) -> Result<W, EncodeError> {
+        encode_cached(self.format, self.flags, self.info, self.reader, sink, options, cache)
+            .map(|(sink, _)| sink)
+    }
+
+    /// Encodes the stream data like [`LazyStream::write_with`], also returning the number of sample frames written.
+    ///
+    /// For PCM streams, frames are counted from the sample data that was actually written.
+    /// For Vorbis streams, frames are counted from the decoded audio blocks.
+    /// Comparing the count against [`LazyStream::sample_count`] can detect streams that were silently truncated.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn write_counted<W: Write>(
+        self,
+        sink: W,
+        options: &EncodeOptions,
+    ) -> Result<(W, u64), EncodeError> {
+        encode_counted(self.format, self.flags, self.info, self.reader, sink, options)
+    }
+
+    /// Encodes the stream data like [`LazyStream::write`], but buffers writes internally.
+    ///
+    /// This is useful for unbuffered writers such as [`File`](std::fs::File), which would otherwise receive many small writes.
+    /// The buffer is flushed before the writer is returned, so there is no need to wrap the writer in a [`BufWriter`](std::io::BufWriter).
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be successfully written,
+    /// or if the buffered data could not be flushed.
+    /// See [`EncodeError`] for more information.
+    pub fn write_buffered<W: Write>(self, sink: W) -> Result<W, EncodeError> {
+        encode_buffered(
+            self.format,
+            self.flags,
+            self.info,
+            self.reader,
+            sink,
+            &EncodeOptions::default(),
+        )
+    }
+
+    /// Encodes the stream data like [`LazyStream::write`], writing it to a new file at `path`.
+    ///
+    /// The file is created (or truncated if it already exists), and writes are buffered internally.
+    ///
+    /// # Errors
+    /// This function returns an error if the file could not be created,
+    /// or if the stream data could not be successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn write_to_path<P: AsRef<Path>>(self, path: P) -> Result<(), EncodeError> {
+        let file = File::create(path).map_err(EncodeError::CreateFile)?;
+        self.write_buffered(file).map(|_| ())
+    }
+
+    /// Encodes a PCM stream with its loop region repeated, producing a single WAVE file.
+    ///
+    /// The data before the loop is written once, followed by the loop region `repeats` times.
+    /// Data after the end of the loop is not written.
+    /// The loop region is always written at least once, even if `repeats` is 0.
+    /// If the stream has no loop information, this behaves the same as [`LazyStream::write`].
+    ///
+    /// Unlike [`LazyStream::write`], the stream data is read into memory before encoding.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream is not in a PCM format,
+    /// or if the stream data could not be successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn write_looped<W: Write>(self, repeats: u32, sink: W) -> Result<W, EncodeError> {
+        encode_looped(
+            self.format,
+            self.flags,
+            self.info,
+            repeats,
+            self.reader,
+            sink,
+            &EncodeOptions::default(),
+        )
+    }
+
+    /// Decodes the part of the stream between two points in time, producing a single WAVE file.
+    ///
+    /// Times are converted to sample positions using the sample rate, and are clamped to the length of the stream.
+    /// PCM streams are written with their original samples, while only the requested part is read.
+    /// Vorbis streams are decoded from the start (discarding samples before `start`) and written with 32-bit float samples.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream is not in a PCM or Vorbis format,
+    /// if the range contains no samples, or if the stream data could not be successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn decode_time_range<W: Write>(
+        self,
+        start: Duration,
+        end: Duration,
+        sink: W,
+    ) -> Result<W, EncodeError> {
+        self.decode_time_range_with(start, end, sink, &EncodeOptions::default())
+    }
+
+    /// Decodes the part of the stream between two points in time like [`LazyStream::decode_time_range`], using the given options.
+    ///
+    /// Vorbis streams are decoded with the setup headers and setup header CRC32 in `options`, like [`LazyStream::write_with`];
+    /// other options are ignored.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream is not in a PCM or Vorbis format,
+    /// if the range contains no samples, or if the stream data could not be successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn decode_time_range_with<W: Write>(
+        self,
+        start: Duration,
+        end: Duration,
+        sink: W,
+        options: &EncodeOptions,
+    ) -> Result<W, EncodeError> {
+        encode_time_range(
+            self.format,
+            self.flags,
+            self.info,
+            start..end,
+            self.reader,
+            sink,
+            options,
+        )
+    }
+
+    /// Decodes the stream into chunks of interleaved 32-bit float samples, each containing `frames_per_chunk` frames.
+    ///
+    /// This is useful for consumers with a fixed buffer size (e.g. audio callbacks), since chunks don't depend on the block size of the audio format.
+    /// Every chunk has `frames_per_chunk` frames (one sample per channel), except for the final chunk, which can be shorter.
+    /// Samples are scaled so that full scale is 1.0.
+    ///
+    /// The entire stream is decoded before the first chunk is returned.
+    /// If decoding fails, the iterator only returns the error.
+    /// Only PCM and Vorbis streams can be decoded.
+    pub fn decode_chunked(
+        self,
+        frames_per_chunk: NonZeroUsize,
+    ) -> impl Iterator<Item = Result<Vec<f32>, DecodeError>> {
+        self.decode_chunked_with(frames_per_chunk, &EncodeOptions::default())
+    }
+
+    /// Decodes the stream into chunks like [`LazyStream::decode_chunked`], using the given options.
+    ///
+    /// Vorbis streams are decoded with the setup headers and setup header CRC32 in `options`, like [`LazyStream::write_with`];
+    /// other options are ignored.
+    pub fn decode_chunked_with(
+        self,
+        frames_per_chunk: NonZeroUsize,
+        options: &EncodeOptions,
+    ) -> impl Iterator<Item = Result<Vec<f32>, DecodeError>> {
+        decode_chunked(
+            self.format,
+            self.flags,
+            self.info,
+            frames_per_chunk,
+            self.reader,
+            options,
+        )
+        .map(|chunk| chunk.map_err(DecodeError::from))
+    }
+
+    /// Writes the stream data to a writer as-is, without any encoding or container.
+    ///
+    /// This works for every [`AudioFormat`], including formats that cannot be encoded yet.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be successfully copied.
+    /// See [`EncodeError`] for more information.
+    pub fn write_raw<W: Write>(self, sink: W) -> Result<W, EncodeError> {
+        write_raw(self.info, self.reader, sink)
     }
 }
 
@@ -108,28 +427,48 @@ impl<'bank, R: Read> LazyStream<'bank, R> {
 /// [`Stream`] is accessible through the [`Bank::into_iter`] method,
 /// which converts a [`Bank`] into a [`StreamIntoIter`] that iterates over [`Stream`] instances.
 ///
-/// See [`LazyStream`] for the version of an audio stream that does not immediately read its data into memory.
+/// See [`LazyStream`] for the version of an audio stream that does not immediately read its data into memory,
+/// which uses less memory when encoding large streams.
 ///
 /// [`Bank::into_iter`]: crate::Bank::into_iter
 /// [`Bank`]: crate::Bank
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Stream {
+    index: u32,
     format: AudioFormat,
     flags: u32,
     info: StreamInfo,
     data: Box<[u8]>,
+    truncated: bool,
 }
 
 impl Stream {
-    pub(crate) fn new(format: AudioFormat, flags: u32, info: StreamInfo, data: Box<[u8]>) -> Self {
+    pub(crate) fn new(
+        index: u32,
+        format: AudioFormat,
+        flags: u32,
+        info: StreamInfo,
+        data: Box<[u8]>,
+    ) -> Self {
         Self {
+            index,
             format,
             flags,
             info,
             data,
+            truncated: false,
         }
     }
 
+    /// Returns whether the stream data ended before the size declared in the sound bank's file header.
+    ///
+    /// Truncated streams are only produced by iterators created with [`StreamIntoIter::allow_truncated`].
+    /// The [size](Self::size) of a truncated stream is the number of bytes that could be read.
+    #[must_use]
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
     /// Returns the audio format of this stream. The format is the same for all streams in a sound bank.
     ///
     /// See [`AudioFormat`] for the list of known formats.
@@ -138,6 +477,15 @@ impl Stream {
         self.format
     }
 
+    /// Returns whether the samples of this stream are encoded from big-endian data, if the stream is in a PCM format.
+    ///
+    /// This reflects the decision made by the PCM encoder, based on flags in the sound bank's file header.
+    /// For other formats, `None` is returned.
+    #[must_use]
+    pub fn pcm_is_big_endian(&self) -> Option<bool> {
+        pcm_endianness(self.format, self.flags).map(|order| order == Endianness::Big)
+    }
+
     /// Returns the sample rate (Hz) of the stream.
     #[must_use]
     pub fn sample_rate(&self) -> NonZeroU32 {
@@ -151,23 +499,110 @@ impl Stream {
     }
 
     /// Returns the number of samples in the stream.
+    ///
+    /// This is the same as [`decoded_sample_len`](Self::decoded_sample_len).
+    /// `None` is returned for streams without samples, which are only parsed with [`BankBuilder::allow_zero_samples`].
+    ///
+    /// [`BankBuilder::allow_zero_samples`]: crate::BankBuilder::allow_zero_samples
     #[must_use]
-    pub fn sample_count(&self) -> NonZeroU32 {
+    pub fn sample_count(&self) -> Option<NonZeroU32> {
         self.info.num_samples
     }
 
+    /// Returns the duration of the stream, computed from its sample count and sample rate without decoding.
+    ///
+    /// This may differ slightly from the duration of the encoded output for formats whose encoders pad the stream
+    /// (e.g. to a whole number of frames). Streams without samples have a duration of 0.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.info.duration()
+    }
+
     /// Returns loop information, if it exists.
     #[must_use]
     pub fn loop_info(&self) -> Option<Loop> {
         self.info.stream_loop
     }
 
+    /// Returns the peak amplitude of the stream stored in the sound bank, if it exists.
+    ///
+    /// The value is linear, where 1.0 is full scale. It is read from metadata, so it can be missing or stale;
+    /// see [`Stream::measure_peaks`] for measuring the peak amplitude from the stream data instead.
+    #[must_use]
+    pub fn peak_volume(&self) -> Option<f32> {
+        self.info.peak_volume.map(f32::from_bits)
+    }
+
+    /// Returns the CRC32 checksum of the Vorbis setup header that the stream needs, if it exists.
+    ///
+    /// Only Vorbis streams contain this checksum. It identifies the setup header in the lookup table
+    /// used for encoding (see [`vorbis_setup_header`](crate::encode::vorbis_setup_header)),
+    /// so it is useful for reporting streams that fail with [`VorbisErrorKind::Crc32Lookup`].
+    ///
+    /// [`VorbisErrorKind::Crc32Lookup`]: crate::encode::VorbisErrorKind::Crc32Lookup
+    #[must_use]
+    pub fn vorbis_setup_crc32(&self) -> Option<u32> {
+        self.info.vorbis_crc32
+    }
+
+    /// Returns the raw ATRAC9 configuration of the stream, if it exists.
+    ///
+    /// Only ATRAC9 streams contain this configuration. The bytes are stored as-is from the stream header,
+    /// so they can be passed to external decoders (e.g. vgmstream or at9tool) alongside the data from `write_raw()`.
+    #[must_use]
+    pub fn atrac9_config(&self) -> Option<&[u8]> {
+        self.info.atrac9_config.as_deref()
+    }
+
+    /// Returns the raw xWMA configuration of the stream, if it exists.
+    ///
+    /// Only xWMA streams contain this configuration. Its first 8 bytes hold the WMA format tag, block alignment,
+    /// and average bytes per second (in big-endian order); the remaining bytes are stored as-is from the stream header.
+    #[must_use]
+    pub fn xwma_config(&self) -> Option<&[u8]> {
+        self.info.xwma_config.as_deref()
+    }
+
+    /// Returns the total size of the Opus packets in the stream, in bytes, if the size exists.
+    ///
+    /// Only Opus streams contain this size. In the data from `write_raw()`, every packet is preceded by a 4-byte frame header,
+    /// which isn't counted in this size.
+    #[must_use]
+    pub fn opus_data_size(&self) -> Option<u32> {
+        self.info.opus_data_size
+    }
+
     /// Returns the size of the stream, in bytes.
+    ///
+    /// This is the same as [`compressed_byte_len`](Self::compressed_byte_len).
     #[must_use]
     pub fn size(&self) -> NonZeroU32 {
         self.info.size
     }
 
+    /// Returns the length of the stream data stored in the sound bank, in bytes.
+    ///
+    /// This is the size of the data before decoding, so it depends on the audio format:
+    /// for PCM formats, it is proportional to the number of samples,
+    /// while for compressed formats (e.g. Vorbis or ADPCM), it is usually much smaller than the decoded audio.
+    /// See [`decoded_sample_len`](Self::decoded_sample_len) for the length of the decoded audio.
+    #[must_use]
+    pub fn compressed_byte_len(&self) -> NonZeroU32 {
+        self.info.size
+    }
+
+    /// Returns the length of the decoded audio, in samples per channel.
+    ///
+    /// Unlike [`compressed_byte_len`](Self::compressed_byte_len), this does not depend on the audio format.
+    /// Dividing it by the [sample rate](Self::sample_rate) gives the duration of the stream in seconds.
+    /// `None` is returned for streams without samples, which are only parsed with [`BankBuilder::allow_zero_samples`].
+    ///
+    /// [`BankBuilder::allow_zero_samples`]: crate::BankBuilder::allow_zero_samples
+    #[must_use]
+    pub fn decoded_sample_len(&self) -> Option<NonZeroU32> {
+        self.info.num_samples
+    }
+
     /// Returns the name of the stream, if it exists.
     #[must_use]
     pub fn name(&self) -> Option<&str> {
@@ -177,21 +612,328 @@ impl Stream {
         }
     }
 
+    /// Returns the name of the stream in a form that can safely be used as a file name (without an extension).
+    ///
+    /// The name is converted with [`sanitize_name`], and is only copied if any characters had to be changed.
+    /// If the stream has no name, `stream_{index}` is returned (e.g. `stream_0`), like the file names used by [`Bank::extract_to_dir`].
+    ///
+    /// [`sanitize_name`]: crate::sanitize_name
+    /// [`Bank::extract_to_dir`]: crate::Bank::extract_to_dir
+    #[must_use]
+    pub fn safe_file_stem(&self) -> Cow<'_, str> {
+        safe_file_stem(self.name(), self.index)
+    }
+
+    /// Returns the stream data stored in the sound bank, without any decoding.
+    ///
+    /// This is useful for debugging and for decoding formats that cannot be encoded yet.
+    /// The length of the data is always [`size`](Self::size), including for [truncated](Self::is_truncated) streams.
+    #[must_use]
+    pub fn raw_data(&self) -> &[u8] {
+        &self.data
+    }
+
     /// Encodes the stream data by writing audio samples to a writer.
     ///
     /// # Errors
     /// This function returns an error if the stream data could not be successfully written.
     /// See [`EncodeError`] for more information.
     pub fn write<W: Write>(self, sink: W) -> Result<W, EncodeError> {
+        self.write_with(sink, &EncodeOptions::default())
+    }
+
+    /// Encodes the stream data by writing audio samples to a writer, using the given options.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn write_with<W: Write>(self, sink: W, options: &EncodeOptions) -> Result<W, EncodeError> {
+        let mut reader = Reader::new(&*self.data);
+        encode(self.format, self.flags, &self.info, &mut reader, sink, options)
+    }
+
+    /// Encodes the stream data like [`Stream::write_with`], also returning the number of sample frames written.
+    ///
+    /// For PCM streams, frames are counted from the sample data that was actually written.
+    /// For Vorbis streams, frames are counted from the decoded audio blocks.
+    /// Comparing the count against [`Stream::sample_count`] can detect streams that were silently truncated.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn write_counted<W: Write>(
+        self,
+        sink: W,
+        options: &EncodeOptions,
+    ) -> Result<(W, u64), EncodeError> {
+        let mut reader = Reader::new(&*self.data);
+        encode_counted(self.format, self.flags, &self.info, &mut reader, sink, options)
+    }
+
+    /// Encodes the stream data like [`Stream::write`], but buffers writes internally.
+    ///
+    /// This is useful for unbuffered writers such as [`File`](std::fs::File), which would otherwise receive many small writes.
+    /// The buffer is flushed before the writer is returned, so there is no need to wrap the writer in a [`BufWriter`](std::io::BufWriter).
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be successfully written,
+    /// or if the buffered data could not be flushed.
+    /// See [`EncodeError`] for more information.
+    pub fn write_buffered<W: Write>(self, sink: W) -> Result<W, EncodeError> {
+        let mut reader = Reader::new(&*self.data);
+        encode_buffered(
+            self.format,
+            self.flags,
+            &self.info,
+            &mut reader,
+            sink,
+            &EncodeOptions::default(),
+        )
+    }
+
+    /// Encodes the stream data like [`Stream::write`], writing it to a new file at `path`.
+    ///
+    /// The file is created (or truncated if it already exists), and writes are buffered internally.
+    ///
+    /// # Errors
+    /// This function returns an error if the file could not be created,
+    /// or if the stream data could not be successfully written.
+    /// See [`EncodeError`] for more information.
+    pub fn write_to_path<P: AsRef<Path>>(self, path: P) -> Result<(), EncodeError> {
+        let file = File::create(path).map_err(EncodeError::CreateFile)?;
+        self.write_buffered(file).map(|_| ())
+    }
+
+    /// Decodes the stream and returns the peak amplitude of each channel, in channel order.
+    ///
+    /// Amplitudes are measured from the decoded samples, scaled so that full scale is 1.0.
+    /// Unlike peak volume metadata stored in the sound bank, this always reflects the actual stream data.
+    /// Only PCM and Vorbis streams can be measured.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be decoded.
+    /// See [`DecodeError`] for more information.
+    pub fn measure_peaks(&self) -> Result<Vec<f32>, DecodeError> {
+        self.measure_peaks_with(&EncodeOptions::default())
+    }
+
+    /// Decodes the stream and returns the peak amplitude of each channel like [`Stream::measure_peaks`], using the given options.
+    ///
+    /// Vorbis streams are decoded with the setup headers and setup header CRC32 in `options`, like [`Stream::write_with`];
+    /// other options are ignored.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be decoded.
+    /// See [`DecodeError`] for more information.
+    pub fn measure_peaks_with(&self, options: &EncodeOptions) -> Result<Vec<f32>, DecodeError> {
+        let mut reader = Reader::new(&*self.data);
+        Ok(measure_peaks(
+            self.format,
+            self.flags,
+            &self.info,
+            &mut reader,
+            options,
+        )?)
+    }
+
+    /// Decodes the stream and measures its integrated loudness, in LUFS (loudness units relative to full scale).
+    ///
+    /// Loudness is measured as specified in ITU-R BS.1770, with K-weighting and gating over 400 ms blocks.
+    /// This is useful for checking that streams (e.g. dialogue) have consistent loudness.
+    /// Streams that are silent or shorter than 400 ms have a loudness of negative infinity.
+    /// Only PCM and Vorbis streams can be measured.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be decoded.
+    /// See [`DecodeError`] for more information.
+    #[cfg(feature = "loudness")]
+    pub fn measure_lufs(&self) -> Result<f32, DecodeError> {
+        self.measure_lufs_with(&EncodeOptions::default())
+    }
+
+    /// Decodes the stream and measures its integrated loudness like [`Stream::measure_lufs`], using the given options.
+    ///
+    /// Vorbis streams are decoded with the setup headers and setup header CRC32 in `options`, like [`Stream::write_with`];
+    /// other options are ignored.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be decoded.
+    /// See [`DecodeError`] for more information.
+    #[cfg(feature = "loudness")]
+    pub fn measure_lufs_with(&self, options: &EncodeOptions) -> Result<f32, DecodeError> {
+        let mut reader = Reader::new(&*self.data);
+        Ok(measure_loudness(
+            self.format,
+            self.flags,
+            &self.info,
+            &mut reader,
+            options,
+        )?)
+    }
+
+    /// Returns the linear gain that brings the peak amplitude of the stream to `target_dbfs` (decibels relative to full scale).
+    ///
+    /// The [stored peak volume](Self::peak_volume) is used if it exists; otherwise, the peak is [measured](Self::measure_peaks)
+    /// from the stream data. Multiplying every sample by the gain normalizes the stream; for example, a target of 0.0 dBFS
+    /// makes the loudest sample reach full scale.
+    ///
+    /// `None` is returned if the stream is silent, or if there is no stored peak volume and the stream data could not be decoded.
+    #[must_use]
+    pub fn normalization_gain(&self, target_dbfs: f32) -> Option<f32> {
+        let peak = match self.peak_volume() {
+            Some(peak) => peak,
+            None => self.measure_peaks().ok()?.into_iter().fold(0.0, f32::max),
+        };
+
+        (peak > 0.0).then(|| 10f32.powf(target_dbfs / 20.0) / peak)
+    }
+
+    /// Decodes a stereo stream and compares its left and right channels, returning `None` for other channel counts.
+    ///
+    /// This helps diagnose stereo streams stored with an inverted or shifted channel.
+    /// See [`ChannelCorrelation`] for more information.
+    /// Only PCM and Vorbis streams can be compared.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be decoded.
+    /// See [`DecodeError`] for more information.
+    pub fn stereo_correlation(&self) -> Result<Option<ChannelCorrelation>, DecodeError> {
+        self.stereo_correlation_with(&EncodeOptions::default())
+    }
+
+    /// Decodes a stereo stream and compares its channels like [`Stream::stereo_correlation`], using the given options.
+    ///
+    /// Vorbis streams are decoded with the setup headers and setup header CRC32 in `options`, like [`Stream::write_with`];
+    /// other options are ignored.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be decoded.
+    /// See [`DecodeError`] for more information.
+    pub fn stereo_correlation_with(
+        &self,
+        options: &EncodeOptions,
+    ) -> Result<Option<ChannelCorrelation>, DecodeError> {
         let mut reader = Reader::new(&*self.data);
-        encode(self.format, self.flags, &self.info, &mut reader, sink)
+        Ok(stereo_correlation(
+            self.format,
+            self.flags,
+            &self.info,
+            &mut reader,
+            options,
+        )?)
+    }
+
+    /// Decodes the stream and returns whether every channel has the same samples ("dual mono").
+    ///
+    /// Samples are compared after decoding, scaled so that full scale is 1.0;
+    /// samples that differ by at most `tolerance` are considered the same.
+    /// A small tolerance is useful for lossy formats like Vorbis, where identical channels can decode slightly differently.
+    /// Streams with a single channel are not dual mono.
+    /// Only PCM and Vorbis streams can be checked.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be decoded.
+    /// See [`DecodeError`] for more information.
+    pub fn is_dual_mono(&self, tolerance: f32) -> Result<bool, DecodeError> {
+        self.is_dual_mono_with(tolerance, &EncodeOptions::default())
+    }
+
+    /// Decodes the stream and returns whether every channel has the same samples like [`Stream::is_dual_mono`],
+    /// using the given options.
+    ///
+    /// Vorbis streams are decoded with the setup headers and setup header CRC32 in `options`, like [`Stream::write_with`];
+    /// other options are ignored.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be decoded.
+    /// See [`DecodeError`] for more information.
+    pub fn is_dual_mono_with(
+        &self,
+        tolerance: f32,
+        options: &EncodeOptions,
+    ) -> Result<bool, DecodeError> {
+        let mut reader = Reader::new(&*self.data);
+        Ok(is_dual_mono(
+            self.format,
+            self.flags,
+            &self.info,
+            tolerance,
+            &mut reader,
+            options,
+        )?)
+    }
+
+    /// Converts the stream into a reader that yields the encoded stream data (e.g. a WAVE file for PCM streams).
+    ///
+    /// This is useful for passing the encoded stream to APIs that consume a reader instead of writing to a writer.
+    /// See [`PcmReader`] for more information.
+    #[must_use]
+    pub fn into_pcm_reader(self) -> PcmReader {
+        PcmReader::new(self)
+    }
+
+    /// Writes the stream data to a writer as-is, without any encoding or container.
+    ///
+    /// This works for every [`AudioFormat`], including formats that cannot be encoded yet.
+    ///
+    /// # Errors
+    /// This function returns an error if the stream data could not be successfully copied.
+    /// See [`EncodeError`] for more information.
+    pub fn write_raw<W: Write>(self, sink: W) -> Result<W, EncodeError> {
+        let mut reader = Reader::new(&*self.data);
+        write_raw(&self.info, &mut reader, sink)
+    }
+}
+
+/// A reader over the encoded data of a [`Stream`].
+///
+/// This type is returned from [`Stream::into_pcm_reader`].
+/// The stream is encoded the first time data is read; the output is the same as [`Stream::write`] (e.g. a WAVE file for PCM streams).
+/// If encoding fails, reading returns an I/O error wrapping the [`EncodeError`].
+#[derive(Debug)]
+pub struct PcmReader {
+    state: PcmReaderState,
+}
+
+#[derive(Debug)]
+enum PcmReaderState {
+    Pending(Stream),
+    Encoded(Cursor<Vec<u8>>),
+    Failed,
+}
+
+impl PcmReader {
+    fn new(stream: Stream) -> Self {
+        Self {
+            state: PcmReaderState::Pending(stream),
+        }
+    }
+}
+
+impl Read for PcmReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        match replace(&mut self.state, PcmReaderState::Failed) {
+            PcmReaderState::Pending(stream) => {
+                let data = stream.write(Vec::new()).map_err(IoError::other)?;
+                self.state = PcmReaderState::Encoded(Cursor::new(data));
+                self.read(buf)
+            }
+            PcmReaderState::Encoded(mut data) => {
+                let n = data.read(buf)?;
+                self.state = PcmReaderState::Encoded(data);
+                Ok(n)
+            }
+            PcmReaderState::Failed => Err(IoError::other("stream data could not be encoded")),
+        }
     }
 }
 
 /// An iterator over sound bank streams.
 ///
 /// This type is returned from [`Bank::into_iter`].
-/// When iterating, `Some(Stream)` is returned if a stream was successfully read from the sound bank, and `None` otherwise.
+/// When iterating, `Ok(Stream)` is returned if a stream was successfully read from the sound bank,
+/// and `Err(DecodeError)` is returned if its data could not be read (e.g. because the file was truncated).
+/// Iteration stops after the first error, since the position of later streams is unknown.
 ///
 /// [`Bank::into_iter`]: crate::Bank::into_iter
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -201,6 +943,8 @@ pub struct StreamIntoIter<R: Read> {
     flags: u32,
     info: Box<[StreamInfo]>,
     reader: Reader<R>,
+    allow_truncated: bool,
+    failed: bool,
 }
 
 impl<R: Read> StreamIntoIter<R> {
@@ -216,41 +960,184 @@ impl<R: Read> StreamIntoIter<R> {
             flags,
             info,
             reader,
+            allow_truncated: false,
+            failed: false,
+        }
+    }
+
+    /// Sets whether a stream whose data ends early is returned instead of an error.
+    ///
+    /// By default, an error is returned for a stream whose data couldn't be fully read (e.g. because the file was truncated).
+    /// When enabled, such a stream is returned with the bytes that could be read, and [`Stream::is_truncated`] returns `true`.
+    /// An error is still returned if none of the stream's data could be read,
+    /// or if the reader fails with an I/O error before reaching the end of its data.
+    /// Iteration ends after a truncated stream is returned, since the data of later streams can't be read.
+    /// This is useful for salvaging partially downloaded files.
+    #[must_use]
+    pub fn allow_truncated(mut self, value: bool) -> Self {
+        self.allow_truncated = value;
+        self
+    }
+
+    fn read_stream(&mut self, index: u32, info: StreamInfo) -> Result<Stream, DecodeError> {
+        let size = info.size.get() as usize;
+        let start_pos = self.reader.position();
+
+        let stream = if self.allow_truncated {
+            self.read_truncated(index, info)?
+        } else {
+            let data = self
+                .reader
+                .take_boxed(size)
+                .map_err(HeaderError::factory(HeaderErrorKind::StreamData { index }))?;
+            Stream::new(index, self.format, self.flags, info, data)
+        };
+
+        if !stream.truncated {
+            self.reader
+                .advance_to_offset(start_pos, size)
+                .map_err(HeaderError::factory(HeaderErrorKind::StreamData { index }))?;
+        }
+
+        Ok(stream)
+    }
+
+    fn read_truncated(&mut self, index: u32, mut info: StreamInfo) -> Result<Stream, DecodeError> {
+        let size = info.size.get() as usize;
+
+        let mut data = Vec::new();
+        // Only the end of the data counts as truncation; other I/O errors are reported.
+        match self.reader.limit(size).read_to_end(&mut data) {
+            Err(e) if e.kind() != ErrorKind::UnexpectedEof => {
+                return Err(
+                    HeaderError::new_with_io(HeaderErrorKind::StreamData { index }, e).into()
+                );
+            }
+            _ => {}
+        }
+
+        let truncated = data.len() < size;
+        info.size = u32::try_from(data.len())
+            .ok()
+            .and_then(NonZeroU32::new)
+            .ok_or_else(|| HeaderError::new(HeaderErrorKind::StreamData { index }))?;
+
+        let mut stream = Stream::new(index, self.format, self.flags, info, data.into_boxed_slice());
+        stream.truncated = truncated;
+        Ok(stream)
+    }
+
+    fn remaining(&self) -> usize {
+        if self.failed {
+            0
+        } else {
+            self.info.len().saturating_sub(self.index as usize)
         }
     }
 }
 
 impl<R: Read> Iterator for StreamIntoIter<R> {
-    type Item = Stream;
+    type Item = Result<Stream, DecodeError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let stream = self.info.get(self.index as usize).cloned().and_then(|info| {
-            let size = info.size.get() as usize;
-            let start_pos = self.reader.position();
-
-            let stream =
-                self.reader.take(size).ok().map(|data| {
-                    Stream::new(self.format, self.flags, info, data.into_boxed_slice())
-                });
-
-            self.reader.advance_to(start_pos + size).ok()?;
+        if self.failed {
+            return None;
+        }
 
-            stream
-        });
+        let info = self.info.get(self.index as usize).cloned()?;
+        let stream = self.read_stream(self.index, info);
 
         self.index += 1;
+        // a truncated stream means the reader reached its end, so no later streams can be read
+        self.failed = stream.as_ref().map_or(true, Stream::is_truncated);
 
-        stream
+        Some(stream)
     }
 
+    // Every remaining stream is returned unless an error occurs, which ends iteration early.
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let len = self.info.len();
-        (len, Some(len))
+        let remaining = self.remaining();
+        (remaining.min(1), Some(remaining))
     }
 }
 
-impl<R: Read> ExactSizeIterator for StreamIntoIter<R> {
-    fn len(&self) -> usize {
-        self.info.len()
+/// An iterator over sound bank streams that borrows the sound bank.
+///
+/// This type is returned from [`Bank::iter`]. Each stream is read into memory as a [`Stream`],
+/// because a [`LazyStream`] holds the underlying reader and only one can exist at a time.
+/// The underlying reader seeks to the start of every stream before reading it,
+/// so the sound bank can be iterated more than once, and previously read streams don't affect iteration.
+/// Like [`StreamIntoIter`], iteration stops after the first error.
+///
+/// [`Bank::iter`]: crate::Bank::iter
+#[derive(Debug, PartialEq, Eq)]
+pub struct StreamIter<'bank, R: Read> {
+    index: u32,
+    format: AudioFormat,
+    flags: u32,
+    info: &'bank [StreamInfo],
+    offsets: &'bank [usize],
+    reader: &'bank mut Reader<R>,
+    failed: bool,
+}
+
+impl<'bank, R: Read + Seek> StreamIter<'bank, R> {
+    pub(crate) fn new(
+        format: AudioFormat,
+        flags: u32,
+        info: &'bank [StreamInfo],
+        offsets: &'bank [usize],
+        reader: &'bank mut Reader<R>,
+    ) -> Self {
+        Self {
+            index: 0,
+            format,
+            flags,
+            info,
+            offsets,
+            reader,
+            failed: false,
+        }
+    }
+
+    fn read_stream(&mut self, index: u32, info: &StreamInfo) -> Result<Stream, DecodeError> {
+        self.reader
+            .seek_to(self.offsets[index as usize])
+            .map_err(|e| HeaderError::new_with_io(HeaderErrorKind::StreamData { index }, e))?;
+
+        let data = self
+            .reader
+            .take_boxed(info.size.get() as usize)
+            .map_err(HeaderError::factory(HeaderErrorKind::StreamData { index }))?;
+
+        Ok(Stream::new(index, self.format, self.flags, info.clone(), data))
+    }
+}
+
+impl<R: Read + Seek> Iterator for StreamIter<'_, R> {
+    type Item = Result<Stream, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let info = self.info.get(self.index as usize)?;
+        let stream = self.read_stream(self.index, info);
+
+        self.index += 1;
+        self.failed = stream.is_err();
+
+        Some(stream)
+    }
+
+    // Every remaining stream is returned unless an error occurs, which ends iteration early.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = if self.failed {
+            0
+        } else {
+            self.info.len().saturating_sub(self.index as usize)
+        };
+        (remaining.min(1), Some(remaining))
     }
 }
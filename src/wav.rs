@@ -0,0 +1,141 @@
+//! Utilities for post-processing WAVE files written by this library.
+
+use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Seek, SeekFrom, Write};
+
+/// Recomputes the `RIFF` and `data` chunk sizes of a WAVE file from its actual content, then patches them.
+///
+/// This is useful after splicing extra data into a WAVE file written by this library, which leaves the sizes stale.
+/// The file is expected to start at the current position of `file`, and to end at the end of `file`.
+///
+/// The `data` chunk is assumed to extend to the end of the file, unless the bytes after its stored size
+/// form a sequence of complete chunks (e.g. the `cue ` and `LIST` chunks written by [`Bank::write_combined_wav`]).
+/// When this function returns successfully, `file` is positioned at the end of the file.
+///
+/// # Errors
+/// This function returns an error if the file is not a WAVE file, if it has no `data` chunk,
+/// if it is too large to be described by 32-bit sizes, or if an I/O error occurs.
+///
+/// [`Bank::write_combined_wav`]: crate::Bank::write_combined_wav
+pub fn fix_sizes<S: Read + Write + Seek>(file: &mut S) -> IoResult<()> {
+    let file_start = file.stream_position()?;
+    let file_end = file.seek(SeekFrom::End(0))?;
+    let _ = file.seek(SeekFrom::Start(file_start))?;
+
+    let mut header = [0; 12];
+    file.read_exact(&mut header)?;
+
+    if &header[..4] != b"RIFF" || &header[8..] != b"WAVE" {
+        return Err(invalid_data("file is not a WAVE file"));
+    }
+
+    let riff_size = file_end - file_start - 8;
+    let data_start = find_data_chunk(file, file_end)?;
+
+    let mut size = [0; 4];
+    let _ = file.seek(SeekFrom::Start(data_start - 4))?;
+    file.read_exact(&mut size)?;
+    let stored_end = data_start + u64::from(u32::from_le_bytes(size));
+
+    let data_end =
+        if stored_end < file_end && chunks_fit(file, stored_end + stored_end % 2, file_end)? {
+            stored_end
+        } else {
+            file_end
+        };
+
+    let _ = file.seek(SeekFrom::Start(file_start + 4))?;
+    file.write_all(&to_u32(riff_size)?.to_le_bytes())?;
+    let _ = file.seek(SeekFrom::Start(data_start - 4))?;
+    file.write_all(&to_u32(data_end - data_start)?.to_le_bytes())?;
+    let _ = file.seek(SeekFrom::End(0))?;
+
+    Ok(())
+}
+
+// Walks the chunks after the `WAVE` identifier and returns the position of the `data` chunk's contents.
+fn find_data_chunk<S: Read + Seek>(file: &mut S, file_end: u64) -> IoResult<u64> {
+    let mut position = file.stream_position()?;
+
+    while position + 8 <= file_end {
+        let (id, size) = read_chunk_header(file)?;
+        position += 8;
+
+        if &id == b"data" {
+            return Ok(position);
+        }
+
+        // chunks start at even offsets
+        position += u64::from(size) + u64::from(size % 2);
+        let _ = file.seek(SeekFrom::Start(position))?;
+    }
+
+    Err(invalid_data("WAVE file has no data chunk"))
+}
+
+// Returns whether the bytes between `start` and `end` form a sequence of complete chunks.
+fn chunks_fit<S: Read + Seek>(file: &mut S, start: u64, end: u64) -> IoResult<bool> {
+    let mut position = start;
+    let _ = file.seek(SeekFrom::Start(position))?;
+
+    while position + 8 <= end {
+        let (_, size) = read_chunk_header(file)?;
+        position += 8 + u64::from(size) + u64::from(size % 2);
+        let _ = file.seek(SeekFrom::Start(position))?;
+    }
+
+    Ok(position == end)
+}
+
+fn read_chunk_header<R: Read>(file: &mut R) -> IoResult<([u8; 4], u32)> {
+    let mut header = [0; 8];
+    file.read_exact(&mut header)?;
+
+    let [a, b, c, d, s0, s1, s2, s3] = header;
+    Ok(([a, b, c, d], u32::from_le_bytes([s0, s1, s2, s3])))
+}
+
+fn to_u32(size: u64) -> IoResult<u32> {
+    u32::try_from(size).map_err(|_| invalid_data("WAVE file is larger than 4 GiB"))
+}
+
+fn invalid_data(message: &str) -> IoError {
+    IoError::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::fix_sizes;
+    use std::io::Cursor;
+
+    fn wave_file(riff_size: u32, data_size: u32, data: &[u8], trailer: &[u8]) -> Vec<u8> {
+        let mut file = Vec::new();
+        file.extend_from_slice(b"RIFF");
+        file.extend_from_slice(&riff_size.to_le_bytes());
+        file.extend_from_slice(b"WAVEfmt ");
+        file.extend_from_slice(&16u32.to_le_bytes());
+        file.extend_from_slice(&[0; 16]);
+        file.extend_from_slice(b"data");
+        file.extend_from_slice(&data_size.to_le_bytes());
+        file.extend_from_slice(data);
+        file.extend_from_slice(trailer);
+        file
+    }
+
+    #[test]
+    fn fix_stale_sizes() {
+        let mut file = Cursor::new(wave_file(40, 4, &[1; 10], &[]));
+        fix_sizes(&mut file).unwrap();
+        assert_eq!(file.into_inner(), wave_file(46, 10, &[1; 10], &[]));
+
+        let trailer = b"cue \x04\x00\x00\x00abcd";
+        let mut file = Cursor::new(wave_file(0, 4, &[1; 4], trailer));
+        fix_sizes(&mut file).unwrap();
+        assert_eq!(file.into_inner(), wave_file(52, 4, &[1; 4], trailer));
+    }
+
+    #[test]
+    fn reject_non_wave_files() {
+        let mut file = Cursor::new(b"RIFF\x04\x00\x00\x00AVI ".to_vec());
+        assert!(fix_sizes(&mut file).is_err());
+    }
+}